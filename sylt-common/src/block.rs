@@ -1,6 +1,7 @@
 use colored::Colorize;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use sylt_tokenizer::Span;
 
 use crate::{Op, Type, Value};
 
@@ -21,8 +22,8 @@ pub struct Block {
     pub name: String,
     pub file: PathBuf,
     pub ops: Vec<Op>,
-    pub last_line_offset: usize,
-    pub line_offsets: HashMap<usize, usize>,
+    pub last_span: Span,
+    pub span_offsets: HashMap<usize, Span>,
 }
 
 impl Block {
@@ -37,8 +38,8 @@ impl Block {
             name: String::from(name),
             file: file.to_owned(),
             ops: Vec::new(),
-            last_line_offset: 0,
-            line_offsets: HashMap::new(),
+            last_span: Span::zero(),
+            span_offsets: HashMap::new(),
         }
     }
 
@@ -58,20 +59,25 @@ impl Block {
         }
     }
 
-    pub fn add_line(&mut self, token_position: usize) {
-        if token_position != self.last_line_offset {
-            self.line_offsets.insert(self.curr(), token_position);
-            self.last_line_offset = token_position;
+    pub fn add_span(&mut self, span: Span) {
+        if span != self.last_span {
+            self.span_offsets.insert(self.curr(), span);
+            self.last_span = span;
         }
     }
 
-    pub fn line(&self, ip: usize) -> usize {
+    /// The span of the code the op at `ip` was compiled from.
+    pub fn span(&self, ip: usize) -> Span {
         for i in (0..=ip).rev() {
-            if let Some(line) = self.line_offsets.get(&i) {
-                return *line;
+            if let Some(span) = self.span_offsets.get(&i) {
+                return *span;
             }
         }
-        0
+        Span::zero()
+    }
+
+    pub fn line(&self, ip: usize) -> usize {
+        self.span(ip).line
     }
 
     pub fn debug_print(&self, constants: Option<&[Value]>) {
@@ -80,8 +86,8 @@ impl Block {
             #[rustfmt::skip]
             println!(
                 "{}{:05} {:?}{}",
-                if self.line_offsets.contains_key(&i) {
-                    format!("{:5} ", self.line_offsets[&i].to_string().blue())
+                if self.span_offsets.contains_key(&i) {
+                    format!("{:5} ", self.span_offsets[&i].line.to_string().blue())
                 } else {
                     format!("    {} ", "|".blue())
                 },
@@ -98,9 +104,9 @@ impl Block {
         println!();
     }
 
-    pub fn add(&mut self, op: Op, token_position: usize) -> usize {
+    pub fn add(&mut self, op: Op, span: Span) -> usize {
         let len = self.curr();
-        self.add_line(token_position);
+        self.add_span(span);
         self.ops.push(op);
         len
     }
@@ -1,3 +1,4 @@
+use crate::ty::Numbered;
 use crate::{Op, Type, Value};
 
 use colored::Colorize;
@@ -50,7 +51,12 @@ fn file_line_display(file: &Path, line: usize) -> String {
     )
 }
 
-#[derive(Debug, Clone)]
+fn error_header(kind: &str, error: &Error) -> String {
+    format!("{}[{}]", kind, error.code()).red().to_string()
+}
+
+/// New variants go last, the error codes are derived from the order.
+#[derive(Debug, Clone, sylt_macro::Numbered)]
 pub enum RuntimeError {
     FieldTypeMismatch(String, String, Type, Type, String),
     TypeError(Op, Vec<Type>),
@@ -60,10 +66,12 @@ pub enum RuntimeError {
     ArgumentType(Vec<Type>, Vec<Type>, String),
     IndexError(Value, Type),
 
-    /// (External function, parameters)
-    ExternTypeMismatch(String, Vec<Type>),
+    /// (External function, parameters, call site)
+    ExternTypeMismatch(String, Vec<Type>, Span),
     ExternError(String, String),
     ValueError(Op, Vec<Value>),
+    /// The integer operation doesn't fit in an `int`.
+    IntegerOverflow(Op, Vec<Value>),
     UnknownField(String, String),
     ImmutableField(String),
     ArgumentCount(usize, usize),
@@ -71,9 +79,42 @@ pub enum RuntimeError {
     /// (Indexed value, length, index)
     IndexOutOfBounds(Value, usize, usize),
 
+    /// (Unpacked value, number of targets)
+    UnpackMismatch(Value, usize),
+
+    /// (Called function, call depth)
+    StackOverflow(String, usize),
+
+    /// (Asserted type, actual type)
+    TypeAssertion(Type, Type),
+
     AssertFailed,
+    /// (Line of the assert, message)
+    AssertionFailed(usize, Option<String>),
     InvalidProgram,
     Unreachable,
+    /// (Maximum number of loop iterations)
+    ExecutionLimitExceeded(usize),
+    /// (What tried to change the range)
+    ImmutableRange(String),
+}
+
+/// How bad a diagnostic is. Warnings don't stop the program unless
+/// `--deny-warnings` is passed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A function that was running when a runtime error happened.
+#[derive(Debug, Clone)]
+pub struct TraceFrame {
+    pub function: String,
+    pub file: PathBuf,
+    /// Where the function was, which is the call for all but the innermost
+    /// frame.
+    pub line: usize,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -91,7 +132,8 @@ impl fmt::Display for RuntimePhase {
     }
 }
 
-#[derive(Debug, Clone)]
+/// New variants go last, the error codes are derived from the order.
+#[derive(Debug, Clone, sylt_macro::Numbered)]
 pub enum TypeError {
     Violating(Type),
 
@@ -147,11 +189,16 @@ pub enum TypeError {
     },
 
     UnresolvedName(String),
+
+    EmptyCollection(Type),
+
+    UninferableGeneric(String),
 }
 
 
 // TODO(ed): Switch to spans for the whole compiler?
-#[derive(Clone, Debug)]
+/// New variants go last, the error codes are derived from the order.
+#[derive(Clone, Debug, sylt_macro::Numbered)]
 pub enum Error {
     NoFileGiven,
     FileNotFound(PathBuf),
@@ -186,28 +233,78 @@ pub enum Error {
         file: PathBuf,
         line: usize,
         message: Option<String>,
+        /// The functions that were running, innermost first.
+        trace: Vec<TraceFrame>,
     },
 
     LuaError(String),
+
+    /// Something that is allowed, but most likely a mistake.
+    Warning {
+        file: PathBuf,
+        span: Span,
+        message: String,
+    },
+}
+
+impl Error {
+    /// A stable code for the kind of error, e.g. `E0103`. Pass it to
+    /// `--explain` for a longer description.
+    pub fn code(&self) -> String {
+        let number = match self {
+            Error::TypeError { kind, .. } => 100 + kind.to_number(),
+            Error::RuntimeError { kind, .. } => 200 + kind.to_number(),
+            error => error.to_number(),
+        };
+        format!("E{:04}", number)
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            Error::Warning { .. } => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+/// Returns the long description of an error code, if there is one.
+pub fn explain(code: &str) -> Option<&'static str> {
+    let code = code.trim();
+    let digits = code.strip_prefix('E').or_else(|| code.strip_prefix('e'))?;
+    if digits.len() != 4 {
+        return None;
+    }
+    let number: usize = digits.parse().ok()?;
+    match number {
+        0..=99 => ERROR_EXPLANATIONS.get(number),
+        100..=199 => TYPE_ERROR_EXPLANATIONS.get(number - 100),
+        200..=299 => RUNTIME_ERROR_EXPLANATIONS.get(number - 200),
+        _ => None,
+    }
+    .copied()
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Error::NoFileGiven => {
+                write!(f, "{}: ", error_header("error", self))?;
                 write!(f, "No file to run")
             }
             Error::LuaError(stderr) => {
+                write!(f, "{}: ", error_header("error", self))?;
                 write!(f, "Lua failed to run, \n:stderr:\n{}", stderr)
             }
             Error::FileNotFound(path) => {
+                write!(f, "{}: ", error_header("error", self))?;
                 write!(f, "File '{}' not found", path.display())
             }
             Error::IOError(e) => {
+                write!(f, "{}: ", error_header("error", self))?;
                 write!(f, "Unknown IO error: {}", e)
             }
             Error::GitConflictError { file, span } => {
-                write!(f, "{}: ", "git conflict error".red())?;
+                write!(f, "{}: ", error_header("git conflict error", self))?;
                 write!(f, "{}\n", file_line_display(file, span.line))?;
                 write!(
                     f,
@@ -218,22 +315,35 @@ impl fmt::Display for Error {
                 write_source_span_at(f, file, *span)
             }
             #[rustfmt::skip]
-            Error::RuntimeError { kind, phase, file, line, message } => {
-                write!(f, "{} {}: ", phase.to_string().red(), "error".red())?;
+            Error::RuntimeError { kind, phase, file, line, message, trace } => {
+                write!(f, "{}: ", error_header(&format!("{} error", phase), self))?;
                 write!(f, "{}\n", file_line_display(file, *line))?;
                 write!(f, "{}{}\n", INDENT, kind)?;
                 if let Some(message) = message {
                     write!(f, "{}\n", message)?;
                 }
 
-                write_source_line_at(f, file, *line)
+                write_source_line_at(f, file, *line)?;
+                if !trace.is_empty() {
+                    writeln!(f, "{}Stack trace, innermost first:", INDENT)?;
+                }
+                for frame in trace.iter() {
+                    writeln!(
+                        f,
+                        "{}  {} in {}",
+                        INDENT,
+                        file_line_display(&frame.file, frame.line),
+                        frame.function,
+                    )?;
+                }
+                Ok(())
             }
             Error::SyntaxError {
                 file,
                 span,
                 message,
             } => {
-                write!(f, "{}: ", "syntax error".red())?;
+                write!(f, "{}: ", error_header("syntax error", self))?;
                 write!(f, "{}\n", file_line_display(file, span.line))?;
                 write!(f, "{}Syntax Error on line {}\n", INDENT, span.line)?;
 
@@ -247,7 +357,7 @@ impl fmt::Display for Error {
                 span,
                 message,
             } => {
-                write!(f, "{}: {}\n", "typecheck error".red(), file_line_display(file, span.line))?;
+                write!(f, "{}: {}\n", error_header("typecheck error", self), file_line_display(file, span.line))?;
                 write!(f, "{}{}\n", INDENT, kind)?;
 
                 if let Some(message) = message {
@@ -261,7 +371,7 @@ impl fmt::Display for Error {
                 span,
                 message,
             } => {
-                write!(f, "{}: ", "compile error".red())?;
+                write!(f, "{}: ", error_header("compile error", self))?;
                 write!(f, "{}\n", file_line_display(file, span.line))?;
                 write!(f, "{}Failed to compile line {}\n", INDENT, span.line)?;
 
@@ -269,6 +379,17 @@ impl fmt::Display for Error {
                     write!(f, "{}{}\n", INDENT, message)?;
                 }
 
+                write_source_span_at(f, file, *span)
+            }
+            Error::Warning {
+                file,
+                span,
+                message,
+            } => {
+                write!(f, "{}[{}]: ", "warning".yellow(), self.code().yellow())?;
+                writeln!(f, "{}", file_line_display(file, span.line))?;
+                writeln!(f, "{}{}", INDENT, message)?;
+
                 write_source_span_at(f, file, *span)
             }
         }
@@ -316,11 +437,11 @@ impl fmt::Display for RuntimeError {
             RuntimeError::IndexError(value, slot) => {
                 write!(f, "Cannot index value '{:?}' with type '{:?}'", value, slot)
             }
-            RuntimeError::ExternTypeMismatch(name, types) => {
+            RuntimeError::ExternTypeMismatch(name, types, span) => {
                 write!(
                     f,
-                    "Extern function '{}' doesn't accept argument(s) with type(s) {:?}",
-                    name, types
+                    "Extern function '{}' doesn't accept argument(s) with type(s) {:?}, called at {}:{}",
+                    name, types, span.line, span.col_start
                 )
             }
             RuntimeError::ExternError(fun, msg) => {
@@ -332,6 +453,12 @@ impl fmt::Display for RuntimeError {
                     .fold(String::new(), |a, v| format!("{}{:?}, ", a, v));
                 write!(f, "Cannot apply {:?} to values {}", op, values)
             }
+            RuntimeError::IntegerOverflow(op, values) => {
+                let values = values
+                    .iter()
+                    .fold(String::new(), |a, v| format!("{}{:?}, ", a, v));
+                write!(f, "Integer overflow when applying {:?} to values {}", op, values)
+            }
             RuntimeError::UnknownField(obj, field) => {
                 write!(f, "Cannot find field '{}' on blob {:?}", field, obj)
             }
@@ -345,6 +472,15 @@ impl fmt::Display for RuntimeError {
                     expected, given
                 )
             }
+            RuntimeError::UnpackMismatch(value, targets) => {
+                write!(f, "Cannot unpack {:?} into {} targets", value, targets)
+            }
+            RuntimeError::TypeAssertion(expected, got) => {
+                write!(f, "Expected a value of type '{:?}' but got a '{:?}'", expected, got)
+            }
+            RuntimeError::StackOverflow(name, depth) => {
+                write!(f, "Stack overflow when calling '{}' at call depth {}", name, depth)
+            }
             RuntimeError::IndexOutOfBounds(value, len, slot) => {
                 write!(
                     f,
@@ -355,12 +491,24 @@ impl fmt::Display for RuntimeError {
             RuntimeError::AssertFailed => {
                 write!(f, "Assertion failed")
             }
+            RuntimeError::AssertionFailed(line, Some(message)) => {
+                write!(f, "Assertion on line {} failed: {}", line, message)
+            }
+            RuntimeError::AssertionFailed(line, None) => {
+                write!(f, "Assertion on line {} failed", line)
+            }
             RuntimeError::InvalidProgram => {
                 write!(f, "{}", "[!!] Invalid program [!!]".bold())
             }
             RuntimeError::Unreachable => {
                 write!(f, "Reached unreachable code")
             }
+            RuntimeError::ExecutionLimitExceeded(max) => {
+                write!(f, "Loops ran more than the allowed {} iterations", max)
+            }
+            RuntimeError::ImmutableRange(by) => {
+                write!(f, "Ranges cannot be changed, but {} tried to", by)
+            }
         }
     }
 }
@@ -392,6 +540,10 @@ impl fmt::Display for TypeError {
                 write!(f, "Got '{:?}', which it cannot be", ty)
             }
 
+            TypeError::EmptyCollection(ty) => {
+                write!(f, "Cannot tell what '{:?}' contains, since it's empty", ty)
+            }
+
             TypeError::ExcessiveForce { got, expected } => {
                 write!(f, "This type force is unnessecary, '{:?}' is a '{:?}'", got, expected)
             }
@@ -415,10 +567,319 @@ impl fmt::Display for TypeError {
             TypeError::UnresolvedName(name) => {
                 write!(f, "Cannot resolve name '{}'", name)
             }
+            TypeError::UninferableGeneric(name) => {
+                write!(f, "Cannot infer the type of '#{}'", name)
+            }
         }
     }
 }
 
+// The explanations are indexed by the error code, so they have to be kept in
+// the same order as the variants.
+
+static ERROR_EXPLANATIONS: [&str; 10] = [
+    // E0000 - NoFileGiven
+    "No file was given to the compiler.
+
+Sylt needs a file with a `start` function to run, e.g.
+
+    sylt hello.sy
+",
+    // E0001 - FileNotFound
+    "A file, or a module that was `use`d, could not be found.
+
+Modules are looked up relative to the file that uses them, so
+
+    use some_module
+
+in `src/main.sy` requires the file `src/some_module.sy`.
+",
+    // E0002 - IOError
+    "The operating system failed to read or write a file.
+
+Check that the file exists and that it can be read.
+",
+    // E0003 - GitConflictError
+    "A git conflict marker was found in the source.
+
+The file contains an unresolved merge conflict, resolve it and remove the
+`<<<<<<<`, `=======` and `>>>>>>>` lines.
+",
+    // E0004 - SyntaxError
+    "The source could not be parsed.
+
+The message says what the parser expected, e.g.
+
+    a := (1 + 2
+
+is missing a closing parenthesis.
+",
+    // E0005 - TypeError
+    "A type error. Type errors are reported with codes from E0100 and up,
+which describe the specific kind of error.
+",
+    // E0006 - CompileError
+    "The program is well typed but could not be compiled.
+
+This usually means something is used where it isn't allowed, e.g.
+
+    start :: fn do
+        break
+    end
+
+breaks out of a loop that doesn't exist.
+",
+    // E0007 - RuntimeError
+    "A runtime error. Runtime errors are reported with codes from E0200 and
+up, which describe the specific kind of error.
+",
+    // E0008 - LuaError
+    "The program compiled to Lua, but Lua failed to run it.
+
+The output from Lua is included in the error.
+",
+    // E0009 - Warning
+    "Something that is allowed, but most likely a mistake.
+
+Warnings don't stop the program from running.
+",
+];
+
+static TYPE_ERROR_EXPLANATIONS: [&str; 15] = [
+    // E0100 - Violating
+    "A value has a type that it is not allowed to have here.
+",
+    // E0101 - BinOp
+    "A binary operator is used on types it isn't defined for, e.g.
+
+    a := 1 + \"one\"
+
+since `+` cannot add an `int` and a `str`.
+",
+    // E0102 - UniOp
+    "A unary operator is used on a type it isn't defined for, e.g.
+
+    a := -\"one\"
+
+since a `str` cannot be negated.
+",
+    // E0103 - Mismatch
+    "A value has a different type than what was expected, e.g.
+
+    a: int = \"one\"
+
+Either change the value or the type annotation.
+",
+    // E0104 - MismatchAssign
+    "A value of the wrong type is assigned to a variable, e.g.
+
+    a := 1
+    a = \"one\"
+
+Variables keep the type they were defined with.
+",
+    // E0105 - Mutability
+    "A constant is assigned to, e.g.
+
+    a :: 1
+    a = 2
+
+Define it with `:=` to make it mutable.
+",
+    // E0106 - ExcessiveForce
+    "A type is forced with `!` where it isn't needed, e.g.
+
+    a: !int = 1
+
+Since `1` already is an `int`, leave out the `!`.
+",
+    // E0107 - NamespaceNotExpression
+    "A module is used as a value, e.g.
+
+    use some_module
+    a := some_module
+
+Access something in the module instead, like `some_module.value`.
+",
+    // E0108 - WrongArity
+    "A function is called with the wrong number of arguments, e.g.
+
+    f :: fn a: int -> int do ret a end
+    f(1, 2)
+
+`f` takes one argument but was given two.
+",
+    // E0109 - UnknownField
+    "A field is accessed that the blob doesn't have, e.g.
+
+    A :: blob { x: int }
+    a := A { x: 1 }
+    a.y
+
+`A` has no field `y`.
+",
+    // E0110 - MissingField
+    "A blob is created without all of its fields, e.g.
+
+    A :: blob { x: int, y: int }
+    a := A { x: 1 }
+
+Fields that can be nil may be left out, all others have to be given.
+",
+    // E0111 - TupleIndexOutOfRange
+    "A tuple is indexed outside of its length, e.g.
+
+    a := (1, 2)
+    a[2]
+
+Tuples have a fixed length, so this is known before the program runs.
+",
+    // E0112 - UnresolvedName
+    "A name is used that isn't defined, e.g.
+
+    a := b + 1
+
+Check the spelling, or define `b` before it's used.
+",
+    // E0113 - EmptyCollection
+    "An empty collection is created without a type to tell what it contains,
+e.g.
+
+    a := []
+
+Annotate the type of the variable instead.
+
+    a: [int] = []
+",
+    // E0114 - UninferableGeneric
+    "A generic in the return type of a function can't be inferred, since it
+isn't in the parameters and the result has no type, e.g.
+
+    a := parse_value(\"1\")
+
+Give the result a type, like `a: int? = parse_value(\"1\")`.
+",
+];
+
+static RUNTIME_ERROR_EXPLANATIONS: [&str; 24] = [
+    // E0200 - FieldTypeMismatch
+    "A field of a blob is given a value of the wrong type.
+",
+    // E0201 - TypeError
+    "An operation is applied to values of types it isn't defined for.
+",
+    // E0202 - TypeCompare
+    "Two types could not be compared.
+",
+    // E0203 - TypeMismatch
+    "A value has a different type than what was expected.
+",
+    // E0204 - CannotInfer
+    "A type could not be inferred from another type.
+",
+    // E0205 - ArgumentType
+    "A function is called with arguments of the wrong types.
+",
+    // E0206 - IndexError
+    "A value is indexed with something it can't be indexed with, e.g.
+
+    a := [1, 2]
+    a[\"one\"]
+
+Lists are indexed with `int`s.
+",
+    // E0207 - ExternTypeMismatch
+    "An external function is called with arguments of types it doesn't accept,
+e.g.
+
+    sqrt(\"four\")
+
+See the signature of the function for what it accepts.
+",
+    // E0208 - ExternError
+    "An external function failed. The message tells what went wrong.
+",
+    // E0209 - ValueError
+    "An operation is applied to values it isn't defined for, e.g.
+
+    a := 1 / 0
+",
+    // E0210 - IntegerOverflow
+    "An integer operation gave a result that doesn't fit in an `int`, e.g.
+
+    a := 9223372036854775807 + 1
+
+Use `float`s for numbers that large.
+",
+    // E0211 - UnknownField
+    "A field is accessed that the blob doesn't have.
+",
+    // E0212 - ImmutableField
+    "A field that isn't mutable is assigned to.
+",
+    // E0213 - ArgumentCount
+    "A function is called with the wrong number of arguments.
+",
+    // E0214 - IndexOutOfBounds
+    "A collection is indexed outside of its length, e.g.
+
+    a := [1, 2]
+    a[2]
+
+The first element has index 0, so the last has index `len(a) - 1`.
+",
+    // E0215 - UnpackMismatch
+    "A value is unpacked into the wrong number of variables, e.g.
+
+    a, b := (1, 2, 3)
+",
+    // E0216 - StackOverflow
+    "Too many function calls are nested, usually from recursion without an end,
+e.g.
+
+    f :: fn do f() end
+
+The maximum depth can be changed with `--max-call-depth`.
+",
+    // E0217 - TypeAssertion
+    "A value didn't have the type it was asserted to have.
+",
+    // E0218 - AssertFailed
+    "An assertion failed, e.g.
+
+    1 <=> 2
+",
+    // E0219 - AssertionFailed
+    "An `assert` failed, e.g.
+
+    assert 1 == 2, \"math is broken\"
+",
+    // E0220 - InvalidProgram
+    "The program is invalid, which is a bug in the compiler.
+",
+    // E0221 - Unreachable
+    "Code that was marked as unreachable was reached, e.g.
+
+    <!>
+",
+    // E0222 - ExecutionLimitExceeded
+    "The loops ran more iterations than `--max-loop-iterations` allows, e.g.
+
+    loop do end
+
+Loops that never end are stopped this way.
+",
+    // E0223 - ImmutableRange
+    "A range is changed, e.g.
+
+    r := range(0, 3)
+    r[0] = 5
+
+Ranges don't store their elements, so they can't be changed. Use
+`range_list` to get a list instead.
+",
+];
+
 #[cfg(test)]
 mod test {
     // A small hack is required to test the functions working on Formatters
@@ -508,4 +969,68 @@ mod test {
    4 | start :: fn {
        ^^^^^\n",
     );
+
+    #[test]
+    fn error_renders_code() {
+        std::env::set_var("NO_COLOR", "1");
+        let err = super::Error::TypeError {
+            kind: super::TypeError::Mismatch {
+                got: super::Type::Int,
+                expected: super::Type::String,
+            },
+            file: write_str_to_tmp("a: str = 1\n"),
+            span: super::Span { line: 1, col_start: 1, col_end: 2 },
+            message: None,
+        };
+        assert_eq!(err.code(), "E0103");
+        assert!(format!("{}", err).starts_with("typecheck error[E0103]: "));
+    }
+
+    #[test]
+    fn explain_known_codes() {
+        assert!(super::explain("E0004").unwrap().contains("parsed"));
+        assert!(super::explain("E0103").unwrap().contains("a: int = \"one\""));
+        assert!(super::explain("E0222").is_some());
+        assert!(super::explain("e0113").is_some());
+    }
+
+    #[test]
+    fn explain_every_code() {
+        let kinds = [
+            (0, super::Error::NUM_VARIANTS),
+            (100, super::TypeError::NUM_VARIANTS),
+            (200, super::RuntimeError::NUM_VARIANTS),
+        ];
+        for (first, variants) in kinds {
+            for number in first..first + variants {
+                let code = format!("E{:04}", number);
+                assert!(super::explain(&code).is_some(), "{} has no explanation", code);
+            }
+            let past_the_end = format!("E{:04}", first + variants);
+            assert!(super::explain(&past_the_end).is_none(), "{} is explained but has no variant", past_the_end);
+        }
+    }
+
+    #[test]
+    fn explain_unknown_codes() {
+        assert!(super::explain("E0010").is_none());
+        assert!(super::explain("E0115").is_none());
+        assert!(super::explain("E0224").is_none());
+        assert!(super::explain("E103").is_none());
+        assert!(super::explain("nonsense").is_none());
+    }
+
+    #[test]
+    fn extern_type_mismatch_call_site() {
+        std::env::set_var("NO_COLOR", "1");
+        let err = super::RuntimeError::ExternTypeMismatch(
+            "sqrt".to_string(),
+            vec![super::Type::String],
+            super::Span { line: 4, col_start: 18, col_end: 19 },
+        );
+        assert_eq!(
+            format!("{}", err),
+            "Extern function 'sqrt' doesn't accept argument(s) with type(s) [str], called at 4:18",
+        );
+    }
 }
@@ -1,8 +1,10 @@
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
-use std::collections::{HashMap, HashSet, hash_map::Entry};
+use indexmap::{IndexMap, IndexSet};
+use std::collections::{HashMap, hash_map::Entry};
 use std::rc::Rc;
 
+use crate::value::{Range, UniqueId};
 use crate::{Type, UpValue, Value};
 
 /// The serialized version of a pointer.
@@ -16,14 +18,16 @@ pub enum FlatValue {
     Blob(HashMap<String, FlatValueID>),
     Tuple(Vec<FlatValueID>),
     List(Vec<FlatValueID>),
-    Set(HashSet<FlatValueID>),
-    Dict(HashMap<FlatValueID, FlatValueID>),
+    Set(IndexSet<FlatValueID>),
+    Dict(IndexMap<FlatValueID, FlatValueID>),
     Float(f64),
     Int(i64),
     Bool(bool),
     String(String),
     Function(Vec<FlatUpValue>, Type, usize),
     ExternFunction(usize),
+    Partial(FlatValueID, FlatValueID),
+    Range(Range),
     Nil,
 }
 
@@ -43,7 +47,12 @@ impl FlatValue {
     }
 
     /// Helper function to package values recursively into a 'flat' [Vec].
-    fn pack_inner(value: &Value, pack: &mut FlatValuePack, seen: &mut HashMap<usize, FlatValueID>) -> FlatValueID {
+    fn pack_inner(value: &Value, pack: &mut FlatValuePack, seen: &mut HashMap<UniqueId, FlatValueID>) -> FlatValueID {
+        // The cache is only an optimization, so the function is enough.
+        if let Value::Memoized(function, _) = value {
+            return Self::pack_inner(function, pack, seen);
+        }
+
         let id = pack.len();
         match seen.entry(value.unique_id()) {
             Entry::Occupied(entry) => { return *entry.get(); }
@@ -89,6 +98,12 @@ impl FlatValue {
                 *slot,
             ),
             Value::ExternFunction(slot) => FlatValue::ExternFunction(*slot),
+            Value::Memoized(..) => unreachable!("Memoized functions are packed as the function"),
+            Value::Partial(function, bound) => FlatValue::Partial(
+                Self::pack_inner(function, pack, seen),
+                Self::pack_inner(bound, pack, seen),
+            ),
+            Value::Range(range) => FlatValue::Range(*range),
             Value::Nil => FlatValue::Nil,
         };
         pack[id] = val;
@@ -104,8 +119,8 @@ impl FlatValue {
             // Tuple is specificly tricky - since it doesn't have a RefCell.
             FlatValue::Tuple(_) => Value::Tuple(Rc::new(Vec::new())),
             FlatValue::List(_) => Value::List(Rc::new(RefCell::new(Vec::new()))),
-            FlatValue::Set(_) => Value::Set(Rc::new(RefCell::new(HashSet::new()))),
-            FlatValue::Dict(_) => Value::Dict(Rc::new(RefCell::new(HashMap::new()))),
+            FlatValue::Set(_) => Value::Set(Rc::new(RefCell::new(IndexSet::new()))),
+            FlatValue::Dict(_) => Value::Dict(Rc::new(RefCell::new(IndexMap::new()))),
             FlatValue::Float(f) => Value::Float(f),
             FlatValue::Int(i) => Value::Int(i),
             FlatValue::Bool(b) => Value::Bool(b),
@@ -117,6 +132,8 @@ impl FlatValue {
                 slot,
             ),
             FlatValue::ExternFunction(slot) => Value::ExternFunction(slot),
+            FlatValue::Partial(_, _) => Value::Partial(Rc::new(Value::Nil), Rc::new(Value::Nil)),
+            FlatValue::Range(range) => Value::Range(range),
             FlatValue::Nil => Value::Nil,
         }
     }
@@ -146,6 +163,11 @@ impl FlatValue {
                         values.push(Rc::new(RefCell::new(UpValue { slot: up.slot, value: mapping[up.value].clone() })));
                     }
                 }
+                (FlatValue::Partial(flat_function, flat_bound), Value::Partial(function, bound)) => {
+                    // See the tuple comment
+                    *unsafe { (Rc::as_ptr(function) as *mut Value).as_mut() }.unwrap() = mapping[*flat_function].clone();
+                    *unsafe { (Rc::as_ptr(bound) as *mut Value).as_mut() }.unwrap() = mapping[*flat_bound].clone();
+                }
                 (FlatValue::Blob(flat), Value::Blob(values)) => {
                     let mut values = values.borrow_mut();
                     for (key, id) in flat {
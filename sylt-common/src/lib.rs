@@ -10,6 +10,7 @@ pub mod value;
 use std::borrow::Cow;
 use std::cell::RefCell;
 use std::rc::Rc;
+use sylt_tokenizer::Span;
 
 pub use block::{Block, BlockLinkState};
 pub use error::Error;
@@ -41,5 +42,7 @@ pub trait Machine {
 
 pub struct RuntimeContext<'m> {
     pub stack_base: usize,
+    /// Where the external function was called from.
+    pub call_site: Span,
     pub machine: &'m mut dyn Machine,
 }
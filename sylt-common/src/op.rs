@@ -38,6 +38,11 @@ pub enum Op {
     ///
     /// {A, B, C} - Tuple(3) - {D(A, B, C)}
     Tuple(usize),
+    /// Replaces the [Value::Tuple] on the top of the stack with its
+    /// elements, which have to be exactly as many as the given size.
+    ///
+    /// {D(A, B, C)} - Unpack(3) - {A, B, C}
+    Unpack(usize),
     /// Creates a new [Value::List] with the given size and place it on the top
     /// of the stack.
     ///
@@ -113,6 +118,12 @@ pub enum Op {
     ///
     /// {A, B} - Div - {A / B}
     Div,
+    /// The remainder of dividing the two top elements
+    /// on the stack, using the function [op::rem]. The
+    /// result is the pushed.
+    ///
+    /// {A, B} - Mod - {A % B}
+    Mod,
     /// Negates the top element on the stack.
     ///
     /// {A} - Neg - {-A}
@@ -185,11 +196,25 @@ pub enum Op {
     ///
     /// {A} - Assert - {}
     Assert,
+    /// Pops the condition and the message (a
+    /// string or nil) from the stack, and crashes
+    /// the program with the message if the
+    /// condition is false.
+    ///
+    /// {A, M} - AssertMessage - {}
+    AssertMessage,
     /// This instruction should not be executed.
     /// If it is the program crashes.
     ///
     /// Does not affect the stack.
     Unreachable,
+    /// Crashes the program if the topmost
+    /// value doesn't fit the type in the
+    /// given constant. Only emitted with
+    /// `--debug-checks`.
+    ///
+    /// Does not affect the stack.
+    AssertType(usize),
 
     /// Reads the value counted from the
     /// bottom of the stack and adds it
@@ -7,10 +7,21 @@ use crate::{Block, RustFunction, Value};
 pub struct BytecodeProg {
     pub blocks: Vec<Rc<RefCell<Block>>>,
     pub functions: Vec<RustFunction>,
+    /// The names the external functions were linked with, in the same order
+    /// as [BytecodeProg::functions].
+    pub function_names: Vec<String>,
     pub constants: Vec<Value>,
     pub strings: Vec<String>,
 }
 
+impl BytecodeProg {
+    /// The slot of the external function linked with the name, the same slot
+    /// [Value::ExternFunction] refers to.
+    pub fn extern_function(&self, name: &str) -> Option<usize> {
+        self.function_names.iter().position(|function| function == name)
+    }
+}
+
 #[derive(Clone)]
 pub enum Prog {
     Bytecode(BytecodeProg),
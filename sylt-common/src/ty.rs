@@ -41,7 +41,7 @@ impl Debug for Type {
 impl Display for Type {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Type::Ty => write!(f, "Type"),
+            Type::Ty => write!(f, "type"),
             Type::Generic(name) => write!(f, "#{}", name),
             Type::Void => write!(f, "void"),
             Type::Unknown => write!(f, "?"),
@@ -121,6 +121,14 @@ impl From<&Value> for Type {
             Value::String(_) => Type::String,
             Value::Function(_, ty, _) => ty.clone(),
             Value::ExternFunction(n) => Type::ExternFunction(*n),
+            Value::Memoized(function, _) => Type::from(function.as_ref()),
+            Value::Partial(function, _) => match Type::from(function.as_ref()) {
+                Type::Function(params, ret) if !params.is_empty() => {
+                    Type::Function(params[1..].to_vec(), ret)
+                }
+                _ => Type::Unknown,
+            },
+            Value::Range(_) => Type::List(Box::new(Type::Int)),
             Value::Nil => Type::Void,
             Value::Ty(_) => Type::Ty,
         }
@@ -264,6 +272,25 @@ impl Type {
         }
     }
 
+    /// Checks if a value of this type can be used where the other type is
+    /// expected. `int` is a subtype of `int | str`, and `[int]` is a subtype
+    /// of `[int | str]`.
+    pub fn is_subtype_of(&self, other: &Self) -> bool {
+        other.fits(self).is_ok()
+    }
+
+    /// Finds the smallest type both types are subtypes of. Types where neither
+    /// is a subtype of the other are joined into a union.
+    pub fn unify(&self, other: &Self) -> Type {
+        if self.is_subtype_of(other) {
+            other.clone()
+        } else if other.is_subtype_of(self) {
+            self.clone()
+        } else {
+            Type::maybe_union([self, other].iter().copied())
+        }
+    }
+
     pub fn is_nil(&self) -> bool {
         matches!(self, Type::Void | Type::Invalid)
     }
@@ -290,3 +317,153 @@ impl Type {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Type;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    fn union(tys: &[Type]) -> Type {
+        Type::Union(tys.iter().cloned().collect::<BTreeSet<_>>())
+    }
+
+    fn list(ty: Type) -> Type {
+        Type::List(Box::new(ty))
+    }
+
+    fn function(args: &[Type], ret: Type) -> Type {
+        Type::Function(args.to_vec(), Box::new(ret))
+    }
+
+    #[test]
+    fn subtype_is_reflexive() {
+        for ty in [Type::Int, Type::String, list(Type::Bool), union(&[Type::Int, Type::Float])] {
+            assert!(ty.is_subtype_of(&ty), "{:?}", ty);
+        }
+    }
+
+    #[test]
+    fn subtype_of_union() {
+        let int_or_str = union(&[Type::Int, Type::String]);
+        assert!(Type::Int.is_subtype_of(&int_or_str));
+        assert!(Type::String.is_subtype_of(&int_or_str));
+        assert!(!Type::Float.is_subtype_of(&int_or_str));
+        assert!(!int_or_str.is_subtype_of(&Type::Int));
+    }
+
+    #[test]
+    fn subtype_of_bigger_union() {
+        let small = union(&[Type::Int, Type::String]);
+        let big = union(&[Type::Int, Type::String, Type::Bool]);
+        assert!(small.is_subtype_of(&big));
+        assert!(!big.is_subtype_of(&small));
+    }
+
+    #[test]
+    fn subtype_of_nullable() {
+        let nullable = union(&[Type::Int, Type::Void]);
+        assert!(Type::Int.is_subtype_of(&nullable));
+        assert!(Type::Void.is_subtype_of(&nullable));
+        assert!(!nullable.is_subtype_of(&Type::Int));
+        assert!(!Type::Void.is_subtype_of(&Type::Int));
+    }
+
+    #[test]
+    fn subtype_of_unknown() {
+        assert!(Type::Int.is_subtype_of(&Type::Unknown));
+        assert!(Type::Unknown.is_subtype_of(&Type::Int));
+    }
+
+    #[test]
+    fn collections_are_covariant() {
+        let int_or_str = union(&[Type::Int, Type::String]);
+        assert!(list(Type::Int).is_subtype_of(&list(int_or_str.clone())));
+        assert!(!list(int_or_str.clone()).is_subtype_of(&list(Type::Int)));
+
+        let set = |ty| Type::Set(Box::new(ty));
+        assert!(set(Type::Int).is_subtype_of(&set(int_or_str.clone())));
+        assert!(!set(Type::String).is_subtype_of(&set(Type::Int)));
+
+        let dict = |k, v| Type::Dict(Box::new(k), Box::new(v));
+        assert!(dict(Type::Int, Type::Int).is_subtype_of(&dict(Type::Int, int_or_str.clone())));
+        assert!(dict(Type::Int, Type::Int).is_subtype_of(&dict(int_or_str.clone(), Type::Int)));
+        assert!(!dict(Type::Int, Type::String).is_subtype_of(&dict(Type::Int, Type::Int)));
+
+        let tuple = Type::Tuple(vec![Type::Int, Type::Bool]);
+        assert!(tuple.is_subtype_of(&Type::Tuple(vec![int_or_str.clone(), Type::Bool])));
+        assert!(!tuple.is_subtype_of(&Type::Tuple(vec![Type::Bool, Type::Bool])));
+    }
+
+    #[test]
+    fn different_collections_are_not_subtypes() {
+        assert!(!list(Type::Int).is_subtype_of(&Type::Set(Box::new(Type::Int))));
+        assert!(!list(Type::Int).is_subtype_of(&Type::Tuple(vec![Type::Int])));
+    }
+
+    #[test]
+    fn function_variance() {
+        let int_or_str = union(&[Type::Int, Type::String]);
+        // Both the arguments and the return value are compared covariantly.
+        // This is a known hole, a sound check would compare the arguments
+        // contravariantly, but blobs that refer to themselves are checked
+        // against their partially declared selves and stop fitting then.
+        // These asserts pin the current behaviour until that's fixed.
+        assert!(function(&[Type::Int], Type::Int)
+            .is_subtype_of(&function(&[int_or_str.clone()], Type::Int)));
+        assert!(function(&[Type::Int], Type::Int)
+            .is_subtype_of(&function(&[Type::Int], int_or_str.clone())));
+        assert!(!function(&[Type::Int], int_or_str)
+            .is_subtype_of(&function(&[Type::Int], Type::Int)));
+    }
+
+    #[test]
+    fn function_arity() {
+        assert!(!function(&[Type::Int], Type::Void)
+            .is_subtype_of(&function(&[Type::Int, Type::Int], Type::Void)));
+        assert!(!function(&[], Type::Void).is_subtype_of(&function(&[Type::Int], Type::Void)));
+    }
+
+    #[test]
+    fn blob_fields() {
+        let blob = |fields: &[(&str, Type)]| {
+            Type::Blob(
+                "A".to_string(),
+                fields
+                    .iter()
+                    .map(|(name, ty)| (name.to_string(), ty.clone()))
+                    .collect::<BTreeMap<_, _>>(),
+            )
+        };
+        let a = blob(&[("a", Type::Int)]);
+        let ab = blob(&[("a", Type::Int), ("b", Type::String)]);
+        // A blob with more fields can be used where fewer are expected.
+        assert!(ab.is_subtype_of(&a));
+        assert!(!a.is_subtype_of(&ab));
+        assert!(!blob(&[("a", Type::String)]).is_subtype_of(&a));
+    }
+
+    #[test]
+    fn unify_subtypes() {
+        let int_or_str = union(&[Type::Int, Type::String]);
+        assert_eq!(Type::Int.unify(&Type::Int), Type::Int);
+        assert_eq!(Type::Int.unify(&int_or_str), int_or_str);
+        assert_eq!(int_or_str.unify(&Type::String), int_or_str);
+        assert_eq!(
+            list(Type::Int).unify(&list(int_or_str.clone())),
+            list(int_or_str)
+        );
+    }
+
+    #[test]
+    fn unify_unrelated() {
+        assert_eq!(
+            Type::Int.unify(&Type::String),
+            union(&[Type::Int, Type::String])
+        );
+        assert_eq!(
+            list(Type::Int).unify(&list(Type::String)),
+            union(&[list(Type::Int), list(Type::String)])
+        );
+        assert_eq!(Type::Int.unify(&Type::Invalid), Type::Invalid);
+    }
+}
@@ -1,12 +1,18 @@
+use indexmap::{IndexMap, IndexSet};
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
 use std::fmt::{Debug, Display};
 use std::hash::{Hash, Hasher};
 use std::rc::Rc;
 
 use crate::{ty::Type, upvalue::UpValue};
 
+/// What [Value::unique_id] gives, a tag for the variant and an address.
+pub type UniqueId = (u8, usize);
+
 #[derive(Clone)]
 #[derive(Deserialize, Serialize)]
 pub enum Value {
@@ -14,17 +20,153 @@ pub enum Value {
     Blob(Rc<RefCell<HashMap<String, Value>>>),
     Tuple(Rc<Vec<Value>>),
     List(Rc<RefCell<Vec<Value>>>),
-    Set(Rc<RefCell<HashSet<Value>>>),
-    Dict(Rc<RefCell<HashMap<Value, Value>>>),
+    /// Sets and dicts remember the order elements were inserted in,
+    /// so printing and iterating over them is deterministic.
+    Set(Rc<RefCell<IndexSet<Value>>>),
+    Dict(Rc<RefCell<IndexMap<Value, Value>>>),
     Float(f64),
     Int(i64),
     Bool(bool),
     String(Rc<String>),
     Function(Rc<Vec<Rc<RefCell<UpValue>>>>, Type, usize),
     ExternFunction(usize),
+    /// A function that remembers what it returned for every tuple of
+    /// arguments it has been called with.
+    Memoized(Rc<Value>, Rc<RefCell<IndexMap<Value, Value>>>),
+    /// A function with its first argument bound, the rest are given when
+    /// it's called.
+    Partial(Rc<Value>, Rc<Value>),
+    /// A list of integers that isn't built, so it doesn't matter how long it is.
+    Range(Range),
     Nil,
 }
 
+/// An `int` or a `float`, see [Value::as_number].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    pub fn as_float(self) -> f64 {
+        match self {
+            Number::Int(i) => i as f64,
+            Number::Float(f) => f,
+        }
+    }
+}
+
+/// Two numbers brought to a common type, see [Value::promote].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Promoted {
+    Int(i64, i64),
+    Float(f64, f64),
+}
+
+/// The integers from `start` to `end`, `step` apart. The range counts down if
+/// `step` is negative, and never contains `end` unless it's `inclusive`.
+///
+/// Ranges are equal if they step through the same integers, so `0..3` is
+/// equal to `0..=2`.
+#[derive(Clone, Copy, Debug, Eq)]
+#[derive(Deserialize, Serialize)]
+pub struct Range {
+    pub start: i64,
+    pub end: i64,
+    pub step: i64,
+    pub inclusive: bool,
+}
+
+impl Range {
+    pub fn iter(&self) -> impl Iterator<Item = i64> {
+        let Range { end, step, inclusive, .. } = *self;
+        let mut next = Some(self.start);
+        std::iter::from_fn(move || {
+            let current = next?;
+            let before_end = if step > 0 { current < end } else { current > end };
+            if !(before_end || (inclusive && current == end)) {
+                return None;
+            }
+            next = current.checked_add(step);
+            Some(current)
+        })
+    }
+
+    /// How many integers the range steps through. A range over all of the
+    /// integers has one more than fits in a `usize`, so this is an `i128`.
+    fn count(&self) -> i128 {
+        let step = self.step as i128;
+        let distance = (self.end as i128 - self.start as i128) * step.signum();
+        let steps = distance / step.abs();
+        if distance < 0 {
+            0
+        } else if self.inclusive || distance % step.abs() != 0 {
+            steps + 1
+        } else {
+            steps
+        }
+    }
+
+    /// The number of integers in the range, `usize::MAX` if there are more.
+    pub fn len(&self) -> usize {
+        usize::try_from(self.count()).unwrap_or(usize::MAX)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count() == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<i64> {
+        if index as i128 >= self.count() {
+            return None;
+        }
+        Some((self.start as i128 + index as i128 * self.step as i128) as i64)
+    }
+
+    pub fn contains(&self, value: i64) -> bool {
+        let offset = value as i128 - self.start as i128;
+        let step = self.step as i128;
+        offset % step == 0 && offset / step >= 0 && offset / step < self.count()
+    }
+
+    pub fn to_list(&self) -> Value {
+        Value::List(Rc::new(RefCell::new(self.iter().map(Value::Int).collect())))
+    }
+
+    /// The same integers as `(start, exclusive end, step)`, where the end is
+    /// right after the last integer. All empty ranges are `(0, 0, 1)`.
+    fn normalized(&self) -> (i64, i128, i64) {
+        match self.count() {
+            0 => (0, 0, 1),
+            1 => (self.start, self.start as i128 + 1, 1),
+            count => (self.start, self.start as i128 + count * self.step as i128, self.step),
+        }
+    }
+}
+
+impl PartialEq for Range {
+    fn eq(&self, other: &Range) -> bool {
+        self.normalized() == other.normalized()
+    }
+}
+
+impl Hash for Range {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.normalized().hash(state);
+    }
+}
+
+impl Display for Range {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "{}..{}{}", self.start, if self.inclusive { "=" } else { "" }, self.end)?;
+        if self.step != 1 {
+            write!(fmt, " step {}", self.step)?;
+        }
+        Ok(())
+    }
+}
+
 impl From<&str> for Value {
     fn from(s: &str) -> Self {
         Value::String(Rc::new(s.to_string()))
@@ -37,21 +179,22 @@ impl From<&Type> for Value {
 
             Type::Unknown
             | Type::Invalid
-            | Type::Generic(_)
             | Type::Union(_) => panic!("This type cannot be represented as a value!"),
-            Type::Void => Value::Nil,
+            // Generics should be resolved before we get here, if one leaks
+            // through we don't know anything about it.
+            Type::Generic(_) | Type::Void => Value::Nil,
             Type::Blob(_, f) => Value::Blob(Rc::new(RefCell::new(
                 f.iter().map(|(n, t)| (n.clone(), t.into())).collect()
             ))),
             Type::Tuple(fields) => Value::Tuple(Rc::new(fields.iter().map(Value::from).collect())),
             Type::List(v) => Value::List(Rc::new(RefCell::new(vec![Value::from(v.as_ref())]))),
             Type::Set(v) => {
-                let mut s = HashSet::new();
+                let mut s = IndexSet::new();
                 s.insert(Value::from(v.as_ref()));
                 Value::Set(Rc::new(RefCell::new(s)))
             }
             Type::Dict(k, v) => {
-                let mut s = HashMap::new();
+                let mut s = IndexMap::new();
                 s.insert(Value::from(k.as_ref()), Value::from(v.as_ref()));
                 Value::Dict(Rc::new(RefCell::new(s)))
             }
@@ -100,8 +243,10 @@ impl PartialEq<Value> for Value {
                 a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| a == b)
             }
             (Value::List(a), Value::List(b)) => a == b,
+            (Value::Range(a), Value::Range(b)) => a == b,
             (Value::Set(a), Value::Set(b)) => a == b,
             (Value::Dict(a), Value::Dict(b)) => a == b,
+            (Value::Ty(a), Value::Ty(b)) => a == b,
             (Value::Nil, Value::Nil) => true,
             _ => false,
         }
@@ -112,20 +257,7 @@ impl Eq for Value {}
 
 impl Hash for Value {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        match self {
-            Value::Float(a) => {
-                // We have to limit the values, because
-                // floats are wierd.
-                assert!(a.is_finite());
-                a.to_bits().hash(state);
-            }
-            Value::Int(a) => a.hash(state),
-            Value::Bool(a) => a.hash(state),
-            Value::String(a) => a.hash(state),
-            Value::Tuple(a) => a.hash(state),
-            Value::Nil => state.write_i8(0),
-            _ => {}
-        };
+        self.safe_hash(state, &mut HashSet::new())
     }
 }
 
@@ -134,30 +266,166 @@ impl Value {
         matches!(self, Value::Nil)
     }
 
-    pub fn unique_id(&self) -> usize {
+    pub fn as_number(&self) -> Option<Number> {
         match self {
-            Value::Ty(ty) => ty as *const _ as usize,
-            Value::Float(f) => f as *const _ as usize,
-            Value::Int(i) => i as *const _ as usize,
-            Value::Bool(b) => b as *const _ as usize,
-            Value::Blob(v) => Rc::as_ptr(v) as usize,
-            Value::String(s) => Rc::as_ptr(s) as usize,
-            Value::List(v) => Rc::as_ptr(v) as usize,
-            Value::Set(v) => Rc::as_ptr(v) as usize,
-            Value::Dict(v) => Rc::as_ptr(v) as usize,
-            Value::Function(v, _, _) => Rc::as_ptr(v) as usize,
-            Value::Tuple(v) => Rc::as_ptr(v) as usize,
-            Value::Nil => 0,  // TODO(ed): This is not a valid pointer - right?
-            Value::ExternFunction(slot) => slot + 2,
+            Value::Int(i) => Some(Number::Int(*i)),
+            Value::Float(f) => Some(Number::Float(*f)),
+            _ => None,
         }
     }
 
+    /// The numeric coercion rules for the arithmetic operators: two `int`s
+    /// stay `int`s, and if either is a `float` both become `float`s. Equality
+    /// doesn't promote, `1 == 1.0` is an error.
+    pub fn promote(a: &Value, b: &Value) -> Option<Promoted> {
+        match (a.as_number()?, b.as_number()?) {
+            (Number::Int(a), Number::Int(b)) => Some(Promoted::Int(a, b)),
+            (a, b) => Some(Promoted::Float(a.as_float(), b.as_float())),
+        }
+    }
+
+    /// Blobs and functions can't be told apart by their hashes, so they -
+    /// and anything containing them - shouldn't be used as keys.
+    pub fn is_hashable(&self) -> bool {
+        self.safe_is_hashable(&mut HashSet::new())
+    }
+
+    fn safe_is_hashable(&self, seen: &mut HashSet<UniqueId>) -> bool {
+        match self {
+            Value::Blob(_)
+            | Value::Function(..)
+            | Value::ExternFunction(_)
+            | Value::Memoized(..)
+            | Value::Partial(..) => false,
+            Value::Tuple(a) => a.iter().all(|v| v.safe_is_hashable(seen)),
+            Value::List(a) => {
+                !seen.insert(self.unique_id()) || a.borrow().iter().all(|v| v.safe_is_hashable(seen))
+            }
+            Value::Set(a) => {
+                !seen.insert(self.unique_id()) || a.borrow().iter().all(|v| v.safe_is_hashable(seen))
+            }
+            Value::Dict(a) => {
+                !seen.insert(self.unique_id())
+                    || a.borrow()
+                        .iter()
+                        .all(|(k, v)| k.safe_is_hashable(seen) && v.safe_is_hashable(seen))
+            }
+            Value::Float(_)
+            | Value::Int(_)
+            | Value::Bool(_)
+            | Value::String(_)
+            | Value::Range(_)
+            | Value::Ty(_)
+            | Value::Nil => true,
+        }
+    }
+
+    /// An id that no other live value shares. The address is paired with a
+    /// tag for the variant, so `Nil` and extern functions - which have no
+    /// address - can't collide with a real pointer, and neither can an
+    /// `Rc<Value>` and the value it points to.
+    pub fn unique_id(&self) -> UniqueId {
+        match self {
+            Value::Ty(ty) => (1, ty as *const _ as usize),
+            Value::Float(f) => (2, f as *const _ as usize),
+            Value::Int(i) => (3, i as *const _ as usize),
+            Value::Range(r) => (4, r as *const _ as usize),
+            Value::Bool(b) => (5, b as *const _ as usize),
+            Value::Blob(v) => (6, Rc::as_ptr(v) as usize),
+            Value::String(s) => (7, Rc::as_ptr(s) as usize),
+            Value::List(v) => (8, Rc::as_ptr(v) as usize),
+            Value::Set(v) => (9, Rc::as_ptr(v) as usize),
+            Value::Dict(v) => (10, Rc::as_ptr(v) as usize),
+            Value::Function(v, _, _) => (11, Rc::as_ptr(v) as usize),
+            Value::Memoized(_, cache) => (12, Rc::as_ptr(cache) as usize),
+            Value::Partial(_, bound) => (13, Rc::as_ptr(bound) as usize),
+            Value::Tuple(v) => (14, Rc::as_ptr(v) as usize),
+            Value::ExternFunction(slot) => (15, *slot),
+            Value::Nil => (16, 0),
+        }
+    }
+
+    /// Hash the Value structurally, consistent with `PartialEq`. Lists and
+    /// tuples hash in order, while sets and dicts hash independently of
+    /// the order of their elements. Collections that have already been
+    /// visited don't recurse again, which stops endless recursion.
+    fn safe_hash<H: Hasher>(&self, state: &mut H, seen: &mut HashSet<UniqueId>) {
+        match self {
+            Value::Float(a) => {
+                // Floats are wierd. Every NaN hashes to the same sentinel,
+                // even though NaN is never equal to anything - not even
+                // itself. So a NaN can be inserted into a set, but never
+                // looked up again. Infinities hash by their bit pattern,
+                // and both zeroes hash the same since `0.0 == -0.0`.
+                if a.is_nan() {
+                    f64::NAN.to_bits().hash(state);
+                } else if *a == 0.0 {
+                    0.0f64.to_bits().hash(state);
+                } else {
+                    a.to_bits().hash(state);
+                }
+            }
+            Value::Int(a) => a.hash(state),
+            Value::Bool(a) => a.hash(state),
+            Value::String(a) => a.hash(state),
+            Value::Tuple(a) => {
+                a.len().hash(state);
+                for v in a.iter() {
+                    v.safe_hash(state, seen);
+                }
+            }
+            Value::List(a) => {
+                if !seen.insert(self.unique_id()) {
+                    return;
+                }
+                a.borrow().len().hash(state);
+                for v in a.borrow().iter() {
+                    v.safe_hash(state, seen);
+                }
+                seen.remove(&self.unique_id());
+            }
+            Value::Range(a) => a.hash(state),
+            Value::Set(a) => {
+                if !seen.insert(self.unique_id()) {
+                    return;
+                }
+                a.borrow().len().hash(state);
+                let mut sum: u64 = 0;
+                for v in a.borrow().iter() {
+                    let mut hasher = DefaultHasher::new();
+                    v.safe_hash(&mut hasher, seen);
+                    sum = sum.wrapping_add(hasher.finish());
+                }
+                sum.hash(state);
+                seen.remove(&self.unique_id());
+            }
+            Value::Dict(a) => {
+                if !seen.insert(self.unique_id()) {
+                    return;
+                }
+                a.borrow().len().hash(state);
+                let mut sum: u64 = 0;
+                for (k, v) in a.borrow().iter() {
+                    let mut hasher = DefaultHasher::new();
+                    k.safe_hash(&mut hasher, seen);
+                    v.safe_hash(&mut hasher, seen);
+                    sum = sum.wrapping_add(hasher.finish());
+                }
+                sum.hash(state);
+                seen.remove(&self.unique_id());
+            }
+            Value::Ty(a) => a.hash(state),
+            Value::Nil => state.write_i8(0),
+            _ => {}
+        };
+    }
+
     /// Format the Value to a nice readable format while removing endless
     /// recursion.
     fn safe_fmt(
         &self,
         fmt: &mut std::fmt::Formatter<'_>,
-        seen: &mut HashSet<usize>
+        seen: &mut HashSet<UniqueId>
     ) -> std::fmt::Result {
         match self {
             Value::Ty(ty) => write!(fmt, "<type \"{:?}\">", ty),
@@ -168,13 +436,16 @@ impl Value {
                     } else {
                         unreachable!("Got blob without a name")
                     },
-                    self.unique_id()
+                    Rc::as_ptr(v) as usize
                 )?;
                 if !seen.insert(self.unique_id()) {
                     return write!(fmt, "...}}");
                 }
                 let mut first = true;
-                for e in v.borrow().iter() {
+                // The fields are sorted so blobs always print the same way.
+                let mut fields: Vec<_> = v.borrow().iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+                fields.sort_by(|a, b| a.0.cmp(&b.0));
+                for e in fields.iter() {
                     if e.0.starts_with("_") {
                         continue;
                     }
@@ -256,7 +527,103 @@ impl Value {
                 write!(fmt, "<fn #{} {:?}>", block, ty)
             },
             Value::ExternFunction(slot) => write!(fmt, "<extern fn {}>", slot),
+            Value::Memoized(function, _) => {
+                write!(fmt, "<memoized ")?;
+                function.safe_fmt(fmt, seen)?;
+                write!(fmt, ">")
+            }
+            Value::Partial(function, bound) => {
+                write!(fmt, "<partial ")?;
+                function.safe_fmt(fmt, seen)?;
+                write!(fmt, " ")?;
+                bound.safe_fmt(fmt, seen)?;
+                write!(fmt, ">")
+            }
+            Value::Range(range) => write!(fmt, "{}", range),
             Value::Nil => write!(fmt, "nil"),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Range, UniqueId, Value};
+    use crate::ty::Type;
+    use std::cell::RefCell;
+    use std::collections::HashSet;
+    use std::rc::Rc;
+
+    #[test]
+    fn unique_ids_are_distinct() {
+        let bound = Rc::new(Value::Ty(Type::Int));
+        let function = Value::Function(Rc::new(Vec::new()), Type::Void, 0);
+        let values = vec![
+            Value::Nil,
+            Value::ExternFunction(0),
+            Value::ExternFunction(1),
+            Value::ExternFunction(2),
+            Value::Ty(Type::Int),
+            Value::Float(1.0),
+            Value::Int(1),
+            Value::Int(1),
+            Value::Bool(true),
+            Value::Range(Range { start: 0, end: 1, step: 1, inclusive: false }),
+            Value::Blob(Rc::new(RefCell::new(Default::default()))),
+            Value::String(Rc::new("a".to_string())),
+            Value::List(Rc::new(RefCell::new(Vec::new()))),
+            Value::Set(Rc::new(RefCell::new(Default::default()))),
+            Value::Dict(Rc::new(RefCell::new(Default::default()))),
+            Value::Tuple(Rc::new(Vec::new())),
+            Value::Memoized(Rc::new(function.clone()), Rc::new(RefCell::new(Default::default()))),
+            Value::Partial(Rc::new(function.clone()), Rc::clone(&bound)),
+            function,
+        ];
+        let mut ids: HashSet<UniqueId> = values.iter().map(Value::unique_id).collect();
+        ids.insert(bound.unique_id());
+        assert_eq!(ids.len(), values.len() + 1);
+    }
+
+    #[test]
+    fn unique_id_is_stable() {
+        let list = Value::List(Rc::new(RefCell::new(vec![Value::Int(1)])));
+        assert_eq!(list.unique_id(), list.clone().unique_id());
+        assert_eq!(Value::Nil.unique_id(), Value::Nil.unique_id());
+    }
+
+    #[test]
+    fn ranges_are_equal_by_their_integers() {
+        let range = |start, end, step, inclusive| Value::Range(Range { start, end, step, inclusive });
+        assert_eq!(range(0, 3, 1, false), range(0, 2, 1, true));
+        assert_eq!(range(0, 10, 3, false), range(0, 9, 3, true));
+        assert_eq!(range(3, 0, 1, false), range(5, 5, -2, false));
+        assert_eq!(range(4, 5, 7, false), range(4, 4, -1, true));
+        assert_ne!(range(0, 3, 1, false), range(0, 3, 1, true));
+        assert_ne!(range(0, 6, 2, false), range(0, 6, 3, false));
+
+        let keys: HashSet<Value> =
+            vec![range(0, 3, 1, false), range(0, 2, 1, true), range(0, i64::MAX, 1, true)].into_iter().collect();
+        assert_eq!(keys.len(), 2);
+    }
+
+    #[test]
+    fn range_over_every_int() {
+        let range = Range { start: i64::MIN, end: i64::MAX, step: 1, inclusive: true };
+        assert_eq!(range.len(), usize::MAX);
+        assert!(!range.is_empty());
+        assert_eq!(range.get(0), Some(i64::MIN));
+        assert_eq!(range.get(usize::MAX - 1), Some(i64::MAX - 1));
+        assert!(range.contains(i64::MIN));
+        assert!(range.contains(i64::MAX));
+    }
+
+    #[test]
+    fn types_are_equal_by_structure() {
+        let list = || Value::Ty(Type::List(Box::new(Type::Int)));
+        assert_eq!(list(), list());
+        assert_ne!(list(), Value::Ty(Type::List(Box::new(Type::Float))));
+        assert!(list().is_hashable());
+
+        let keys: HashSet<Value> = vec![list(), list(), Value::Ty(Type::Int)].into_iter().collect();
+        assert_eq!(keys.len(), 2);
+    }
+}
@@ -3,17 +3,21 @@ use sylt_common::error::Error;
 use sylt_common::{Block, Op, Type, Value};
 use sylt_parser::expression::ComparisonKind;
 use sylt_parser::{
-    Assignable, AssignableKind, Expression, ExpressionKind, Op as ParserOp,
+    Assignable, AssignableKind, Expression, ExpressionKind, Identifier, Op as ParserOp,
     Span, Statement, StatementKind, VarKind,
 };
 
 use crate::*;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 struct LoopFrame {
+    label: Option<String>,
+    defer_depth: usize,
     continue_addr: usize,
     break_addr: usize,
     stack_size: usize,
+    /// The hidden variable a loop expression evaluates to.
+    value_slot: Option<usize>,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -39,6 +43,8 @@ pub struct BytecodeCompiler<'t> {
 
     pub blocks: Vec<Block>,
     loops: Vec<LoopFrame>,
+    /// The deferred statements of every block we're in, innermost last.
+    defers: Vec<Vec<Statement>>,
 }
 
 impl<'t> BytecodeCompiler<'t> {
@@ -47,6 +53,7 @@ impl<'t> BytecodeCompiler<'t> {
             compiler,
             blocks: Vec::new(),
             loops: Vec::new(),
+            defers: Vec::new(),
         }
     }
 
@@ -54,7 +61,58 @@ impl<'t> BytecodeCompiler<'t> {
         self.blocks
             .get_mut(ctx.block_slot)
             .expect("Invalid block id")
-            .add(op, span.line)
+            .add(op, span)
+    }
+
+    /// The innermost loop, or the innermost loop with the label.
+    fn find_loop(&self, label: &Option<Identifier>) -> Option<LoopFrame> {
+        match label {
+            Some(label) => self
+                .loops
+                .iter()
+                .rfind(|frame| frame.label.as_ref() == Some(&label.name))
+                .cloned(),
+            None => self.loops.last().cloned(),
+        }
+    }
+
+    fn loop_statement(
+        &mut self,
+        label: &Option<Identifier>,
+        condition: &Expression,
+        body: &Statement,
+        value_slot: Option<usize>,
+        ctx: BytecodeContext,
+    ) {
+        let start = self.next_ip(ctx);
+        self.expression(condition, ctx);
+        let jump_from = self.add_op(ctx, condition.span, Op::Illegal);
+
+        // Skip the next op - it's for the break
+        //  start: .. condition ..
+        //         JmpFalse(over)
+        //  break: Jmp(end)
+        //   over: .. loop ..
+        //    end: ..
+        self.add_op(ctx, condition.span, Op::Jmp(jump_from + 3));
+        let break_from = self.add_op(ctx, condition.span, Op::Illegal);
+
+        let stack_size = self.compiler.frames[ctx.frame].variables.len();
+        self.loops.push(LoopFrame {
+            label: label.as_ref().map(|label| label.name.clone()),
+            defer_depth: self.defers.len(),
+            continue_addr: start,
+            break_addr: break_from,
+            stack_size,
+            value_slot,
+        });
+        self.statement(body, ctx);
+        self.loops.pop();
+
+        self.add_op(ctx, body.span, Op::Jmp(start));
+        let out = self.next_ip(ctx);
+        self.patch(ctx, jump_from, Op::JmpFalse(out));
+        self.patch(ctx, break_from, Op::Jmp(out));
     }
 
     fn patch(&mut self, ctx: BytecodeContext, ip: usize, op: Op) {
@@ -96,6 +154,18 @@ impl<'t> BytecodeCompiler<'t> {
         }
     }
 
+    /// Compiles the deferred statements of the blocks from `depth` and in,
+    /// for when they're jumped out of. Innermost blocks go first.
+    fn emit_defers(&mut self, ctx: BytecodeContext, span: Span, depth: usize) {
+        let stack_size = self.compiler.frames[ctx.frame].variables.len();
+        let scopes = self.defers.split_off(depth);
+        for deferred in scopes.iter().rev().flat_map(|scope| scope.iter().rev()) {
+            self.statement(deferred, ctx);
+        }
+        self.defers.extend(scopes);
+        self.pop_until_size(ctx, span, stack_size);
+    }
+
     fn pop_until_size(&mut self, ctx: BytecodeContext, span: Span, target_size: usize) {
         self.emit_pop_until_size(ctx, span, target_size);
         self.compiler.frames[ctx.frame].variables.truncate(target_size);
@@ -187,6 +257,7 @@ impl<'t> BytecodeCompiler<'t> {
             Sub(a, b) => self.bin_op(a, b, &[Op::Sub], expression.span, ctx),
             Mul(a, b) => self.bin_op(a, b, &[Op::Mul], expression.span, ctx),
             Div(a, b) => self.bin_op(a, b, &[Op::Div], expression.span, ctx),
+            Mod(a, b) => self.bin_op(a, b, &[Op::Mod], expression.span, ctx),
 
             Comparison(a, cmp, b) => match cmp {
                 Equals => self.bin_op(a, b, &[Op::Equal], expression.span, ctx),
@@ -198,8 +269,29 @@ impl<'t> BytecodeCompiler<'t> {
                 In => self.bin_op(a, b, &[Op::Contains], expression.span, ctx),
             }
 
+            Is(a, ty) => {
+                // The type goes first, since it's the type the value has to fit.
+                let resolved_ty = self.compiler.resolve_type(ty, ctx.into());
+                let ty_constant = self.compiler.constant(Value::Ty(resolved_ty));
+                self.add_op(ctx, expression.span, ty_constant);
+                self.expression(a, ctx);
+                self.add_op(ctx, expression.span, Op::Is);
+            }
+
             AssertEq(a, b) => self.bin_op(a, b, &[Op::Equal, Op::Assert], expression.span, ctx),
 
+            Assert { condition, message } => {
+                self.expression(condition, ctx);
+                let message = match message {
+                    Some(message) => Value::String(Rc::new(message.clone())),
+                    None => Value::Nil,
+                };
+                let message = self.compiler.constant(message);
+                self.add_op(ctx, expression.span, message);
+                self.add_op(ctx, expression.span, Op::AssertMessage);
+                self.push(Value::Nil, expression.span, ctx);
+            }
+
             Neg(a) => self.un_op(a, &[Op::Neg], expression.span, ctx),
 
             And(a, b) => {
@@ -252,16 +344,31 @@ impl<'t> BytecodeCompiler<'t> {
                 self.patch(ctx, out, op);
             }
 
+            Loop(body) => {
+                // `break` assigns the value to a hidden variable, which is
+                // left on the stack as the value of the loop.
+                let nil = self.compiler.constant(Value::Nil);
+                self.add_op(ctx, expression.span, nil);
+                let slot = self.compiler.define("/loop", VarKind::Mutable, expression.span);
+                self.compiler.activate(slot);
+                if let StatementKind::Loop { label, condition, body } = &body.kind {
+                    self.loop_statement(label, condition, body, Some(slot), ctx);
+                }
+                self.compiler.frames[ctx.frame].variables.truncate(slot);
+            }
+
             Function {
                 name,
                 params,
                 ret,
                 body,
+                ..
             } => {
                 let file = self.compiler.file_from_namespace(ctx.namespace).display();
                 let name = format!("fn {} {}:{}", name, file, expression.span.line);
 
                 // === Frame begin ===
+                let outer_defers = std::mem::take(&mut self.defers);
                 let inner_ctx = self.push_frame_and_block(ctx, &name, expression.span);
                 let mut param_types = Vec::new();
                 for (ident, ty) in params.iter() {
@@ -288,6 +395,7 @@ impl<'t> BytecodeCompiler<'t> {
                     .map(|u| (u.parent, u.upupvalue, u.ty))
                     .collect();
                 let function = Value::Function(Rc::new(Vec::new()), ty, inner_ctx.block_slot);
+                self.defers = outer_defers;
                 // === Frame end ===
 
                 let function = self.compiler.constant(function);
@@ -445,9 +553,15 @@ impl<'t> BytecodeCompiler<'t> {
             }
 
             #[rustfmt::skip]
-            Definition { ident, kind, value, .. } => {
+            Definition { ident, kind, ty, value } => {
                 // TODO(ed): Don't use type here - type check the tree first.
                 self.expression(value, ctx);
+                if self.compiler.debug_checks && kind.force() {
+                    let ty = self.compiler.resolve_type(ty, ctx.into());
+                    if let Op::Constant(slot) = self.compiler.constant(Value::Ty(ty)) {
+                        self.add_op(ctx, statement.span, Op::AssertType(slot));
+                    }
+                }
 
                 if ctx.frame == 0 {
                     // Global
@@ -459,6 +573,31 @@ impl<'t> BytecodeCompiler<'t> {
                 }
             }
 
+            DestructuringDefinition { idents, kind, value } => {
+                self.expression(value, ctx);
+                self.add_op(ctx, statement.span, Op::Unpack(idents.len()));
+                for ident in idents.iter() {
+                    let slot = self.compiler.define(&ident.name, *kind, ident.span);
+                    self.compiler.activate(slot);
+                }
+            }
+
+            DestructuringAssignment { targets, value } => {
+                self.expression(value, ctx);
+                self.add_op(ctx, statement.span, Op::Unpack(targets.len()));
+                // The elements are kept as variables until they're assigned.
+                let stack_size = self.compiler.frames[ctx.frame].variables.len();
+                let names: Vec<_> = (0..targets.len()).map(|i| format!("/unpacked/{}", i)).collect();
+                for name in names.iter() {
+                    let slot = self.compiler.define(name, VarKind::Const, statement.span);
+                    self.compiler.activate(slot);
+                }
+                for (target, name) in targets.iter().zip(names.iter()) {
+                    self.statement(&assign_from_variable(target, name), ctx);
+                }
+                self.pop_until_size(ctx, statement.span, stack_size);
+            }
+
             ExternalDefinition { .. } => {
                 // TODO(ed): Should they be? Is this how we should type the standard library?
                 error!(
@@ -473,7 +612,7 @@ impl<'t> BytecodeCompiler<'t> {
                 use AssignableKind::*;
                 use ParserOp::*;
 
-                let mutator = |kind| matches!(kind, Add | Sub | Mul | Div);
+                let mutator = |kind| matches!(kind, Add | Sub | Mul | Div | Mod);
 
                 let write_mutator_op = |comp: &mut Self, ctx, kind| {
                     let op = match kind {
@@ -481,6 +620,7 @@ impl<'t> BytecodeCompiler<'t> {
                         Sub => Op::Sub,
                         Mul => Op::Mul,
                         Div => Op::Div,
+                        Mod => Op::Mod,
                         Nop => {
                             return;
                         }
@@ -556,40 +696,27 @@ impl<'t> BytecodeCompiler<'t> {
             Block { statements } => {
                 let stack_size = self.compiler.frames[ctx.frame].variables.len();
 
+                self.defers.push(Vec::new());
                 for statement in statements {
                     self.statement(statement, ctx);
                 }
+                let defers = self.defers.pop().unwrap();
+                for deferred in defers.iter().rev() {
+                    self.statement(deferred, ctx);
+                }
 
                 self.pop_until_size(ctx, statement.span, stack_size);
             }
 
-            Loop { condition, body } => {
-                let start = self.next_ip(ctx);
-                self.expression(condition, ctx);
-                let jump_from = self.add_op(ctx, condition.span, Op::Illegal);
-
-                // Skip the next op - it's for the break
-                //  start: .. condition ..
-                //         JmpFalse(over)
-                //  break: Jmp(end)
-                //   over: .. loop ..
-                //    end: ..
-                self.add_op(ctx, condition.span, Op::Jmp(jump_from + 3));
-                let break_from = self.add_op(ctx, condition.span, Op::Illegal);
-
-                let stack_size = self.compiler.frames[ctx.frame].variables.len();
-                self.loops.push(LoopFrame {
-                    continue_addr: start,
-                    break_addr: break_from,
-                    stack_size,
-                });
-                self.statement(body, ctx);
-                self.loops.pop();
+            Defer { body } => match self.defers.last_mut() {
+                Some(defers) => defers.push(Statement::clone(body)),
+                None => {
+                    error!(self.compiler, ctx, statement.span, "`defer` statement not in a block");
+                }
+            }
 
-                self.add_op(ctx, body.span, Op::Jmp(start));
-                let out = self.next_ip(ctx);
-                self.patch(ctx, jump_from, Op::JmpFalse(out));
-                self.patch(ctx, break_from, Op::Jmp(out));
+            Loop { label, condition, body } => {
+                self.loop_statement(label, condition, body, None, ctx);
             }
 
             #[rustfmt::skip]
@@ -607,8 +734,49 @@ impl<'t> BytecodeCompiler<'t> {
                 self.patch(ctx, jump_out, Op::Jmp(end));
             }
 
-            Continue {} => match self.loops.last().cloned() {
-                Some(LoopFrame { stack_size, continue_addr, .. }) => {
+            TypeMatch { value, binding, arms, fallback } => {
+                let stack_size = self.compiler.frames[ctx.frame].variables.len();
+                // The value is only evaluated once, and kept hidden while the arms check it.
+                self.expression(value, ctx);
+                let slot = self.compiler.define("/type_match", VarKind::Const, statement.span);
+                self.compiler.activate(slot);
+
+                let mut jumps_out = Vec::new();
+                for (ty, body) in arms.iter() {
+                    let resolved_ty = self.compiler.resolve_type(ty, ctx.into());
+                    let ty_constant = self.compiler.constant(Value::Ty(resolved_ty));
+                    self.add_op(ctx, ty.span, ty_constant);
+                    self.read_identifier("/type_match", ty.span, ctx, ctx.namespace);
+                    self.add_op(ctx, ty.span, Op::Is);
+                    let jump_from = self.add_op(ctx, ty.span, Op::Illegal);
+
+                    let arm_size = self.compiler.frames[ctx.frame].variables.len();
+                    if let Some(binding) = binding {
+                        self.read_identifier("/type_match", binding.span, ctx, ctx.namespace);
+                        let slot = self.compiler.define(&binding.name, VarKind::Const, binding.span);
+                        self.compiler.activate(slot);
+                    }
+                    self.statement(body, ctx);
+                    self.pop_until_size(ctx, body.span, arm_size);
+                    jumps_out.push(self.add_op(ctx, ty.span, Op::Illegal));
+
+                    let next = self.next_ip(ctx);
+                    self.patch(ctx, jump_from, Op::JmpFalse(next));
+                }
+                if let Some(fallback) = fallback {
+                    self.statement(fallback, ctx);
+                }
+
+                let end = self.next_ip(ctx);
+                for jump_out in jumps_out {
+                    self.patch(ctx, jump_out, Op::Jmp(end));
+                }
+                self.pop_until_size(ctx, statement.span, stack_size);
+            }
+
+            Continue { label } => match self.find_loop(label) {
+                Some(LoopFrame { stack_size, continue_addr, defer_depth, .. }) => {
+                    self.emit_defers(ctx, statement.span, defer_depth);
                     self.emit_pop_until_size(ctx, statement.span, stack_size);
                     self.add_op(ctx, statement.span, Op::Jmp(continue_addr));
                 }
@@ -617,13 +785,24 @@ impl<'t> BytecodeCompiler<'t> {
                 }
             }
 
-            Break {} => match self.loops.last().cloned() {
-                Some(LoopFrame { stack_size, break_addr, .. }) => {
+            Break { label, value } => match self.find_loop(label) {
+                Some(LoopFrame { stack_size, break_addr, defer_depth, value_slot, .. }) => {
+                    match (value, value_slot) {
+                        (Some(value), Some(slot)) => {
+                            self.expression(value, ctx);
+                            self.add_op(ctx, statement.span, Op::AssignLocal(slot));
+                        }
+                        (Some(value), None) => {
+                            error!(self.compiler, ctx, value.span, "Only loop expressions can be broken with a value");
+                        }
+                        (None, _) => {}
+                    }
+                    self.emit_defers(ctx, statement.span, defer_depth);
                     self.emit_pop_until_size(ctx, statement.span, stack_size);
                     self.add_op(ctx, statement.span, Op::Jmp(break_addr));
                 }
                 None => {
-                    error!(self.compiler, ctx, statement.span, "`continue` statement not in a loop");
+                    error!(self.compiler, ctx, statement.span, "`break` statement not in a loop");
                 }
             }
 
@@ -631,8 +810,28 @@ impl<'t> BytecodeCompiler<'t> {
                 self.add_op(ctx, statement.span, Op::Unreachable);
             }
 
+            Assert { expr, message } => {
+                self.expression(expr, ctx);
+                let message = match message {
+                    Some(message) => Value::String(Rc::new(message.clone())),
+                    None => Value::Nil,
+                };
+                let message = self.compiler.constant(message);
+                self.add_op(ctx, statement.span, message);
+                self.add_op(ctx, statement.span, Op::AssertMessage);
+            }
+
             Ret { value } => {
                 self.expression(value, ctx);
+                if self.defers.iter().any(|defers| !defers.is_empty()) {
+                    // The value is kept as a variable so the deferred
+                    // statements can use the stack.
+                    let slot = self.compiler.define("/ret", VarKind::Const, statement.span);
+                    self.compiler.activate(slot);
+                    self.emit_defers(ctx, statement.span, 0);
+                    self.add_op(ctx, statement.span, Op::ReadLocal(slot));
+                    self.compiler.frames[ctx.frame].variables.truncate(slot);
+                }
                 self.add_op(ctx, statement.span, Op::Return);
             }
         }
@@ -689,7 +888,8 @@ impl<'t> BytecodeCompiler<'t> {
 
         // TODO(ed): Real ugly hack until we can run the typechecker before the compiler.
         self.compiler.panic = true;
-        self.read_identifier("start", Span::zero(), ctx, 0);
+        let entry = self.compiler.entry.clone();
+        self.read_identifier(&entry, Span::zero(), ctx, 0);
         self.compiler.panic = false;
 
         self.add_op(ctx, Span::zero(), Op::Call(0));
@@ -707,19 +907,27 @@ fn all_paths_return(statement: &Statement) -> bool {
     match &statement.kind {
         | StatementKind::Assignment { .. }
         | StatementKind::Blob { .. }
-        | StatementKind::Break
-        | StatementKind::Continue
+        | StatementKind::Break { .. }
+        | StatementKind::Continue { .. }
+        | StatementKind::Defer { .. }
         | StatementKind::Definition { .. }
+        | StatementKind::DestructuringAssignment { .. }
+        | StatementKind::DestructuringDefinition { .. }
         | StatementKind::EmptyStatement
         | StatementKind::ExternalDefinition { .. }
         | StatementKind::IsCheck { .. }
         | StatementKind::StatementExpression { .. }
         | StatementKind::Unreachable
+        | StatementKind::Assert { .. }
         | StatementKind::Use { .. } => false,
 
         StatementKind::If { pass, fail, .. } => all_paths_return(pass) && all_paths_return(fail),
 
         StatementKind::Loop { body, .. } => all_paths_return(body),
+        StatementKind::TypeMatch { arms, fallback, .. } => {
+            arms.iter().all(|(_, body)| all_paths_return(body))
+                && matches!(fallback, Some(fallback) if all_paths_return(fallback))
+        }
         StatementKind::Block { statements } => statements.iter().any(all_paths_return),
 
         StatementKind::Ret { .. } => true,
@@ -1,5 +1,5 @@
 use std::cell::RefCell;
-use std::collections::{hash_map::Entry, HashMap};
+use std::collections::{hash_map::Entry, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::io::Write;
@@ -9,15 +9,19 @@ use sylt_common::{Op, RustFunction, Type, Value};
 use sylt_parser::statement::NameIdentifier;
 use sylt_parser::{
     Context as ParserContext,
-    Assignable, AssignableKind, Identifier, Span,
-    StatementKind, Type as ParserType, TypeKind, VarKind, AST,
+    Assignable, AssignableKind, Expression, ExpressionKind, Identifier, Op as ParserOp, Span,
+    Statement, StatementKind, Type as ParserType, TypeKind, VarKind, AST,
 };
 
+mod const_eval;
+mod optimize;
 mod typechecker;
 mod dependency;
 mod bytecode;
 mod lua;
 
+pub use optimize::Propagation;
+
 type VarSlot = usize;
 
 #[derive(Debug, Clone)]
@@ -162,6 +166,12 @@ struct Compiler {
     constants: Vec<Value>,
 
     values: HashMap<Value, usize>,
+
+    /// Check the types the typechecker was told to trust when the program runs.
+    debug_checks: bool,
+
+    /// The function the program starts in, `start` unless another is picked.
+    entry: String,
 }
 
 #[macro_export]
@@ -205,6 +215,10 @@ impl Compiler {
             constants: Vec::new(),
 
             values: HashMap::new(),
+
+            debug_checks: false,
+
+            entry: "start".to_string(),
         }
     }
 
@@ -398,7 +412,15 @@ impl Compiler {
             Resolved(ty) => ty.clone(),
             UserDefined(assignable) => self.resolve_type_ident(&assignable, ctx.namespace, ctx),
             Union(a, b) => match (self.resolve_type(a, ctx), self.resolve_type(b, ctx)) {
-                (Type::Union(_), _) => panic!("Didn't expect union on RHS - check parser"),
+                (Type::Union(mut us), Type::Union(them)) => {
+                    us.extend(them);
+                    Type::Union(us)
+                }
+                // `(a | b)?` puts a grouped union on the left.
+                (Type::Union(mut us), b) => {
+                    us.insert(b);
+                    Type::Union(us)
+                }
                 (a, Type::Union(mut us)) => {
                     us.insert(a);
                     Type::Union(us)
@@ -448,10 +470,11 @@ impl Compiler {
         mut self,
         typecheck: bool,
         lua_file: Option<Box<dyn Write>>,
-        tree: AST,
+        mut tree: AST,
         functions: &[(String, RustFunction, String)],
     ) -> Result<Prog, Vec<Error>> {
         assert!(!tree.modules.is_empty(), "Cannot compile an empty program");
+        const_eval::fold(&mut tree)?;
         let name = "/preamble/";
         let start_span = tree.modules[0].1.span;
         self.frames.push(Frame::new(name, start_span));
@@ -532,6 +555,7 @@ impl Compiler {
                     .map(|x| Rc::new(RefCell::new(x)))
                     .collect(),
                 functions: functions.iter().map(|(_, f, _)| *f).collect(),
+                function_names: functions.iter().map(|(name, _, _)| name.clone()).collect(),
                 constants: self.constants,
                 strings: self.strings,
             }))
@@ -557,6 +581,8 @@ impl Compiler {
             .collect();
 
         let mut num_constants = 0;
+        let mut imports = Vec::new();
+        let mut imports_all = Vec::new();
         // Find all globals in all files and declare them. The globals are
         // initialized at a later stage.
         for (path, module) in tree.modules.iter() {
@@ -576,11 +602,21 @@ impl Compiler {
                         }
                     },
                     Use { path: _, name, file } => {
+                        let other = path_to_namespace_id[file];
                         let ident = match name {
                             NameIdentifier::Implicit(ident) => ident,
                             NameIdentifier::Alias(ident) => ident,
+                            NameIdentifier::Select(selected) => {
+                                // Imported when all globals are known.
+                                imports.extend(selected.iter().map(|(name, alias)| (slot, other, name, alias)));
+                                continue;
+                            }
+                            NameIdentifier::All => {
+                                // Also imported when all globals are known.
+                                imports_all.push((slot, other, statement.span));
+                                continue;
+                            }
                         };
-                        let other = path_to_namespace_id[file];
                         (Name::Namespace(other), ident.name.clone(), ident.span)
                     }
                     Definition { ident: Identifier { name, .. }, kind, .. } => {
@@ -621,6 +657,84 @@ impl Compiler {
             }
             self.namespaces[slot] = namespace;
         }
+
+        // Names can be imported from files that import them in turn,
+        // so we keep going until no more names can be found. Names are only
+        // ever added, so circular imports stop once every file has them all.
+        //
+        // Names imported with `use <file>.*` give way to all other names,
+        // and are kept track of so they can be replaced.
+        let mut imported_all = HashSet::new();
+        let mut ambiguous = HashSet::new();
+        loop {
+            let num_imports = imports.len();
+            imports.retain(|(slot, other, name, alias)| {
+                let found = match self.namespaces[*other].get(&name.name) {
+                    Some(found) => *found,
+                    None => return true,
+                };
+                match self.namespaces[*slot].entry(alias.name.clone()) {
+                    Entry::Vacant(vac) => { vac.insert(found); }
+                    Entry::Occupied(mut occ) if imported_all.remove(&(*slot, alias.name.clone())) => {
+                        occ.insert(found);
+                    }
+                    Entry::Occupied(_) => {
+                        error!(
+                            self,
+                            Context::from_namespace(*slot),
+                            alias.span,
+                            "A global variable with the name '{}' already exists",
+                            alias.name
+                        );
+                    }
+                }
+                false
+            });
+
+            let mut found_new = false;
+            for (slot, other, span) in imports_all.iter() {
+                let names: Vec<_> = self.namespaces[*other]
+                    .iter()
+                    .map(|(name, found)| (name.clone(), *found))
+                    .collect();
+                for (name, found) in names {
+                    match self.namespaces[*slot].entry(name.clone()) {
+                        Entry::Vacant(vac) => {
+                            vac.insert(found);
+                            imported_all.insert((*slot, name));
+                            found_new = true;
+                        }
+                        Entry::Occupied(occ) if *occ.get() == found => {}
+                        Entry::Occupied(_) if imported_all.contains(&(*slot, name.clone())) => {
+                            if ambiguous.insert((*slot, name.clone())) {
+                                error!(
+                                    self,
+                                    Context::from_namespace(*slot),
+                                    *span,
+                                    "The name '{}' is imported from more than one file",
+                                    name
+                                );
+                            }
+                        }
+                        Entry::Occupied(_) => {}
+                    }
+                }
+            }
+
+            if imports.len() == num_imports && !found_new {
+                break;
+            }
+        }
+        for (slot, other, name, _) in imports {
+            error!(
+                self,
+                Context::from_namespace(slot),
+                name.span,
+                "Cannot import '{}' - it's not defined in '{}'",
+                name.name,
+                self.file_from_namespace(other).display()
+            );
+        }
         num_constants
     }
 }
@@ -643,8 +757,19 @@ fn parse_signature(func_name: &str, sig: &str) -> ParserType {
     }
 }
 
-pub fn compile(typecheck: bool, lua_file: Option<Box<dyn Write>>, prog: AST, functions: &[(String, RustFunction, String)]) -> Result<Prog, Vec<Error>> {
-    Compiler::new().compile(typecheck, lua_file, prog, functions)
+pub fn compile(typecheck: bool, debug_checks: bool, entry: &str, lua_file: Option<Box<dyn Write>>, prog: AST, functions: &[(String, RustFunction, String)]) -> Result<Prog, Vec<Error>> {
+    let mut compiler = Compiler::new();
+    compiler.debug_checks = debug_checks;
+    compiler.entry = entry.to_string();
+    compiler.compile(typecheck, lua_file, prog, functions)
+}
+
+/// Evaluates the calls to `const fn`s and propagates constants through the
+/// tree, returning every read that was replaced. The calls are evaluated
+/// first, so constants they define are propagated too.
+pub fn optimize(tree: &mut AST) -> Result<Vec<Propagation>, Vec<Error>> {
+    const_eval::fold(tree)?;
+    Ok(optimize::propagate(tree))
 }
 
 pub(crate) fn first_ok_or_errs<I, T, E>(mut iter: I) -> Result<T, Vec<E>>
@@ -659,3 +784,24 @@ where I: Iterator<Item = Result<T, E>>
         }
     }
 }
+
+/// Assigns the variable with the name to the target. Destructuring assignments
+/// are compiled as one of these for each element of the tuple.
+pub(crate) fn assign_from_variable(target: &Assignable, name: &str) -> Statement {
+    let span = target.span;
+    let variable = Assignable {
+        span,
+        kind: AssignableKind::Read(Identifier { span, name: name.to_string() }),
+    };
+    Statement {
+        span,
+        kind: StatementKind::Assignment {
+            kind: ParserOp::Nop,
+            target: target.clone(),
+            value: Expression { span, kind: ExpressionKind::Get(variable) },
+        },
+        comments: Vec::new(),
+        trailing_comments: Vec::new(),
+        annotations: Vec::new(),
+    }
+}
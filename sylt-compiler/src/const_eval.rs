@@ -0,0 +1,613 @@
+//! Evaluates calls to `const fn`s when compiling.
+//!
+//! A constant defined by calling a `const fn` with literal arguments, like
+//! `SIZE :: square(3)`, is replaced by the value it evaluates to - so the
+//! program is compiled as if it said `SIZE :: 9`. Only pure computations
+//! are allowed: arithmetic, comparisons, lists, tuples, local variables,
+//! branches, loops and calls to other `const fn`s. Calling anything else
+//! from a `const fn` is an error, since it might do IO.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+use sylt_common::error::Error;
+use sylt_parser::expression::ComparisonKind;
+use sylt_parser::visitor::{walk_assignable, Visitor};
+use sylt_parser::{
+    Assignable, AssignableKind, Expression, ExpressionKind, Op as ParserOp, Span, Statement,
+    StatementKind, AST,
+};
+
+/// The number of statements one evaluation may run, so a loop that never
+/// ends fails instead of hanging the compiler.
+const MAX_STEPS: usize = 1_000_000;
+/// How deep `const fn`s may call each other.
+const MAX_DEPTH: usize = 256;
+
+/// A value that is known when compiling.
+#[derive(Clone, Debug, PartialEq)]
+enum Constant {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+    List(Vec<Constant>),
+    Tuple(Vec<Constant>),
+    Nil,
+}
+
+impl Constant {
+    fn into_expression(self, span: Span) -> Expression {
+        let kind = match self {
+            Constant::Int(i) => ExpressionKind::Int(i),
+            Constant::Float(f) => ExpressionKind::Float(f),
+            Constant::Bool(b) => ExpressionKind::Bool(b),
+            Constant::Str(s) => ExpressionKind::Str(s),
+            Constant::List(l) => {
+                ExpressionKind::List(l.into_iter().map(|c| c.into_expression(span)).collect())
+            }
+            Constant::Tuple(t) => {
+                ExpressionKind::Tuple(t.into_iter().map(|c| c.into_expression(span)).collect())
+            }
+            Constant::Nil => ExpressionKind::Nil,
+        };
+        Expression { span, kind }
+    }
+
+    /// An int paired with a float becomes a float, like `Value::promote`.
+    fn promote(a: Constant, b: Constant) -> (Constant, Constant) {
+        match (a, b) {
+            (Constant::Int(a), Constant::Float(b)) => (Constant::Float(a as f64), Constant::Float(b)),
+            (Constant::Float(a), Constant::Int(b)) => (Constant::Float(a), Constant::Float(b as f64)),
+            (a, b) => (a, b),
+        }
+    }
+
+    /// If the constants have types that can be compared with `==`. Only the
+    /// same types can, but `nil` can be compared with anything since it may
+    /// come from an optional type.
+    fn same_type(a: &Constant, b: &Constant) -> bool {
+        use Constant::*;
+        match (a, b) {
+            (Nil, _) | (_, Nil) => true,
+            (List(a), List(b)) => {
+                let elements: Vec<_> = a.iter().chain(b.iter()).collect();
+                elements.windows(2).all(|pair| Constant::same_type(pair[0], pair[1]))
+            }
+            (Tuple(a), Tuple(b)) => {
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| Constant::same_type(a, b))
+            }
+            (a, b) => std::mem::discriminant(a) == std::mem::discriminant(b),
+        }
+    }
+}
+
+struct ConstFn {
+    params: Vec<String>,
+    body: Statement,
+}
+
+/// Where the evaluation of a statement goes next.
+enum Flow {
+    Next,
+    Break,
+    Continue,
+    Ret(Constant),
+}
+
+/// The variables in scope, innermost scope last.
+type Scopes = Vec<HashMap<String, Constant>>;
+
+struct Evaluator<'a> {
+    file: &'a Path,
+    functions: &'a HashMap<String, ConstFn>,
+    steps: usize,
+    depth: usize,
+    /// Set when something is left for the typechecker to report, like
+    /// comparing an `int` to a `str`. The call isn't folded then.
+    left_for_typechecker: bool,
+}
+
+impl<'a> Evaluator<'a> {
+    fn new(file: &'a Path, functions: &'a HashMap<String, ConstFn>) -> Self {
+        Self { file, functions, steps: 0, depth: 0, left_for_typechecker: false }
+    }
+
+    fn error(&self, span: Span, message: String) -> Box<Error> {
+        Box::new(Error::CompileError {
+            file: self.file.to_path_buf(),
+            span,
+            message: Some(message),
+        })
+    }
+
+    fn call(&mut self, name: &str, args: Vec<Constant>, span: Span) -> Result<Constant, Box<Error>> {
+        let function = &self.functions[name];
+        if function.params.len() != args.len() {
+            return Err(self.error(
+                span,
+                format!(
+                    "'{}' takes {} arguments but got {}",
+                    name,
+                    function.params.len(),
+                    args.len()
+                ),
+            ));
+        }
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error(
+                span,
+                format!("The const fn '{}' calls itself too deeply to evaluate", name),
+            ));
+        }
+
+        let mut scopes = vec![function.params.iter().cloned().zip(args).collect()];
+        self.depth += 1;
+        let flow = self.statement(&mut scopes, &function.body);
+        self.depth -= 1;
+        match flow? {
+            Flow::Ret(value) => Ok(value),
+            _ => Ok(Constant::Nil),
+        }
+    }
+
+    fn statement(&mut self, scopes: &mut Scopes, statement: &Statement) -> Result<Flow, Box<Error>> {
+        use StatementKind::*;
+
+        self.steps += 1;
+        if self.steps > MAX_STEPS {
+            return Err(self.error(
+                statement.span,
+                "The const fn runs for too long to evaluate".to_string(),
+            ));
+        }
+
+        match &statement.kind {
+            EmptyStatement => {}
+
+            Block { statements } => {
+                scopes.push(HashMap::new());
+                for statement in statements.iter() {
+                    match self.statement(scopes, statement) {
+                        Ok(Flow::Next) => {}
+                        flow => {
+                            scopes.pop();
+                            return flow;
+                        }
+                    }
+                }
+                scopes.pop();
+            }
+
+            Definition { ident, value, .. } => {
+                let value = self.expression(scopes, value)?;
+                scopes.last_mut().unwrap().insert(ident.name.clone(), value);
+            }
+
+            Assignment { kind, target, value } => {
+                let ident = match &target.kind {
+                    AssignableKind::Read(ident) => ident,
+                    _ => {
+                        return Err(self.error(
+                            target.span,
+                            "Only variables can be assigned in a const fn".to_string(),
+                        ));
+                    }
+                };
+                let value = self.expression(scopes, value)?;
+                let value = match kind {
+                    ParserOp::Nop => value,
+                    op => {
+                        let current = self.read(scopes, &ident.name, target.span)?;
+                        self.arithmetic(op, current, value, statement.span)?
+                    }
+                };
+                match scopes.iter_mut().rev().find_map(|scope| scope.get_mut(&ident.name)) {
+                    Some(variable) => *variable = value,
+                    None => {
+                        return Err(self.error(
+                            target.span,
+                            format!("'{}' isn't a variable in the const fn", ident.name),
+                        ));
+                    }
+                }
+            }
+
+            If { condition, pass, fail } => {
+                return if self.condition(scopes, condition)? {
+                    self.statement(scopes, pass)
+                } else {
+                    self.statement(scopes, fail)
+                };
+            }
+
+            Loop { label: None, condition, body } => {
+                while self.condition(scopes, condition)? {
+                    match self.statement(scopes, body)? {
+                        Flow::Next | Flow::Continue => {}
+                        Flow::Break => break,
+                        ret @ Flow::Ret(_) => return Ok(ret),
+                    }
+                }
+            }
+
+            Break { label: None, value: None } => return Ok(Flow::Break),
+            Continue { label: None } => return Ok(Flow::Continue),
+
+            Ret { value } => return Ok(Flow::Ret(self.expression(scopes, value)?)),
+
+            StatementExpression { value } => {
+                self.expression(scopes, value)?;
+            }
+
+            Assert { expr, message } => {
+                if !self.condition(scopes, expr)? {
+                    let message = message.clone().unwrap_or_else(|| "Assertion failed".to_string());
+                    return Err(self.error(
+                        statement.span,
+                        format!("{} - when evaluating a const fn", message),
+                    ));
+                }
+            }
+
+            _ => {
+                return Err(self.error(
+                    statement.span,
+                    "This statement can't be evaluated in a const fn".to_string(),
+                ));
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    fn condition(&mut self, scopes: &mut Scopes, expression: &Expression) -> Result<bool, Box<Error>> {
+        match self.expression(scopes, expression)? {
+            Constant::Bool(b) => Ok(b),
+            value => Err(self.error(
+                expression.span,
+                format!("Expected a bool but got {:?} in a const fn", value),
+            )),
+        }
+    }
+
+    fn read(&self, scopes: &Scopes, name: &str, span: Span) -> Result<Constant, Box<Error>> {
+        match scopes.iter().rev().find_map(|scope| scope.get(name)) {
+            Some(value) => Ok(value.clone()),
+            None => Err(self.error(span, format!("'{}' isn't known when compiling", name))),
+        }
+    }
+
+    fn assignable(&mut self, scopes: &mut Scopes, assignable: &Assignable) -> Result<Constant, Box<Error>> {
+        match &assignable.kind {
+            AssignableKind::Read(ident) => self.read(scopes, &ident.name, assignable.span),
+
+            AssignableKind::Call(callee, args) => match &callee.kind {
+                AssignableKind::Read(ident) if self.functions.contains_key(&ident.name) => {
+                    let args = args
+                        .iter()
+                        .map(|arg| self.expression(scopes, arg))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    self.call(&ident.name, args, assignable.span)
+                }
+                _ => Err(self.error(
+                    assignable.span,
+                    "Only const fns can be called when compiling".to_string(),
+                )),
+            },
+
+            AssignableKind::Index(indexed, index) => {
+                let indexed = self.assignable(scopes, indexed)?;
+                let index = self.expression(scopes, index)?;
+                let element = match (&indexed, index) {
+                    (Constant::List(values) | Constant::Tuple(values), Constant::Int(i)) => {
+                        usize::try_from(i).ok().and_then(|i| values.get(i))
+                    }
+                    _ => None,
+                };
+                match element {
+                    Some(element) => Ok(element.clone()),
+                    None => Err(self.error(
+                        assignable.span,
+                        format!("Cannot index {:?} in a const fn", indexed),
+                    )),
+                }
+            }
+
+            _ => Err(self.error(
+                assignable.span,
+                "This can't be evaluated in a const fn".to_string(),
+            )),
+        }
+    }
+
+    fn arithmetic(&self, op: &ParserOp, a: Constant, b: Constant, span: Span) -> Result<Constant, Box<Error>> {
+        use Constant::*;
+
+        let (a, b) = Constant::promote(a, b);
+        let result = match (op, &a, &b) {
+            (ParserOp::Div | ParserOp::Mod, Int(_), Int(0)) => {
+                return Err(self.error(span, "Division by zero in a const fn".to_string()));
+            }
+            (ParserOp::Add, Int(a), Int(b)) => a.checked_add(*b).map(Int),
+            (ParserOp::Sub, Int(a), Int(b)) => a.checked_sub(*b).map(Int),
+            (ParserOp::Mul, Int(a), Int(b)) => a.checked_mul(*b).map(Int),
+            (ParserOp::Div, Int(a), Int(b)) => a.checked_div(*b).map(Int),
+            (ParserOp::Mod, Int(a), Int(b)) => a.checked_rem(*b).map(Int),
+            (ParserOp::Add, Float(a), Float(b)) => Some(Float(a + b)),
+            (ParserOp::Sub, Float(a), Float(b)) => Some(Float(a - b)),
+            (ParserOp::Mul, Float(a), Float(b)) => Some(Float(a * b)),
+            (ParserOp::Div, Float(a), Float(b)) => Some(Float(a / b)),
+            (ParserOp::Mod, Float(a), Float(b)) => Some(Float(a % b)),
+            (ParserOp::Add, Str(a), Str(b)) => Some(Str(format!("{}{}", a, b))),
+            _ => {
+                return Err(self.error(
+                    span,
+                    format!("Cannot apply {:?} to {:?} and {:?} in a const fn", op, a, b),
+                ));
+            }
+        };
+        result.ok_or_else(|| self.error(span, "Integer overflow in a const fn".to_string()))
+    }
+
+    /// Stops the evaluation and leaves the call for the typechecker, so the
+    /// program fails the same way whether it's folded or not.
+    fn leave_for_typechecker(&mut self, span: Span, message: String) -> Box<Error> {
+        self.left_for_typechecker = true;
+        self.error(span, message)
+    }
+
+    fn equals(&mut self, a: &Constant, b: &Constant, span: Span) -> Result<bool, Box<Error>> {
+        if Constant::same_type(a, b) {
+            Ok(a == b)
+        } else {
+            Err(self.leave_for_typechecker(span, format!("Cannot compare {:?} and {:?} in a const fn", a, b)))
+        }
+    }
+
+    fn less(&mut self, a: &Constant, b: &Constant, span: Span) -> Result<bool, Box<Error>> {
+        use Constant::*;
+
+        match Constant::promote(a.clone(), b.clone()) {
+            (Int(a), Int(b)) => Ok(a < b),
+            (Float(a), Float(b)) => Ok(a < b),
+            (Str(a), Str(b)) => Ok(a < b),
+            _ => Err(self.leave_for_typechecker(span, format!("Cannot compare {:?} and {:?} in a const fn", a, b))),
+        }
+    }
+
+    fn expression(&mut self, scopes: &mut Scopes, expression: &Expression) -> Result<Constant, Box<Error>> {
+        use ExpressionKind::*;
+
+        let span = expression.span;
+        Ok(match &expression.kind {
+            Int(i) => Constant::Int(*i),
+            Float(f) => Constant::Float(*f),
+            Bool(b) => Constant::Bool(*b),
+            Str(s) => Constant::Str(s.clone()),
+            Nil => Constant::Nil,
+
+            Get(assignable) => self.assignable(scopes, assignable)?,
+            Parenthesis(a) => self.expression(scopes, a)?,
+
+            Add(a, b) | Sub(a, b) | Mul(a, b) | Div(a, b) | Mod(a, b) => {
+                let op = match &expression.kind {
+                    Add(..) => ParserOp::Add,
+                    Sub(..) => ParserOp::Sub,
+                    Mul(..) => ParserOp::Mul,
+                    Div(..) => ParserOp::Div,
+                    _ => ParserOp::Mod,
+                };
+                let a = self.expression(scopes, a)?;
+                let b = self.expression(scopes, b)?;
+                self.arithmetic(&op, a, b, span)?
+            }
+
+            Neg(a) => match self.expression(scopes, a)? {
+                Constant::Int(i) => Constant::Int(i.checked_neg().ok_or_else(|| {
+                    self.error(span, "Integer overflow in a const fn".to_string())
+                })?),
+                Constant::Float(f) => Constant::Float(-f),
+                a => return Err(self.error(span, format!("Cannot negate {:?} in a const fn", a))),
+            },
+
+            Comparison(a, kind, b) => {
+                let a = self.expression(scopes, a)?;
+                let b = self.expression(scopes, b)?;
+                Constant::Bool(match kind {
+                    ComparisonKind::Equals => self.equals(&a, &b, span)?,
+                    ComparisonKind::NotEquals => !self.equals(&a, &b, span)?,
+                    ComparisonKind::Less => self.less(&a, &b, span)?,
+                    ComparisonKind::Greater => self.less(&b, &a, span)?,
+                    ComparisonKind::LessEqual => !self.less(&b, &a, span)?,
+                    ComparisonKind::GreaterEqual => !self.less(&a, &b, span)?,
+                    ComparisonKind::In => match &b {
+                        Constant::List(values) | Constant::Tuple(values) => values.contains(&a),
+                        _ => {
+                            return Err(self.error(
+                                span,
+                                format!("Cannot look for {:?} in {:?} in a const fn", a, b),
+                            ));
+                        }
+                    },
+                })
+            }
+
+            And(a, b) => Constant::Bool(self.condition(scopes, a)? && self.condition(scopes, b)?),
+            Or(a, b) => Constant::Bool(self.condition(scopes, a)? || self.condition(scopes, b)?),
+            Not(a) => Constant::Bool(!self.condition(scopes, a)?),
+
+            IfExpression { condition, pass, fail } => {
+                if self.condition(scopes, condition)? {
+                    self.expression(scopes, pass)?
+                } else {
+                    self.expression(scopes, fail)?
+                }
+            }
+
+            List(values) => Constant::List(
+                values
+                    .iter()
+                    .map(|value| self.expression(scopes, value))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Tuple(values) => Constant::Tuple(
+                values
+                    .iter()
+                    .map(|value| self.expression(scopes, value))
+                    .collect::<Result<_, _>>()?,
+            ),
+
+            _ => {
+                return Err(self.error(span, "This can't be evaluated in a const fn".to_string()));
+            }
+        })
+    }
+}
+
+/// Finds calls in a `const fn` to functions that aren't `const fn`s.
+struct NonConstCalls<'a> {
+    file: &'a Path,
+    function: &'a str,
+    functions: &'a HashMap<String, ConstFn>,
+    errors: Vec<Error>,
+}
+
+impl Visitor for NonConstCalls<'_> {
+    fn visit_assignable(&mut self, assignable: &Assignable) {
+        let callee = match &assignable.kind {
+            AssignableKind::Call(callee, _) | AssignableKind::ArrowCall(_, callee, _) => Some(callee),
+            _ => None,
+        };
+        if let Some(callee) = callee {
+            let message = match &callee.kind {
+                AssignableKind::Read(ident) if self.functions.contains_key(&ident.name) => None,
+                AssignableKind::Read(ident) => Some(format!(
+                    "The const fn '{}' can't call '{}', since it isn't a const fn",
+                    self.function, ident.name
+                )),
+                _ => Some(format!(
+                    "The const fn '{}' can only call other const fns",
+                    self.function
+                )),
+            };
+            if let Some(message) = message {
+                self.errors.push(Error::CompileError {
+                    file: self.file.to_path_buf(),
+                    span: assignable.span,
+                    message: Some(message),
+                });
+            }
+        }
+        walk_assignable(self, assignable);
+    }
+}
+
+/// Replaces constants defined by calling a `const fn` with the value the call
+/// gives, in every module of the tree.
+///
+/// # Errors
+///
+/// Returns a [Error::CompileError] for every `const fn` that calls something
+/// that isn't a `const fn`, and for calls that fail to evaluate.
+pub(crate) fn fold(tree: &mut AST) -> Result<(), Vec<Error>> {
+    let mut errors = Vec::new();
+    for (file, module) in tree.modules.iter_mut() {
+        let functions = const_fns(&module.statements);
+        let mut illegal = non_const_calls(file, &functions);
+        if !illegal.is_empty() {
+            errors.append(&mut illegal);
+            continue;
+        }
+
+        for statement in module.statements.iter_mut() {
+            if let StatementKind::Definition { value, .. } = &mut statement.kind {
+                match fold_call(file, &functions, value) {
+                    Ok(Some(folded)) => *value = folded,
+                    Ok(None) => {}
+                    Err(err) => errors.push(*err),
+                }
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// The `const fn`s defined in the outer scope of a module.
+fn const_fns(statements: &[Statement]) -> HashMap<String, ConstFn> {
+    statements
+        .iter()
+        .filter_map(|statement| match &statement.kind {
+            StatementKind::Definition { ident, value, .. } => match &value.kind {
+                ExpressionKind::Function { params, body, is_const: true, .. } => Some((
+                    ident.name.clone(),
+                    ConstFn {
+                        params: params.iter().map(|(param, _)| param.name.clone()).collect(),
+                        body: Statement::clone(body),
+                    },
+                )),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+fn non_const_calls(file: &PathBuf, functions: &HashMap<String, ConstFn>) -> Vec<Error> {
+    let mut names: Vec<_> = functions.keys().collect();
+    // Report the errors in the same order every time.
+    names.sort();
+    names
+        .into_iter()
+        .flat_map(|name| {
+            let mut visitor = NonConstCalls {
+                file,
+                function: name,
+                functions,
+                errors: Vec::new(),
+            };
+            visitor.visit_statement(&functions[name].body);
+            visitor.errors
+        })
+        .collect()
+}
+
+/// Evaluates the value if it's a call to a `const fn` with arguments that
+/// are known when compiling.
+fn fold_call(
+    file: &Path,
+    functions: &HashMap<String, ConstFn>,
+    value: &Expression,
+) -> Result<Option<Expression>, Box<Error>> {
+    let (name, args) = match &value.kind {
+        ExpressionKind::Get(Assignable { kind: AssignableKind::Call(callee, args), .. }) => {
+            match &callee.kind {
+                AssignableKind::Read(ident) if functions.contains_key(&ident.name) => {
+                    (&ident.name, args)
+                }
+                _ => return Ok(None),
+            }
+        }
+        _ => return Ok(None),
+    };
+
+    let mut evaluator = Evaluator::new(file, functions);
+    // Arguments that aren't known when compiling leave the call for later.
+    let args = match args
+        .iter()
+        .map(|arg| evaluator.expression(&mut Vec::new(), arg))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(args) => args,
+        Err(_) => return Ok(None),
+    };
+    match evaluator.call(name, args, value.span) {
+        Ok(result) => Ok(Some(result.into_expression(value.span))),
+        Err(_) if evaluator.left_for_typechecker => Ok(None),
+        Err(err) => Err(err),
+    }
+}
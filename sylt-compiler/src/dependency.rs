@@ -138,7 +138,24 @@ fn statement_dependencies(ctx: &mut Context, statement: &Statement) -> BTreeSet<
             .cloned()
             .collect()
         },
-        Loop { condition, body } => dependencies(ctx, condition)
+        TypeMatch { value, binding, arms, fallback } => {
+            let mut deps = dependencies(ctx, value);
+            for (ty, body) in arms.iter() {
+                deps.extend(type_dependencies(ctx, ty));
+                let vars_before = ctx.variables.len();
+                if let Some(binding) = binding {
+                    ctx.shadow(&binding.name);
+                }
+                deps.extend(statement_dependencies(ctx, body));
+                ctx.variables.truncate(vars_before);
+            }
+            if let Some(fallback) = fallback {
+                deps.extend(statement_dependencies(ctx, fallback));
+            }
+            deps
+        },
+        Defer { body } => statement_dependencies(ctx, body),
+        Loop { condition, body, .. } => dependencies(ctx, condition)
             .union(&statement_dependencies(ctx, body))
             .cloned()
             .collect(),
@@ -155,13 +172,29 @@ fn statement_dependencies(ctx: &mut Context, statement: &Statement) -> BTreeSet<
             ctx.shadow(&ident.name);
             dependencies(ctx, value)
         },
+        DestructuringDefinition { idents, value, .. } => {
+            for ident in idents.iter() {
+                ctx.shadow(&ident.name);
+            }
+            dependencies(ctx, value)
+        },
+        DestructuringAssignment { targets, value } => {
+            let mut deps = dependencies(ctx, value);
+            for target in targets.iter() {
+                deps.extend(assignable_dependencies(ctx, target));
+            }
+            deps
+        },
 
         | Ret { value }
-        | StatementExpression { value } => dependencies(ctx, value),
+        | StatementExpression { value }
+        | Assert { expr: value, .. } => dependencies(ctx, value),
+
+        Break { value: Some(value), .. } => dependencies(ctx, value),
 
         | Blob { .. }
-        | Break
-        | Continue
+        | Break { .. }
+        | Continue { .. }
         | EmptyStatement
         | ExternalDefinition { .. }
         | IsCheck { .. }
@@ -185,6 +218,7 @@ fn dependencies(ctx: &mut Context, expression: &Expression) -> BTreeSet<Name> {
         | Sub(lhs, rhs)
         | Mul(lhs, rhs)
         | Div(lhs, rhs)
+        | Mod(lhs, rhs)
         | AssertEq(lhs, rhs)
         | And(lhs, rhs)
         | Or(lhs, rhs) => dependencies(ctx, lhs)
@@ -192,6 +226,15 @@ fn dependencies(ctx: &mut Context, expression: &Expression) -> BTreeSet<Name> {
             .cloned()
             .collect(),
 
+        Is(expr, ty) => dependencies(ctx, expr)
+            .union(&type_dependencies(ctx, ty))
+            .cloned()
+            .collect(),
+
+        Loop(body) => statement_dependencies(ctx, body),
+
+        Assert { condition, .. } => dependencies(ctx, condition),
+
         IfExpression { condition, pass, fail } => {
             [pass, fail, condition].iter()
                 .map(|expr| dependencies(ctx, expr))
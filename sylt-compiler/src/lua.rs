@@ -15,9 +15,22 @@ macro_rules! write {
     };
 }
 
+/// A loop we're inside of. Counts the jumps to its labels, so only the
+/// labels that are used are written out.
+struct LoopFrame {
+    label: Option<String>,
+    id: usize,
+    defer_depth: usize,
+    continues: usize,
+    breaks: usize,
+}
+
 pub struct LuaCompiler<'t> {
     compiler: &'t mut Compiler,
-    loops: Vec<usize>,
+    loops: Vec<LoopFrame>,
+    num_loops: usize,
+    /// The deferred statements of every block we're in, innermost last.
+    defers: Vec<Vec<Statement>>,
     file: Box<dyn Write>,
 }
 
@@ -26,6 +39,8 @@ impl<'t> LuaCompiler<'t> {
         Self {
             compiler,
             loops: Vec::new(),
+            num_loops: 0,
+            defers: Vec::new(),
             file,
         }
     }
@@ -39,6 +54,28 @@ impl<'t> LuaCompiler<'t> {
         }
     }
 
+    /// The innermost loop, or the innermost loop with the label.
+    fn find_loop(&mut self, label: &Option<Identifier>) -> &mut LoopFrame {
+        match label {
+            Some(label) => self
+                .loops
+                .iter_mut()
+                .rfind(|frame| frame.label.as_ref() == Some(&label.name)),
+            None => self.loops.last_mut(),
+        }
+        .expect("Not inside a loop")
+    }
+
+    /// Writes the deferred statements of the blocks from `depth` and in,
+    /// for when they're jumped out of. Innermost blocks go first.
+    fn write_defers(&mut self, ctx: Context, depth: usize) {
+        let scopes = self.defers.split_off(depth);
+        for deferred in scopes.iter().rev().flat_map(|scope| scope.iter().rev()) {
+            self.statement(deferred, ctx);
+        }
+        self.defers.extend(scopes);
+    }
+
     fn write_global(&mut self, slot: usize) {
         write!(self, "GLOBAL_{}", slot);
     }
@@ -135,6 +172,20 @@ impl<'t> LuaCompiler<'t> {
                 );
             }
 
+            Is(_, _) => {
+                error!(
+                    self.compiler,
+                    ctx, expression.span, "Is-expressions are not supported in the lua-compiler"
+                );
+            }
+
+            Loop(_) => {
+                error!(
+                    self.compiler,
+                    ctx, expression.span, "Loop expressions are not supported in the lua-compiler"
+                );
+            }
+
             Add(a, b) => {
                 write!(self, "__ADD(");
                 self.expression(a, ctx);
@@ -143,8 +194,21 @@ impl<'t> LuaCompiler<'t> {
                 write!(self, ")");
             }
             Sub(a, b) => self.bin_op(a, b, "-", ctx),
-            Mul(a, b) => self.bin_op(a, b, "*", ctx),
+            Mul(a, b) => {
+                write!(self, "__MUL(");
+                self.expression(a, ctx);
+                write!(self, ",");
+                self.expression(b, ctx);
+                write!(self, ")");
+            }
             Div(a, b) => self.bin_op(a, b, "/", ctx),
+            Mod(a, b) => {
+                write!(self, "__MOD(");
+                self.expression(a, ctx);
+                write!(self, ",");
+                self.expression(b, ctx);
+                write!(self, ")");
+            }
 
             Comparison(a, cmp, b) => match cmp {
                 Equals => self.bin_op(a, b, "==", ctx),
@@ -170,6 +234,15 @@ impl<'t> LuaCompiler<'t> {
                 write!(self, ", \"Assert failed\")");
             }
 
+            Assert { condition, message } => {
+                write!(self, "__ASSERT(");
+                self.expression(condition, ctx);
+                match message {
+                    Some(message) => write!(self, ", {:?})", format!("Assertion failed: {}", message)),
+                    None => write!(self, ", \"Assertion failed\")"),
+                }
+            }
+
             Neg(a) => {
                 write!(self, "-");
                 self.expression(a, ctx);
@@ -201,10 +274,9 @@ impl<'t> LuaCompiler<'t> {
             }
 
             Function {
-                name: _,
                 params,
-                ret: _,
                 body,
+                ..
             } => {
                 // TODO(ed): We don't use multiple frames here...
                 let s = self.compiler.frames.last().unwrap().variables.len();
@@ -220,7 +292,9 @@ impl<'t> LuaCompiler<'t> {
                     self.write_slot(slot);
                 }
                 write!(self, ")");
+                let outer_defers = std::mem::take(&mut self.defers);
                 self.statement(body, ctx);
+                self.defers = outer_defers;
                 write!(self, "end");
                 self.compiler
                     .frames
@@ -277,7 +351,8 @@ impl<'t> LuaCompiler<'t> {
                 // TODO(ed): Fill in empty fields with nil-value
                 write!(self, "__BLOB { ");
                 for (k, v) in fields.iter() {
-                    write!(self, "{} =", k);
+                    // Quoted, since fields can be named after lua keywords.
+                    write!(self, "[\"{}\"] =", k);
                     self.expression(v, ctx);
                     write!(self, ",");
                 }
@@ -444,6 +519,52 @@ impl<'t> LuaCompiler<'t> {
                 );
             }
 
+            DestructuringDefinition { idents, kind, value } => {
+                let slots: Vec<_> = idents
+                    .iter()
+                    .map(|ident| self.compiler.define(&ident.name, *kind, ident.span))
+                    .collect();
+                write!(self, "local");
+                for (i, slot) in slots.iter().enumerate() {
+                    if i != 0 {
+                        write!(self, ",");
+                    }
+                    self.write_slot(*slot);
+                }
+                write!(self, "= __UNPACK(");
+                self.expression(value, ctx);
+                write!(self, ", {})", idents.len());
+                for slot in slots {
+                    self.compiler.activate(slot);
+                }
+            }
+
+            DestructuringAssignment { targets, value } => {
+                let s = self.compiler.frames.last().unwrap().variables.len();
+                write!(self, "do local");
+                for i in 0..targets.len() {
+                    if i != 0 {
+                        write!(self, ",");
+                    }
+                    let slot = self.compiler.define(&format!("/unpacked/{}", i), VarKind::Const, statement.span);
+                    self.compiler.activate(slot);
+                    self.write_slot(slot);
+                }
+                write!(self, "= __UNPACK(");
+                self.expression(value, ctx);
+                write!(self, ", {});", targets.len());
+                for (i, target) in targets.iter().enumerate() {
+                    self.statement(&assign_from_variable(target, &format!("/unpacked/{}", i)), ctx);
+                }
+                write!(self, "end");
+                self.compiler
+                    .frames
+                    .last_mut()
+                    .unwrap()
+                    .variables
+                    .truncate(s);
+            }
+
             #[rustfmt::skip]
             Assignment { target, value, kind } => {
                 if *kind == Op::Nop {
@@ -481,12 +602,15 @@ impl<'t> LuaCompiler<'t> {
                         }
                     }
                 } else {
-                    let op = match kind {
+                    // Written around and between the two operands, since
+                    // modulo is a function call and not an operator in lua.
+                    let (open, op, close) = match kind {
                         Op::Nop => unreachable!(),
-                        Op::Add => "+",
-                        Op::Sub => "-",
-                        Op::Mul => "*",
-                        Op::Div => "/",
+                        Op::Add => ("", "+", ""),
+                        Op::Sub => ("", "-", ""),
+                        Op::Mul => ("", "*", ""),
+                        Op::Div => ("", "/", ""),
+                        Op::Mod => ("__MOD(", ",", ")"),
                     };
 
                     match &target.kind {
@@ -498,15 +622,18 @@ impl<'t> LuaCompiler<'t> {
                                 write!(self, "nil ; end ;");
                                 self.read_identifier(&field.name, statement.span, ctx, namespace);
                                 write!(self, "=");
+                                write!(self, open);
                                 self.read_identifier(&field.name, statement.span, ctx, namespace);
                                 write!(self, "{}", op);
                                 self.expression(value, ctx);
+                                write!(self, close);
                             } else {
                                 write!(self, ";");
-                                write!(self, "__ASSIGN_INDEX( tmp_ass, \"{}\", __INDEX( tmp_ass, \"{}\" ) {}", field.name, field.name, op);
+                                write!(self, "__ASSIGN_INDEX( tmp_ass, \"{}\", {}__INDEX( tmp_ass, \"{}\" ) {}", field.name, open, field.name, op);
                                 write!(self, "(");
                                 self.expression(value, ctx);
                                 write!(self, ")");
+                                write!(self, close);
                                 write!(self, ")");
                                 write!(self, ";");
                                 write!(self, "end");
@@ -519,10 +646,11 @@ impl<'t> LuaCompiler<'t> {
                             write!(self, "local tmp_expr =");
                             self.expression(index, ctx);
                             write!(self, ";");
-                            write!(self, "__ASSIGN_INDEX( tmp_ass, tmp_expr, __INDEX( tmp_ass, tmp_expr ) {}", op);
+                            write!(self, "__ASSIGN_INDEX( tmp_ass, tmp_expr, {}__INDEX( tmp_ass, tmp_expr ) {}", open, op);
                             write!(self, "(");
                             self.expression(value, ctx);
                             write!(self, ")");
+                            write!(self, close);
                             write!(self, ")");
                             write!(self, ";");
                             write!(self, "end");
@@ -531,9 +659,11 @@ impl<'t> LuaCompiler<'t> {
                             println!("{:?}", target.kind);
                             self.assignable(target, ctx);
                             write!(self, "=");
+                            write!(self, open);
                             self.assignable(target, ctx);
                             write!(self, op);
                             self.expression(value, ctx);
+                            write!(self, close);
                         }
                     }
                 }
@@ -549,9 +679,14 @@ impl<'t> LuaCompiler<'t> {
                 // TODO(ed): Some of these blocks are wrong - but it should still work.
                 let s = self.compiler.frames.last().unwrap().variables.len();
                 write!(self, "do");
+                self.defers.push(Vec::new());
                 for stmt in statements.iter() {
                     self.statement(stmt, ctx);
                 }
+                let defers = self.defers.pop().unwrap();
+                for deferred in defers.iter().rev() {
+                    self.statement(deferred, ctx);
+                }
                 write!(self, "end");
                 self.compiler
                     .frames
@@ -561,20 +696,31 @@ impl<'t> LuaCompiler<'t> {
                     .truncate(s);
             }
 
-            Loop { condition, body } => {
+            Loop { label, condition, body } => {
                 write!(self, "while");
                 self.expression(condition, ctx);
                 write!(self, "do");
-                self.loops.push(0);
+                self.num_loops += 1;
+                self.loops.push(LoopFrame {
+                    label: label.as_ref().map(|label| label.name.clone()),
+                    id: self.num_loops,
+                    defer_depth: self.defers.len(),
+                    continues: 0,
+                    breaks: 0,
+                });
                 write!(self, ";");
                 self.statement(body, ctx);
-                let l = self.loops.len();
-                if self.loops.pop().unwrap() > 0 {
-                    write!(self, "::CONTINUE_{}::", l);
+                let frame = self.loops.pop().unwrap();
+                if frame.continues > 0 {
+                    write!(self, "::CONTINUE_{}::", frame.id);
                     write!(self, ";");
                 }
                 write!(self, "end");
                 write!(self, ";");
+                if frame.breaks > 0 {
+                    write!(self, "::BREAK_{}::", frame.id);
+                    write!(self, ";");
+                }
             }
 
             If { condition, pass, fail } => {
@@ -590,23 +736,83 @@ impl<'t> LuaCompiler<'t> {
                 write!(self, ";");
             }
 
-            Continue => {
+            TypeMatch { .. } => {
+                error!(
+                    self.compiler,
+                    ctx, statement.span, "Type-matches are not supported in the lua-compiler"
+                );
+            }
+
+            Defer { body } => match self.defers.last_mut() {
+                Some(defers) => defers.push(Statement::clone(body)),
+                None => {
+                    error!(self.compiler, ctx, statement.span, "`defer` statement not in a block");
+                }
+            },
+
+            Continue { label } => {
+                let frame = self.find_loop(label);
+                frame.continues += 1;
+                let (id, depth) = (frame.id, frame.defer_depth);
+                self.write_defers(ctx, depth);
                 write!(self, "goto");
-                let cont = self.loops.len();
-                *self.loops.last_mut().unwrap() += 1;
-                write!(self, "CONTINUE_{}", cont);
+                write!(self, "CONTINUE_{}", id);
                 write!(self, ";");
             }
 
-            Break => {
+            Break { value: Some(value), .. } => {
+                error!(
+                    self.compiler,
+                    ctx, value.span, "Only loop expressions can be broken with a value"
+                );
+            }
+
+            Break { label: None, .. } => {
+                let depth = self.find_loop(&None).defer_depth;
+                self.write_defers(ctx, depth);
                 write!(self, "break");
                 write!(self, ";");
             }
 
+            // Lua can only break out of the innermost loop.
+            Break { label, .. } => {
+                let frame = self.find_loop(label);
+                frame.breaks += 1;
+                let (id, depth) = (frame.id, frame.defer_depth);
+                self.write_defers(ctx, depth);
+                write!(self, "goto");
+                write!(self, "BREAK_{}", id);
+                write!(self, ";");
+            }
+
             Unreachable {} => {
                 write!(self, "assert(false, \"unreachable\")");
             }
 
+            Assert { expr, message } => {
+                write!(self, "assert(");
+                self.expression(expr, ctx);
+                match message {
+                    Some(message) => write!(self, ", {:?})", format!("Assertion failed: {}", message)),
+                    None => write!(self, ", \"Assertion failed\")"),
+                }
+            }
+
+            Ret { value } if self.defers.iter().any(|defers| !defers.is_empty()) => {
+                // Lua only allows `return` last in a block.
+                let slot = self.compiler.define("/ret", VarKind::Const, statement.span);
+                self.compiler.activate(slot);
+                write!(self, "local");
+                self.write_slot(slot);
+                write!(self, "=");
+                self.expression(value, ctx);
+                write!(self, ";");
+                self.write_defers(ctx, 0);
+                write!(self, "do return");
+                self.write_slot(slot);
+                write!(self, "end");
+            }
+
             Ret { value } => {
                 write!(self, "return");
                 self.expression(value, ctx);
@@ -632,7 +838,8 @@ impl<'t> LuaCompiler<'t> {
             frame: self.compiler.frames.len() - 1,
             namespace: 0,
         };
-        self.read_identifier("start", span, ctx, 0);
+        let entry = self.compiler.entry.clone();
+        self.read_identifier(&entry, span, ctx, 0);
         write!(self, "()");
         write!(self, ";");
     }
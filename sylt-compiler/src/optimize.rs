@@ -0,0 +1,320 @@
+//! Propagates constants when compiling with `--optimize`.
+//!
+//! A read of a variable defined with `::` to a literal, like `SIZE :: 3`, is
+//! replaced by the literal. Arithmetic on literals is folded afterwards, so
+//! `AREA :: SIZE * SIZE` becomes `AREA :: 9` - which is then propagated too.
+//! Mutable variables, variables with an explicit type and reads of a variable
+//! that shadows a constant are left alone.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use sylt_parser::{
+    Assignable, AssignableKind, Expression, ExpressionKind, Span, Statement, StatementKind,
+    TypeKind, VarKind, AST,
+};
+
+/// A read of a constant that was replaced by its value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Propagation {
+    pub file: PathBuf,
+    pub span: Span,
+    pub name: String,
+    /// The literal the read was replaced by, as it's written in the source.
+    pub value: String,
+}
+
+impl fmt::Display for Propagation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: '{}' is replaced by {}",
+            self.file.display(),
+            self.span.line,
+            self.name,
+            self.value
+        )
+    }
+}
+
+/// The literals the variables in scope are bound to, innermost scope last. A
+/// variable bound to anything else is `None`, so it shadows constants in the
+/// outer scopes.
+type Scopes = Vec<HashMap<String, Option<ExpressionKind>>>;
+
+struct Propagator<'a> {
+    file: &'a Path,
+    propagations: Vec<Propagation>,
+}
+
+/// Replaces reads of constants with their values in every module of the tree.
+pub(crate) fn propagate(tree: &mut AST) -> Vec<Propagation> {
+    let mut propagations = Vec::new();
+    for (file, module) in tree.modules.iter_mut() {
+        let mut propagator = Propagator { file, propagations: Vec::new() };
+        // Globals can be read before they are defined, so a constant that
+        // becomes a literal late in the module needs another pass.
+        loop {
+            let globals = module
+                .statements
+                .iter()
+                .filter_map(|statement| match &statement.kind {
+                    StatementKind::Definition { ident, kind, ty, value } => {
+                        bound_literal(kind, &ty.kind, value).map(|literal| (ident.name.clone(), Some(literal)))
+                    }
+                    _ => None,
+                })
+                .collect();
+            let before = propagator.propagations.len();
+            let mut scopes = vec![globals];
+            for statement in module.statements.iter_mut() {
+                propagator.statement(&mut scopes, statement);
+            }
+            if propagator.propagations.len() == before {
+                break;
+            }
+        }
+        propagations.append(&mut propagator.propagations);
+    }
+    propagations
+}
+
+/// The literal a definition binds, if it can be propagated.
+fn bound_literal(kind: &VarKind, ty: &TypeKind, value: &Expression) -> Option<ExpressionKind> {
+    match (kind, ty, &value.kind) {
+        (
+            VarKind::Const,
+            TypeKind::Implied,
+            literal @ (ExpressionKind::Int(_)
+            | ExpressionKind::Float(_)
+            | ExpressionKind::Str(_)
+            | ExpressionKind::Bool(_)
+            | ExpressionKind::Nil),
+        ) => Some(literal.clone()),
+        _ => None,
+    }
+}
+
+fn source(literal: &ExpressionKind) -> String {
+    match literal {
+        ExpressionKind::Int(i) => format!("{}", i),
+        ExpressionKind::Float(f) => format!("{:?}", f),
+        ExpressionKind::Str(s) => format!("\"{}\"", s),
+        ExpressionKind::Bool(b) => format!("{}", b),
+        _ => "nil".to_string(),
+    }
+}
+
+/// Evaluates operators whose operands are all literals. Operations that
+/// would fail, like overflowing, are left for when the program runs.
+fn fold(kind: &ExpressionKind) -> Option<ExpressionKind> {
+    use ExpressionKind::*;
+    Some(match kind {
+        Parenthesis(a) => match &a.kind {
+            literal @ (Int(_) | Float(_) | Str(_) | Bool(_) | Nil) => literal.clone(),
+            _ => return None,
+        },
+        Neg(a) => match &a.kind {
+            Int(a) => Int(a.checked_neg()?),
+            Float(a) => Float(-a),
+            _ => return None,
+        },
+        Not(a) => match &a.kind {
+            Bool(a) => Bool(!a),
+            _ => return None,
+        },
+        Add(a, b) => match (&a.kind, &b.kind) {
+            (Int(a), Int(b)) => Int(a.checked_add(*b)?),
+            (Float(a), Float(b)) => Float(a + b),
+            (Str(a), Str(b)) => Str(format!("{}{}", a, b)),
+            _ => return None,
+        },
+        Sub(a, b) => match (&a.kind, &b.kind) {
+            (Int(a), Int(b)) => Int(a.checked_sub(*b)?),
+            (Float(a), Float(b)) => Float(a - b),
+            _ => return None,
+        },
+        Mul(a, b) => match (&a.kind, &b.kind) {
+            (Int(a), Int(b)) => Int(a.checked_mul(*b)?),
+            (Float(a), Float(b)) => Float(a * b),
+            _ => return None,
+        },
+        And(a, b) => match (&a.kind, &b.kind) {
+            (Bool(a), Bool(b)) => Bool(*a && *b),
+            _ => return None,
+        },
+        Or(a, b) => match (&a.kind, &b.kind) {
+            (Bool(a), Bool(b)) => Bool(*a || *b),
+            _ => return None,
+        },
+        _ => return None,
+    })
+}
+
+impl<'a> Propagator<'a> {
+    fn statement(&mut self, scopes: &mut Scopes, statement: &mut Statement) {
+        use StatementKind::*;
+
+        match &mut statement.kind {
+            Definition { ident, kind, ty, value } => {
+                self.expression(scopes, value);
+                let literal = bound_literal(kind, &ty.kind, value);
+                scopes.last_mut().unwrap().insert(ident.name.clone(), literal);
+            }
+            ExternalDefinition { ident, .. } => {
+                scopes.last_mut().unwrap().insert(ident.name.clone(), None);
+            }
+            DestructuringDefinition { idents, value, .. } => {
+                self.expression(scopes, value);
+                for ident in idents.iter() {
+                    scopes.last_mut().unwrap().insert(ident.name.clone(), None);
+                }
+            }
+            Assignment { target, value, .. } => {
+                self.assignable(scopes, target);
+                self.expression(scopes, value);
+            }
+            DestructuringAssignment { targets, value } => {
+                for target in targets.iter_mut() {
+                    self.assignable(scopes, target);
+                }
+                self.expression(scopes, value);
+            }
+            If { condition, pass, fail } => {
+                self.expression(scopes, condition);
+                self.statement(scopes, pass);
+                self.statement(scopes, fail);
+            }
+            Loop { condition, body, .. } => {
+                self.expression(scopes, condition);
+                self.statement(scopes, body);
+            }
+            TypeMatch { value, binding, arms, fallback } => {
+                // The binding is the variable itself, so it has to stay a read.
+                if binding.is_none() {
+                    self.expression(scopes, value);
+                }
+                let mut scope = HashMap::new();
+                if let Some(binding) = binding {
+                    scope.insert(binding.name.clone(), None);
+                }
+                scopes.push(scope);
+                for (_, body) in arms.iter_mut() {
+                    self.statement(scopes, body);
+                }
+                if let Some(fallback) = fallback {
+                    self.statement(scopes, fallback);
+                }
+                scopes.pop();
+            }
+            Break { value: Some(value), .. }
+            | Ret { value }
+            | StatementExpression { value }
+            | Assert { expr: value, .. } => self.expression(scopes, value),
+            Defer { body } => self.statement(scopes, body),
+            Block { statements } => {
+                scopes.push(HashMap::new());
+                for statement in statements.iter_mut() {
+                    self.statement(scopes, statement);
+                }
+                scopes.pop();
+            }
+            Use { .. }
+            | Blob { .. }
+            | Break { value: None, .. }
+            | Continue { .. }
+            | IsCheck { .. }
+            | Unreachable
+            | EmptyStatement => {}
+        }
+    }
+
+    fn expression(&mut self, scopes: &mut Scopes, expression: &mut Expression) {
+        use ExpressionKind::*;
+
+        match &mut expression.kind {
+            Get(Assignable { kind: AssignableKind::Read(ident), .. }) => {
+                let bound = scopes.iter().rev().find_map(|scope| scope.get(&ident.name));
+                if let Some(Some(literal)) = bound {
+                    self.propagations.push(Propagation {
+                        file: self.file.to_path_buf(),
+                        span: expression.span,
+                        name: ident.name.clone(),
+                        value: source(literal),
+                    });
+                    expression.kind = literal.clone();
+                }
+                return;
+            }
+            Get(assignable) => self.assignable(scopes, assignable),
+            Add(a, b)
+            | Sub(a, b)
+            | Mul(a, b)
+            | Div(a, b)
+            | Mod(a, b)
+            | Comparison(a, _, b)
+            | AssertEq(a, b)
+            | And(a, b)
+            | Or(a, b) => {
+                self.expression(scopes, a);
+                self.expression(scopes, b);
+            }
+            Neg(a) | Not(a) | Parenthesis(a) | Is(a, _) | Assert { condition: a, .. } => {
+                self.expression(scopes, a)
+            }
+            IfExpression { condition, pass, fail } => {
+                self.expression(scopes, condition);
+                self.expression(scopes, pass);
+                self.expression(scopes, fail);
+            }
+            Loop(body) => self.statement(scopes, body),
+            Function { params, body, .. } => {
+                scopes.push(params.iter().map(|(param, _)| (param.name.clone(), None)).collect());
+                self.statement(scopes, body);
+                scopes.pop();
+            }
+            Blob { fields, .. } => {
+                for (_, value) in fields.iter_mut() {
+                    self.expression(scopes, value);
+                }
+            }
+            Tuple(values) | List(values) | Set(values) | Dict(values) => {
+                for value in values.iter_mut() {
+                    self.expression(scopes, value);
+                }
+            }
+            TypeConstant(_) | Float(_) | Int(_) | Str(_) | Bool(_) | Nil => {}
+        }
+
+        if let Some(folded) = fold(&expression.kind) {
+            expression.kind = folded;
+        }
+    }
+
+    fn assignable(&mut self, scopes: &mut Scopes, assignable: &mut Assignable) {
+        use AssignableKind::*;
+
+        match &mut assignable.kind {
+            Read(_) => {}
+            Call(callee, args) => {
+                self.assignable(scopes, callee);
+                for arg in args.iter_mut() {
+                    self.expression(scopes, arg);
+                }
+            }
+            ArrowCall(first, callee, args) => {
+                self.expression(scopes, first);
+                self.assignable(scopes, callee);
+                for arg in args.iter_mut() {
+                    self.expression(scopes, arg);
+                }
+            }
+            Access(accessed, _) => self.assignable(scopes, accessed),
+            Index(indexed, index) => {
+                self.assignable(scopes, indexed);
+                self.expression(scopes, index);
+            }
+            Expression(expression) => self.expression(scopes, expression),
+        }
+    }
+}
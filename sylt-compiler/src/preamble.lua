@@ -48,6 +48,12 @@ __ASSIGN_INDEX = function(o, i, v)
 end
 
 
+__UNPACK = function(t, n)
+    local m = getmetatable(t)
+    assert(m and m._type == "tuple" and #t == n, "Cannot unpack into " .. n .. " targets")
+    return (table.unpack or unpack)(t)
+end
+
 __ADD = function(a, b)
     if type(a) == "string" and type(b) == "string" then
         return a .. b
@@ -55,6 +61,25 @@ __ADD = function(a, b)
     return a + b
 end
 
+__MUL = function(a, b)
+    if type(a) == "string" and type(b) == "number" then
+        assert(b >= 0, "Cannot repeat a string a negative number of times")
+        return string.rep(a, b)
+    end
+    if type(a) == "number" and type(b) == "string" then
+        return __MUL(b, a)
+    end
+    return a * b
+end
+
+-- Lua's `%` rounds towards negative infinity, sylt truncates like `math.fmod`.
+__MOD = function(a, b)
+    if type(a) == "number" and type(b) == "number" then
+        return math.fmod(a, b)
+    end
+    return a % b
+end
+
 __TUPLE_META = { _type = "tuple" }
 __TUPLE_META.__newindex = function()
     assert(false, "Tuples are immutable")
@@ -80,6 +105,13 @@ __TUPLE_META.__div = function(a, b)
     end
     return __TUPLE(out)
 end
+__TUPLE_META.__mod = function(a, b)
+    local out = {}
+    for x = 1, #a, 1 do
+        out[x] = __MOD(a[x], b[x])
+    end
+    return __TUPLE(out)
+end
 __TUPLE_META.__mul = function(a, b)
     local out = {}
     for x = 1, #a, 1 do
@@ -146,20 +178,17 @@ __LIST_META.__eq = function(a, b)
     return true
 end
 __LIST_META.__lt = function(a, b)
-    for x = 1, #a, 1 do
-        if not (a[x] < b[x]) then
+    for x = 1, math.min(#a, #b), 1 do
+        if a[x] < b[x] then
+            return true
+        elseif b[x] < a[x] then
             return false
         end
     end
-    return true
+    return #a < #b
 end
 __LIST_META.__le = function(a, b)
-    for x = 1, #a, 1 do
-        if not (a[x] <= b[x]) then
-            return false
-        end
-    end
-    return true
+    return not (b < a)
 end
 __LIST_META.__tostring = function(a)
     local out = "["
@@ -240,7 +269,16 @@ function __SET(obj)
 end
 
 __BLOB_META = { _type = "blob" }
+-- Blobs with an operator method, like `_add` for `+`, use it.
+__BLOB_META.__add = function(a, b) return a._add(a, b) end
+__BLOB_META.__sub = function(a, b) return a._sub(a, b) end
+__BLOB_META.__mul = function(a, b) return a._mul(a, b) end
+__BLOB_META.__div = function(a, b) return a._div(a, b) end
+__BLOB_META.__mod = function(a, b) return a._mod(a, b) end
 __BLOB_META.__eq = function(a, b)
+    if a._eq then
+        return a._eq(a, b)
+    end
     for k, _ in pairs(a) do
         if not b[k] then
             return false
@@ -320,13 +358,154 @@ function filter(l, f)
     return __LIST(o)
 end
 
+function count(l, f)
+    local c = 0
+    for _, v in pairs(l) do
+        if f(v) then
+            c = c + 1
+        end
+    end
+    return c
+end
+
+function take_while(l, f)
+    local o = {}
+    for _, v in ipairs(l) do
+        if not f(v) then
+            break
+        end
+        table.insert(o, v)
+    end
+    return __LIST(o)
+end
+
+function drop_while(l, f)
+    local o = {}
+    local dropping = true
+    for _, v in ipairs(l) do
+        dropping = dropping and f(v)
+        if not dropping then
+            table.insert(o, v)
+        end
+    end
+    return __LIST(o)
+end
+
+function sum_by(l, f)
+    local s = 0
+    for _, v in pairs(l) do
+        local x = f(v)
+        assert(type(x) == "number", "Can only sum numbers, but got " .. tostring(x))
+        s = s + x
+    end
+    return s
+end
+
+function __EXTREME_BY(l, f, better)
+    local best = __NIL
+    local best_key = nil
+    for _, v in ipairs(l) do
+        local key = f(v)
+        if best_key == nil or better(key, best_key) then
+            best = v
+            best_key = key
+        end
+    end
+    return best
+end
+-- Lua can't compare booleans with `<`, so false is ordered before true like in the VM
+function __LESS_KEY(a, b)
+    if type(a) == "boolean" and type(b) == "boolean" then
+        return not a and b
+    end
+    return a < b
+end
+function min_by(l, f) return __EXTREME_BY(l, f, __LESS_KEY) end
+function max_by(l, f) return __EXTREME_BY(l, f, function(a, b) return __LESS_KEY(b, a) end) end
+
+function zip(a, b)
+    local o = {}
+    for i = 1, math.min(#a, #b), 1 do
+        o[i] = __TUPLE({ a[i], b[i] })
+    end
+    return __LIST(o)
+end
+
+function enumerate(l)
+    local o = {}
+    for i, v in ipairs(l) do
+        o[i] = __TUPLE({ i - 1, v })
+    end
+    return __LIST(o)
+end
+
+function memoize(f)
+    local cache = {}
+    return function(...)
+        local args = __TUPLE({ ... })
+        assert(__HASHABLE(args, {}), "Cannot use " .. tostring(args) .. " as a key")
+        local key = tostring(args)
+        if cache[key] == nil then
+            cache[key] = { f(...) }
+        end
+        return table.unpack(cache[key])
+    end
+end
+
+function partial(f, a)
+    return function(...)
+        return f(a, ...)
+    end
+end
+
+function apply(f, l)
+    local info = debug.getinfo(f, "u")
+    assert(info.isvararg or info.nparams == #l, "Incorrect argument count")
+    return f((table.unpack or unpack)(l))
+end
+
 push = table.insert
 
 function prepend(l, v)
     table.insert(l, 1, v)
 end
 
+function swap(l, i, j)
+    local a = __INDEX(l, i)
+    local b = __INDEX(l, j)
+    l[i + 1] = b
+    l[j + 1] = a
+end
+
+function set(l, i, v)
+    __ASSIGN_INDEX(l, i, v)
+end
+
+-- Functions and blobs can't be keys in the VM, so they can't be here either
+function __HASHABLE(v, seen)
+    if type(v) == "function" then
+        return false
+    end
+    if type(v) ~= "table" or v == __NIL or seen[v] then
+        return true
+    end
+    if getmetatable(v) == __BLOB_META then
+        return false
+    end
+    seen[v] = true
+    for k, x in pairs(v) do
+        if not (__HASHABLE(k, seen) and __HASHABLE(x, seen)) then
+            return false
+        end
+    end
+    return true
+end
+
 function add(s, v)
+    -- NaN can't be a table key, and would never be found again anyway
+    if v ~= v then
+        return
+    end
     s[v] = true
 end
 
@@ -334,6 +513,161 @@ function len(c)
     return #c
 end
 
+function get(d, k)
+    assert(__HASHABLE(k, {}), "Cannot use " .. tostring(k) .. " as a key")
+    local v = d[k]
+    if v == nil then
+        return __NIL
+    end
+    return v
+end
+
+function first(l)
+    if #l == 0 then
+        return __NIL
+    end
+    return l[1]
+end
+
+function last(l)
+    if #l == 0 then
+        return __NIL
+    end
+    return l[#l]
+end
+
+function rest(l)
+    local r = __LIST({})
+    for i = 2, #l do
+        table.insert(r, l[i])
+    end
+    return r
+end
+
+function to_set(l)
+    local s = __SET({})
+    for _, v in ipairs(l) do
+        assert(__HASHABLE(v, {}), "Cannot put " .. tostring(v) .. " in a set")
+        add(s, v)
+    end
+    return s
+end
+
+-- Lua tables don't remember the insertion order, unlike the VM's sets
+function to_list(s)
+    local o = {}
+    for k, _ in pairs(s) do
+        table.insert(o, k)
+    end
+    return __LIST(o)
+end
+
+function contains_key(d, k)
+    assert(__HASHABLE(k, {}), "Cannot use " .. tostring(k) .. " as a key")
+    return d[k] ~= nil
+end
+
+function repeat_call(f, n)
+    assert(n >= 0, "Cannot call a function a negative number of times")
+    local o = {}
+    for i = 1, n do
+        o[i] = f()
+    end
+    return __LIST(o)
+end
+
+function frequencies(l)
+    local d = __DICT({})
+    for _, v in ipairs(l) do
+        assert(__HASHABLE(v, {}), "Cannot count " .. tostring(v) .. ", since it can't be a key in a dict")
+        d[v] = (d[v] or 0) + 1
+    end
+    return d
+end
+
+function value_in(v, d)
+    for _, x in pairs(d) do
+        if x == v then
+            return true
+        end
+    end
+    return false
+end
+
+function sorted_keys(d)
+    local o = {}
+    for k, _ in pairs(d) do
+        table.insert(o, k)
+    end
+    table.sort(o)
+    return __LIST(o)
+end
+
+function sorted_items(d)
+    local o = {}
+    for i, k in ipairs(sorted_keys(d)) do
+        o[i] = __TUPLE({ k, d[k] })
+    end
+    return __LIST(o)
+end
+
+function approx_equal(a, b, e)
+    assert(e >= 0, "The tolerance can't be negative")
+    return a == b or math.abs(a - b) <= e
+end
+
+function deep_approx_equal(a, b, e)
+    assert(e >= 0, "The tolerance can't be negative")
+    if math.type(a) == "float" and math.type(b) == "float" then
+        return approx_equal(a, b, e)
+    end
+    local meta = getmetatable(a)
+    if type(a) ~= "table" or meta ~= getmetatable(b) then
+        return a == b
+    end
+    if meta == __LIST_META or meta == __TUPLE_META then
+        if #a ~= #b then
+            return false
+        end
+        for i, x in ipairs(a) do
+            if not deep_approx_equal(x, b[i], e) then
+                return false
+            end
+        end
+        return true
+    end
+    if meta == __DICT_META then
+        for k, x in pairs(a) do
+            if b[k] == nil or not deep_approx_equal(x, b[k], e) then
+                return false
+            end
+        end
+        for k, _ in pairs(b) do
+            if a[k] == nil then
+                return false
+            end
+        end
+        return true
+    end
+    return a == b
+end
+
+function range_step(a, b, s)
+    if s == 0 then
+        assert(false, "Cannot step a range by 0")
+    end
+    local o = {}
+    -- Lua's numeric for includes the end, so stop one short of it
+    local last = s > 0 and b - 1 or b + 1
+    for i = a, last, s do
+        table.insert(o, i)
+    end
+    return __LIST(o)
+end
+function range(a, b) return range_step(a, b, 1) end
+function range_inclusive(a, b) return range_step(a, b + 1, 1) end
+function range_list(a, b, s) return range_step(a, b, s) end
+
 sin = math.sin
 cos = math.cos
 
@@ -342,12 +676,181 @@ function as_int(x)
     local f, _ = math.modf(x)
     return f
 end
-floor = math.floor
+function floor(x) return to_int(math.floor(x)) end
+function ceil(x) return to_int(math.ceil(x)) end
+-- Halfway cases go away from zero, like in Rust. The difference between a
+-- float and its floor is exact, so it's safe to compare with 0.5
+function round(x)
+    local r = math.floor(math.abs(x))
+    if math.abs(x) - r >= 0.5 then
+        r = r + 1
+    end
+    if x < 0 then
+        r = -r
+    end
+    return to_int(r)
+end
+to_float = as_float
+-- nan fails both comparisons, and 2^63 is right after the largest int
+function to_int(x)
+    assert(x >= -2^63 and x < 2^63, "Cannot convert " .. tostring(x) .. " to an int")
+    return as_int(x)
+end
 as_char = string.byte
+function parse_int(s)
+    if string.match(s, "^%s*[-+]?%d+%s*$") then
+        return math.tointeger(tonumber(s))
+    end
+    return __NIL
+end
+-- Follows what Rust accepts: inf and nan in any case, but no hex like tonumber
+function parse_float(s)
+    s = string.match(s, "^%s*(.-)%s*$")
+    local sign, word = string.match(string.lower(s), "^([-+]?)(%a+)$")
+    if word == "inf" or word == "infinity" then
+        if sign == "-" then
+            return -math.huge
+        end
+        return math.huge
+    elseif word == "nan" then
+        return 0.0 / 0.0
+    end
+    local digits = string.match(s, "^[-+]?(%d*%.?%d*)[eE][-+]?%d+$")
+        or string.match(s, "^[-+]?(%d*%.?%d*)$")
+    if digits == nil or not string.find(digits, "%d") then
+        return __NIL
+    end
+    return tonumber(s) + 0.0
+end
+
+function parse_value(s)
+    local pos = 1
+    local function peek()
+        pos = string.find(s, "[^%s]", pos) or #s + 1
+        return string.sub(s, pos, pos)
+    end
+    local function expect(c)
+        assert(peek() == c)
+        pos = pos + 1
+    end
+    -- Reads items separated by commas, up to the closing character
+    local function items(close, read)
+        local o = {}
+        while peek() ~= close do
+            table.insert(o, read())
+            if peek() ~= close then
+                expect(",")
+            end
+        end
+        expect(close)
+        return o
+    end
+    local value
+    local function entry()
+        local k = value()
+        if peek() == ":" then
+            pos = pos + 1
+            return { k, value() }
+        end
+        return { k }
+    end
+    value = function()
+        local c = peek()
+        if c == "[" then
+            pos = pos + 1
+            return __LIST(items("]", value))
+        elseif c == "(" then
+            pos = pos + 1
+            return __TUPLE(items(")", value))
+        elseif c == "{" then
+            pos = pos + 1
+            -- An empty dict is written as `{:}`, an empty set as `{}`
+            if peek() == ":" then
+                pos = pos + 1
+                expect("}")
+                return __DICT({})
+            end
+            local entries = items("}", entry)
+            local o = {}
+            for _, e in ipairs(entries) do
+                assert(#e == #entries[1])
+                o[e[1]] = e[2] or true
+            end
+            if #entries > 0 and #entries[1] == 2 then
+                return __DICT(o)
+            end
+            return __SET(o)
+        elseif c == "\"" then
+            local stop = string.find(s, "\"", pos + 1, true)
+            assert(stop)
+            local str = string.sub(s, pos + 1, stop - 1)
+            pos = stop + 1
+            return str
+        end
+        for word, v in pairs({ ["true"] = true, ["false"] = false, ["nil"] = __NIL, ["inf"] = math.huge, ["-inf"] = -math.huge, ["NaN"] = 0 / 0 }) do
+            if string.find(s, "^" .. word .. "%f[^%w_]", pos) then
+                pos = pos + #word
+                return v
+            end
+        end
+        local number = string.match(s, "^-?%d*%.?%d+", pos) or string.match(s, "^-?%d+%.?", pos)
+        assert(number)
+        pos = pos + #number
+        return tonumber(number)
+    end
+    local ok, v = pcall(value)
+    if ok and peek() == "" then
+        return v
+    end
+    return __NIL
+end
+function split(s, sep)
+    local out = {}
+    if sep == "" then
+        for c in string.gmatch(s, utf8.charpattern) do
+            out[#out + 1] = c
+        end
+        return __LIST(out)
+    end
+    local start = 1
+    while true do
+        local from, to = string.find(s, sep, start, true)
+        if from == nil then
+            break
+        end
+        out[#out + 1] = string.sub(s, start, from - 1)
+        start = to + 1
+    end
+    out[#out + 1] = string.sub(s, start)
+    return __LIST(out)
+end
+function join(l, sep)
+    for _, v in ipairs(l) do
+        assert(type(v) == "string", "Can only join strings, got " .. tostring(v))
+    end
+    return table.concat(l, sep)
+end
 function as_chars(s)
     return __LIST(string.byte(s, 1, string.len(s)))
 end
 
+function to_bytes(s)
+    return __LIST({ string.byte(s, 1, string.len(s)) })
+end
+
+function from_bytes(l)
+    for _, b in ipairs(l) do
+        if b < 0 or b > 255 then
+            return __NIL
+        end
+    end
+    local s = string.char(table.unpack(l))
+    if utf8.len(s) == nil then
+        return __NIL
+    end
+    return s
+end
+
 sqrt = math.sqrt
 abs = math.abs
 function sign(x)
@@ -367,6 +870,16 @@ max = math.max
 function rem(x, y)
     return math.abs(x % y)
 end
+-- Lua integers already wrap around.
+function wrapping_add(x, y)
+    return x + y
+end
+function wrapping_sub(x, y)
+    return x - y
+end
+function wrapping_mul(x, y)
+    return x * y
+end
 pow = math.pow
 function angle(v)
     return atan2(v[1], v[2])
@@ -384,6 +897,11 @@ function magnitude(a)
     return math.sqrt(dot(a, a))
 end
 
+function __ASSERT(c, msg)
+    assert(c, msg)
+    return __NIL
+end
+
 function __CRASH(msg)
     return function() assert(false, "crash" .. (msg or "")) end
 end
@@ -393,10 +911,72 @@ reflect = __CRASH("reflect is not implemented")
 debug_assertions = __CRASH("debug_assertions is not implemented")
 thread_sleep = __CRASH("thread_sleep is not implemented")
 
+type_of = __CRASH("type_of is not implemented")
+assert_type = __CRASH("assert_type is not implemented")
 pop = __CRASH("pop is not implemented")
-last = __CRASH("las is not implemented")
+
+function __COPY(x, copies)
+    if type(x) ~= "table" or x == __NIL then
+        return x
+    end
+    if copies[x] ~= nil then
+        return copies[x]
+    end
+    local o = setmetatable({}, getmetatable(x))
+    copies[x] = o
+    for k, v in pairs(x) do
+        rawset(o, __COPY(k, copies), __COPY(v, copies))
+    end
+    return o
+end
+function copy(x) return __COPY(x, {}) end
+
+function __MERGE(a, b, merged)
+    merged[a] = merged[a] or {}
+    if merged[a][b] then
+        return merged[a][b]
+    end
+    local o = __DICT({})
+    merged[a][b] = o
+    for k, v in pairs(a) do
+        o[k] = v
+    end
+    for k, v in pairs(b) do
+        if getmetatable(a[k]) == __DICT_META and getmetatable(v) == __DICT_META then
+            o[k] = __MERGE(a[k], v, merged)
+        else
+            o[k] = v
+        end
+    end
+    return o
+end
+function merge(a, b) return __MERGE(a, b, {}) end
 
 as_str = tostring
+function format(template, values)
+    local out = {}
+    local given = #values
+    local placeholders = 0
+    local i = 1
+    while i <= #template do
+        local c = template:sub(i, i)
+        local next = template:sub(i + 1, i + 1)
+        if (c == "{" and next == "{") or (c == "}" and next == "}") then
+            table.insert(out, c)
+            i = i + 2
+        elseif c == "{" and next == "}" then
+            placeholders = placeholders + 1
+            table.insert(out, tostring(values[placeholders]))
+            i = i + 2
+        else
+            assert(c ~= "{" and c ~= "}", "Unmatched '" .. c .. "' in the template")
+            table.insert(out, c)
+            i = i + 1
+        end
+    end
+    assert(placeholders == given, "The template has " .. placeholders .. " placeholder(s) but " .. given .. " value(s) were given")
+    return table.concat(out)
+end
 print = print
 function spy(tag, x)
     print(tag, x)
@@ -80,6 +80,11 @@ struct TypeChecker<'c> {
     namespace: usize,
     namespaces: Vec<HashMap<String, Name>>,
     stack: Vec<Variable>,
+    /// The label of each loop we're in, and the types loop expressions are broken with.
+    loops: Vec<(Option<String>, Option<Vec<Type>>)>,
+    /// The type the call being checked should give, like `int` in
+    /// `a: int = f()`. Generics only in the return type are deduced from it.
+    expected: Option<Type>,
 }
 
 #[derive(Debug, Clone)]
@@ -118,10 +123,22 @@ impl<'c> TypeChecker<'c> {
             namespace: 0,
             namespaces,
             stack: Vec::new(),
+            loops: Vec::new(),
+            expected: None,
         }
     }
 
 
+    /// Sets the global in the namespace, and everywhere it has been imported to.
+    fn define_global(&mut self, namespace: usize, name: &str, global: Name) {
+        let defined = self.compiler.namespaces[namespace][name];
+        for (slot, names) in self.compiler.namespaces.iter().enumerate() {
+            for (name, _) in names.iter().filter(|(_, other)| **other == defined) {
+                self.namespaces[slot].insert(name.clone(), global.clone());
+            }
+        }
+    }
+
     fn file(&self) -> PathBuf {
         self.compiler.file_from_namespace(self.namespace).into()
     }
@@ -171,6 +188,14 @@ impl<'c> TypeChecker<'c> {
                 Box::new(self.solve_generics_recursively(span, generics, av, bv)?),
             ),
             (Type::Function(a_args, a_ret), Type::Function(b_args, b_ret)) => {
+                if a_args.len() != b_args.len() {
+                    return err_type_error!(
+                        self,
+                        span,
+                        TypeError::Mismatch { got: arg.clone(), expected: par.clone() },
+                        "because the functions take a different number of arguments"
+                    );
+                }
                 let args = a_args.iter().zip(b_args.iter()).map(|(a, b)| self.solve_generics_recursively(span, generics, a, b)).collect::<Result<Vec<_>, _>>()?;
                 let ret = Box::new(self.solve_generics_recursively(span, generics, a_ret, b_ret)?);
                 Type::Function(args, ret)
@@ -218,14 +243,44 @@ impl<'c> TypeChecker<'c> {
         })
     }
 
-    fn resolve_functions_from_args(&self, span: Span, args: &Vec<Type>, ty: &Type) -> Result<(Vec<Type>, Type), Vec<Error>> {
+    /// The type with void taken out of it, if it's a union.
+    fn without_void(ty: &Type) -> Type {
+        match ty {
+            Type::Union(tys) => Type::maybe_union(tys.iter().filter(|ty| !matches!(ty, Type::Void))),
+            ty => ty.clone(),
+        }
+    }
+
+    /// Replaces every generic in `ty` with the type it was bound to,
+    /// or returns the name of the first generic that isn't bound.
+    fn substitute_generics(generics: &HashMap<String, Type>, ty: &Type) -> Result<Type, String> {
+        let sub = |ty: &Type| Self::substitute_generics(generics, ty);
+        Ok(match ty {
+            Type::Generic(name) => match generics.get(name) {
+                Some(ty) => ty.clone(),
+                None => return Err(name.clone()),
+            },
+            Type::Tuple(tys) => Type::Tuple(tys.iter().map(sub).collect::<Result<_, _>>()?),
+            Type::Union(tys) => Type::maybe_union(tys.iter().map(sub).collect::<Result<Vec<_>, _>>()?.iter()),
+            Type::List(ty) => Type::List(Box::new(sub(ty)?)),
+            Type::Set(ty) => Type::Set(Box::new(sub(ty)?)),
+            Type::Dict(k, v) => Type::Dict(Box::new(sub(k)?), Box::new(sub(v)?)),
+            Type::Function(params, ret) => Type::Function(
+                params.iter().map(sub).collect::<Result<_, _>>()?,
+                Box::new(sub(ret)?),
+            ),
+            ty => ty.clone(),
+        })
+    }
+
+    fn resolve_functions_from_args(&self, span: Span, args: &Vec<Type>, ty: &Type, expected: Option<&Type>) -> Result<(Vec<Type>, Type), Vec<Error>> {
         let (params, ret) = match ty {
             // Recursive case
             Type::Union(tys) => {
                 let mut solutions = Vec::new();
                 let mut errors = Vec::new();
                 for ty in tys.iter() {
-                    match self.resolve_functions_from_args(span, args, ty) {
+                    match self.resolve_functions_from_args(span, args, ty, expected) {
                         Ok(res) => { solutions.push(res); }
                         Err(mut err) => { errors.append(&mut err); }
                     }
@@ -272,21 +327,33 @@ impl<'c> TypeChecker<'c> {
             }
             self.solve_generics_recursively(span, &mut generics, par, arg)?;
         }
-        let ret = if let Type::Generic(ret) = ret.as_ref() {
-            match generics.get(ret) {
-                Some(ty) => ty.clone(),
-                None => {
-                    return err_type_error!(
-                        self,
-                        span,
-                        TypeError::Mutability, // TODO(ed): Wrong error
-                        "Generics are only allowed if they can be deduced from the function signature, but '#{}' is not mentioned in the parameters",
-                        ret
-                    )
-                }
+        if let Some(expected) = expected.filter(|ty| !matches!(ty, Type::Unknown)) {
+            if Self::substitute_generics(&generics, ret).is_err() {
+                // Nil is left out of both, otherwise '#OUT?' could make '#OUT'
+                // void when the result is an 'int?'.
+                self.solve_generics_recursively(span, &mut generics, &Self::without_void(ret), &Self::without_void(expected))?;
+            }
+        }
+        let ret = match Self::substitute_generics(&generics, ret) {
+            Ok(ret) => ret,
+            Err(name) if args.iter().any(Self::is_empty_collection) => {
+                let arg = args.iter().find(|arg| Self::is_empty_collection(arg)).unwrap();
+                return err_type_error!(
+                    self,
+                    span,
+                    TypeError::EmptyCollection(arg.clone()),
+                    "so '#{}' can't be deduced. Give it a type, like 'xs: [int] = []'",
+                    name
+                )
+            }
+            Err(name) => {
+                return err_type_error!(
+                    self,
+                    span,
+                    TypeError::UninferableGeneric(name),
+                    "because it is not mentioned in the parameters. Give the result a type"
+                )
             }
-        } else {
-            Type::clone(&ret)
         };
         Ok((args.to_vec(), ret))
     }
@@ -335,6 +402,8 @@ impl<'c> TypeChecker<'c> {
                 }
             }
             AK::Call(fun, args) => {
+                // Taken here, so the callee and the arguments don't see it.
+                let expected = self.expected.take();
                 // TODO(ed): External functions need a different lookup.
                 let ty = match self.assignable(fun, namespace)? {
                     Value(ty, _) => ty,
@@ -347,8 +416,20 @@ impl<'c> TypeChecker<'c> {
                         );
                     }
                 };
-                let args = args.iter().map(|e| self.expression(e)).collect::<Result<Vec<_>, Vec<_>>>()?;
-                let (_params, ret) = self.resolve_functions_from_args(span, &args, &ty)?;
+                // Empty collections passed to a known function get the type of the parameter.
+                let params = match &ty {
+                    Type::Function(params, _) if params.len() == args.len() => params
+                        .iter()
+                        .map(|param| Self::substitute_generics(&HashMap::new(), param).unwrap_or(Type::Unknown))
+                        .collect(),
+                    _ => vec![Type::Unknown; args.len()],
+                };
+                let args = args
+                    .iter()
+                    .zip(params.iter())
+                    .map(|(e, param)| self.expression_expecting(e, param))
+                    .collect::<Result<Vec<_>, Vec<_>>>()?;
+                let (_params, ret) = self.resolve_functions_from_args(span, &args, &ty, expected.as_ref())?;
                 return Ok(Value(Type::clone(&ret), VarKind::Const));
             }
             AK::ArrowCall(extra, fun, args) => {
@@ -535,6 +616,34 @@ impl<'c> TypeChecker<'c> {
         Ok(Type::maybe_union(ty.iter()))
     }
 
+    /// The type of an empty list, set or dict literal, before it gets one
+    /// from where it's used.
+    fn is_empty_collection(ty: &Type) -> bool {
+        match ty {
+            Type::List(ty) | Type::Set(ty) => matches!(**ty, Type::Unknown),
+            Type::Dict(key, value) => matches!((&**key, &**value), (Type::Unknown, Type::Unknown)),
+            _ => false,
+        }
+    }
+
+    /// Like [Self::expression], but empty collection literals take the
+    /// expected type, since they have no elements to infer it from.
+    fn expression_expecting(&mut self, expression: &Expression, expected: &Type) -> Result<Type, Vec<Error>> {
+        use ExpressionKind as EK;
+        match (&expression.kind, expected) {
+            (EK::List(values), Type::List(_))
+            | (EK::Set(values), Type::Set(_))
+            | (EK::Dict(values), Type::Dict(_, _)) if values.is_empty() => Ok(expected.clone()),
+            (EK::Get(Assignable { kind: AssignableKind::Call(..) | AssignableKind::ArrowCall(..), .. }), _) => {
+                let outer = self.expected.replace(expected.clone());
+                let res = self.expression(expression);
+                self.expected = outer;
+                res
+            }
+            _ => self.expression(expression),
+        }
+    }
+
     fn expression(&mut self, expression: &Expression) -> Result<Type, Vec<Error>> {
         use ExpressionKind as EK;
         let span = expression.span;
@@ -556,7 +665,23 @@ impl<'c> TypeChecker<'c> {
             EK::Sub(a, b) => self.bin_op(span, a, b, op::sub, "Subtraction")?,
             EK::Mul(a, b) => self.bin_op(span, a, b, op::mul, "Multiplication")?,
             EK::Div(a, b) => self.bin_op(span, a, b, op::div, "Division")?,
+            EK::Mod(a, b) => self.bin_op(span, a, b, op::rem, "Modulo")?,
             EK::AssertEq(a, b) => self.bin_op(span, a, b, op::eq, "Equality")?,
+            EK::Assert { condition, .. } => {
+                let ty = self.expression(condition)?;
+                if !matches!(ty, Type::Bool) {
+                    return err_type_error!(
+                        self,
+                        condition.span,
+                        TypeError::Mismatch {
+                            got: ty,
+                            expected: Type::Bool,
+                        },
+                        "Only boolean expressions can be asserted"
+                    );
+                }
+                Type::Void
+            }
 
             EK::Comparison(a, cmp, b) => match cmp {
                 ComparisonKind::Equals | ComparisonKind::NotEquals => {
@@ -588,6 +713,11 @@ impl<'c> TypeChecker<'c> {
                 }
             }
 
+            EK::Is(a, _) => {
+                self.expression(a)?;
+                Type::Bool
+            }
+
             EK::Neg(a) => self.uni_op(span, a, op::neg, "Negation")?,
 
             EK::And(a, b) => self.bin_op(span, a, b, op::and, "Boolean and")?,
@@ -627,10 +757,10 @@ impl<'c> TypeChecker<'c> {
             }
 
             EK::Function {
-                name: _,
                 params,
                 ret,
                 body,
+                ..
             } => {
                 let stack_size = self.stack.len();
                 let mut param_types = Vec::new();
@@ -641,9 +771,11 @@ impl<'c> TypeChecker<'c> {
                 }
 
                 let ret = self.compiler.resolve_type(ret, self.compiler_context());
-                let actual_ret = self
-                    .statement(body)?
-                    .expect("A function that doesn't return a value");
+                // Loops outside the function can't be broken from inside it.
+                let outer_loops = std::mem::take(&mut self.loops);
+                let actual_ret = self.statement(body);
+                self.loops = outer_loops;
+                let actual_ret = actual_ret?.expect("A function that doesn't return a value");
 
                 // TODO(ed): We can catch types being too lenient here
                 if let Err(reason) = ret.fits(&actual_ret) {
@@ -661,6 +793,21 @@ impl<'c> TypeChecker<'c> {
                 Type::Function(param_types, Box::new(ret))
             }
 
+            EK::Loop(body) => match &body.kind {
+                StatementKind::Loop { label, condition, body } => {
+                    let mut breaks = self.loop_statement(label, condition, body, Some(Vec::new()))?.unwrap();
+                    // Loops that can end without a `break` evaluate to nil.
+                    if !matches!(condition.kind, EK::Bool(true)) {
+                        breaks.push(Type::Void);
+                    }
+                    match breaks.len() {
+                        0 => Type::Void,
+                        _ => Type::maybe_union(breaks.iter()),
+                    }
+                }
+                _ => unreachable!("Loop expressions always contain loops"),
+            },
+
             EK::IfExpression {
                 condition,
                 pass,
@@ -773,6 +920,57 @@ impl<'c> TypeChecker<'c> {
         Ok(res)
     }
 
+    /// The type of something that's assigned to, if it can be assigned to.
+    fn assignment_target(&mut self, target: &Assignable, span: Span) -> Result<Type, Vec<Error>> {
+        match self.assignable(target, self.namespace)? {
+            Lookup::Value(_, kind) if kind.immutable() => {
+                // TODO(ed): I want this to point to the equal-sign, the parser is
+                // probably a bit off.
+                // TODO(ed): This should not be a type error - prefereably a compile error?
+                err_type_error!(
+                    self,
+                    span,
+                    TypeError::Mutability
+                )
+            }
+            Lookup::Namespace(_) => {
+                err_type_error!(
+                    self,
+                    span,
+                    TypeError::NamespaceNotExpression
+                )
+            }
+            Lookup::Value(ty, _) => {
+                Ok(ty)
+            }
+        }
+    }
+
+    /// The types of the elements when a value is unpacked into `targets` variables.
+    fn destructure(&self, value: &Type, targets: usize, span: Span) -> Result<Vec<Type>, Vec<Error>> {
+        match value {
+            Type::Tuple(elements) if elements.len() == targets => Ok(elements.clone()),
+            Type::Tuple(elements) => err_type_error!(
+                self,
+                span,
+                TypeError::WrongArity { got: elements.len(), expected: targets },
+                "The tuple has {} elements, but there are {} targets",
+                elements.len(),
+                targets
+            ),
+            Type::Unknown => Ok(vec![Type::Unknown; targets]),
+            _ => err_type_error!(
+                self,
+                span,
+                TypeError::Mismatch {
+                    got: value.clone(),
+                    expected: Type::Tuple(vec![Type::Unknown; targets]),
+                },
+                "Only tuples can be destructured"
+            ),
+        }
+    }
+
     fn statement(&mut self, statement: &Statement) -> Result<Option<Type>, Vec<Error>> {
         use StatementKind as SK;
         let span = statement.span;
@@ -782,35 +980,15 @@ impl<'c> TypeChecker<'c> {
                 target,
                 value,
             } => {
-                let value = self.expression(value)?;
-                let target_ty = match self.assignable(target, self.namespace)? {
-                    Lookup::Value(_, kind) if kind.immutable() => {
-                        // TODO(ed): I want this to point to the equal-sign, the parser is
-                        // probably a bit off.
-                        // TODO(ed): This should not be a type error - prefereably a compile error?
-                        return err_type_error!(
-                            self,
-                            span,
-                            TypeError::Mutability
-                        );
-                    }
-                    Lookup::Namespace(_) => {
-                        return err_type_error!(
-                            self,
-                            span,
-                            TypeError::NamespaceNotExpression
-                        );
-                    }
-                    Lookup::Value(ty, _) => {
-                        ty
-                    }
-                };
+                let target_ty = self.assignment_target(target, span)?;
+                let value = self.expression_expecting(value, &target_ty)?;
                 let result = match kind {
                     ParserOp::Nop => value.clone(),
                     ParserOp::Add => op::add(&target_ty, &value),
                     ParserOp::Sub => op::sub(&target_ty, &value),
                     ParserOp::Mul => op::mul(&target_ty, &value),
                     ParserOp::Div => op::div(&target_ty, &value),
+                    ParserOp::Mod => op::rem(&target_ty, &value),
                 };
                 type_error_if_invalid!(
                     self,
@@ -825,6 +1003,7 @@ impl<'c> TypeChecker<'c> {
                             ParserOp::Sub => "Subtraction",
                             ParserOp::Mul => "Multiplication",
                             ParserOp::Div => "Division",
+                            ParserOp::Mod => "Modulo",
                         }.to_string()
                     }
                 );
@@ -845,6 +1024,31 @@ impl<'c> TypeChecker<'c> {
 
             SK::ExternalDefinition { .. } => { None }
 
+            SK::DestructuringDefinition { idents, kind, value } => {
+                let value = self.expression(value)?;
+                for (ident, ty) in idents.iter().zip(self.destructure(&value, idents.len(), span)?) {
+                    self.stack.push(Variable::new(ident.clone(), ty, *kind));
+                }
+                None
+            }
+
+            SK::DestructuringAssignment { targets, value } => {
+                let value = self.expression(value)?;
+                for (target, ty) in targets.iter().zip(self.destructure(&value, targets.len(), span)?) {
+                    let target_ty = self.assignment_target(target, span)?;
+                    if let Err(reason) = target_ty.fits(&ty) {
+                        return err_type_error!(
+                            self,
+                            target.span,
+                            TypeError::MismatchAssign { got: ty, expected: target_ty },
+                            "because {}",
+                            reason
+                        );
+                    }
+                }
+                None
+            }
+
             SK::Definition {
                 ident,
                 kind,
@@ -866,7 +1070,7 @@ impl<'c> TypeChecker<'c> {
                     ty
                 };
 
-                let value = self.expression(value);
+                let value = self.expression_expecting(value, &ty);
                 self.stack.push(Variable::new(ident.clone(), ty.clone(), *kind));
                 let value = value?;
 
@@ -920,24 +1124,42 @@ impl<'c> TypeChecker<'c> {
                 self.statement(fail)?;
                 None
             }
-            SK::Loop { condition, body } => {
-                let ty = self.expression(condition)?;
+            SK::Loop { label, condition, body } => {
+                self.loop_statement(label, condition, body, None)?;
+                None
+            }
+            SK::Break { label, value } => {
+                let ty = match value {
+                    Some(value) => self.expression(value)?,
+                    None => Type::Void,
+                };
+                let target = match label {
+                    Some(label) => self.loops.iter_mut().rfind(|(name, _)| name.as_ref() == Some(&label.name)),
+                    None => self.loops.last_mut(),
+                };
+                // Breaking a loop statement with a value is an error when compiling.
+                if let Some((_, Some(breaks))) = target {
+                    breaks.push(ty);
+                }
+                None
+            }
+            SK::IsCheck { .. } => {
+                // Checked in the compiler
+                None
+            }
+            SK::Assert { expr, .. } => {
+                let ty = self.expression(expr)?;
                 if !matches!(ty, Type::Bool) {
                     return err_type_error!(
                         self,
-                        condition.span,
+                        expr.span,
                         TypeError::Mismatch {
                             got: ty,
                             expected: Type::Bool,
                         },
-                        "Only boolean expressions are valid if-statement conditions"
+                        "Only boolean expressions can be asserted"
                     )
                 }
-                self.statement(body)?;
-                None
-            }
-            SK::IsCheck { .. } => {
-                // Checked in the compiler
                 None
             }
             SK::Block { statements } => {
@@ -966,6 +1188,27 @@ impl<'c> TypeChecker<'c> {
             }
 
             SK::Ret { value } => Some(self.expression(value)?),
+            SK::TypeMatch { value, binding, arms, fallback } => {
+                self.expression(value)?;
+                for (ty, body) in arms.iter() {
+                    let ty = self.compiler.resolve_type(ty, self.compiler_context());
+                    let stack_size = self.stack.len();
+                    if let Some(binding) = binding {
+                        self.stack.push(Variable::new(binding.clone(), ty, VarKind::Const));
+                    }
+                    let result = self.statement(body);
+                    self.stack.truncate(stack_size);
+                    result?;
+                }
+                if let Some(fallback) = fallback {
+                    self.statement(fallback)?;
+                }
+                None
+            }
+            SK::Defer { body } => {
+                self.statement(body)?;
+                None
+            }
             SK::StatementExpression { value } => {
                 self.expression(value)?;
                 None
@@ -973,14 +1216,41 @@ impl<'c> TypeChecker<'c> {
 
             | SK::Use { .. }
             | SK::Blob { .. }
-            | SK::Continue
-            | SK::Break
+            | SK::Continue { .. }
             | SK::Unreachable
             | SK::EmptyStatement => None,
         };
         Ok(ret)
     }
 
+    /// Checks a loop, and returns the types it's broken with if `breaks` is
+    /// set - which it is for loop expressions.
+    fn loop_statement(
+        &mut self,
+        label: &Option<Identifier>,
+        condition: &Expression,
+        body: &Statement,
+        breaks: Option<Vec<Type>>,
+    ) -> Result<Option<Vec<Type>>, Vec<Error>> {
+        let ty = self.expression(condition)?;
+        if !matches!(ty, Type::Bool) {
+            return err_type_error!(
+                self,
+                condition.span,
+                TypeError::Mismatch {
+                    got: ty,
+                    expected: Type::Bool,
+                },
+                "Only boolean expressions are valid if-statement conditions"
+            )
+        }
+        self.loops.push((label.as_ref().map(|label| label.name.clone()), breaks));
+        let body = self.statement(body);
+        let (_, breaks) = self.loops.pop().unwrap();
+        body?;
+        Ok(breaks)
+    }
+
     fn outer_definition(&mut self, namespace: usize, stmt: &Statement) -> Result<(), Vec<Error>> {
         use StatementKind as SK;
 
@@ -1018,7 +1288,7 @@ impl<'c> TypeChecker<'c> {
                     // so we don't have to care about the duplicates.
                     x => unreachable!("X: {:?}", x),
                 };
-                self.namespaces[namespace].insert(ident.name.clone(), Name::Global(Some(name)));
+                self.define_global(namespace, &ident.name, Name::Global(Some(name)));
             }
 
             SK::Definition { ident, kind, ty, value } => {
@@ -1038,8 +1308,8 @@ impl<'c> TypeChecker<'c> {
                             ty
                         };
                         let name = Name::Global(Some((ty.clone(), *kind)));
-                        self.namespaces[namespace].insert(ident.name.clone(), name);
-                        let value = self.expression(value)?;
+                        self.define_global(namespace, &ident.name, name);
+                        let value = self.expression_expecting(value, &ty)?;
                         let fit = ty.fits(&value);
                         let ty = match (kind.force(), fit) {
                             (true, Ok(_)) => {
@@ -1072,7 +1342,7 @@ impl<'c> TypeChecker<'c> {
                     // so we don't have to care about the duplicates.
                     x => unreachable!("X: {:?}", x),
                 };
-                self.namespaces[namespace].insert(ident.name.clone(), Name::Global(Some(name)));
+                self.define_global(namespace, &ident.name, Name::Global(Some(name)));
             }
             _ => {},
         }
@@ -1104,7 +1374,7 @@ impl<'c> TypeChecker<'c> {
             .iter()
             .find_map(|(stmt, _)| {
                 if let StatementKind::Definition{ ident, .. } = &stmt.kind {
-                    if ident.name == "start" {
+                    if ident.name == self.compiler.entry {
                         return Some(ident.span);
                     }
                 }
@@ -1119,7 +1389,7 @@ impl<'c> TypeChecker<'c> {
                     span,
                     kind: AssignableKind::Read(Identifier {
                         span: Span::zero(),
-                        name: "start".to_string()
+                        name: self.compiler.entry.clone()
                     }),
                 }),
                 Vec::new(),
@@ -1211,10 +1481,47 @@ mod op {
         }
     }
 
+    /// Follows `Value::promote`, two `int`s stay an `int` and any other
+    /// mix of numbers becomes a `float`.
+    fn promote(a: &Type, b: &Type) -> Option<Type> {
+        match (a, b) {
+            (Type::Int, Type::Int) => Some(Type::Int),
+            (Type::Float | Type::Int, Type::Float | Type::Int) => Some(Type::Float),
+            _ => None,
+        }
+    }
+
+    /// The type the operator method - like `_add` for `+` - gives, if the
+    /// left operand is a blob that has one.
+    fn method(method: &str, a: &Type, b: &Type) -> Option<Type> {
+        match a {
+            Type::Blob(_, fields) => match fields.get(method)? {
+                Type::Function(params, ret) if params.len() == 2 => {
+                    if params[0].fits(a).is_ok() && params[1].fits(b).is_ok() {
+                        // The blob has no fields yet when the types of its own
+                        // fields are resolved, so use the complete one.
+                        match &**ret {
+                            Type::Blob(name, _) if matches!(a, Type::Blob(a_name, _) if a_name == name) => Some(a.clone()),
+                            ret => Some(ret.clone()),
+                        }
+                    } else {
+                        Some(Type::Invalid)
+                    }
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     pub fn add(a: &Type, b: &Type) -> Type {
+        if let Some(ty) = method("_add", a, b) {
+            return ty;
+        }
+        if let Some(ty) = promote(a, b) {
+            return ty;
+        }
         match (a, b) {
-            (Type::Float, Type::Float) => Type::Float,
-            (Type::Int, Type::Int) => Type::Int,
             (Type::String, Type::String) => Type::String,
             (Type::Tuple(a), Type::Tuple(b)) if a.len() == b.len() => tuple_bin_op(a, b, add),
             (Type::Unknown, a) | (a, Type::Unknown) if !matches!(a, Type::Unknown) => add(a, a),
@@ -1224,13 +1531,22 @@ mod op {
     }
 
     pub fn sub(a: &Type, b: &Type) -> Type {
+        if let Some(ty) = method("_sub", a, b) {
+            return ty;
+        }
         add(a, &neg(b))
     }
 
     pub fn mul(a: &Type, b: &Type) -> Type {
+        if let Some(ty) = method("_mul", a, b) {
+            return ty;
+        }
+        if let Some(ty) = promote(a, b) {
+            return ty;
+        }
         match (a, b) {
-            (Type::Float, Type::Float) => Type::Float,
-            (Type::Int, Type::Int) => Type::Int,
+            (Type::String, Type::Int) | (Type::Int, Type::String) => Type::String,
+            (Type::String, Type::Unknown) | (Type::Unknown, Type::String) => Type::String,
             (Type::Tuple(a), Type::Tuple(b)) if a.len() == b.len() => tuple_bin_op(a, b, mul),
             (Type::Unknown, a) | (a, Type::Unknown) if !matches!(a, Type::Unknown) => mul(a, a),
             (Type::Unknown, Type::Unknown) => Type::Unknown,
@@ -1239,9 +1555,13 @@ mod op {
     }
 
     pub fn div(a: &Type, b: &Type) -> Type {
+        if let Some(ty) = method("_div", a, b) {
+            return ty;
+        }
+        if let Some(ty) = promote(a, b) {
+            return ty;
+        }
         match (a, b) {
-            (Type::Float, Type::Float) => Type::Float,
-            (Type::Int, Type::Int) => Type::Int,
             (Type::Tuple(a), Type::Tuple(b)) if a.len() == b.len() => tuple_bin_op(a, b, div),
             (Type::Unknown, a) | (a, Type::Unknown) if !matches!(a, Type::Unknown) => div(a, a),
             (Type::Unknown, Type::Unknown) => Type::Unknown,
@@ -1249,12 +1569,31 @@ mod op {
         }
     }
 
+    pub fn rem(a: &Type, b: &Type) -> Type {
+        if let Some(ty) = method("_mod", a, b) {
+            return ty;
+        }
+        if let Some(ty) = promote(a, b) {
+            return ty;
+        }
+        match (a, b) {
+            (Type::Tuple(a), Type::Tuple(b)) if a.len() == b.len() => tuple_bin_op(a, b, rem),
+            (Type::Unknown, a) | (a, Type::Unknown) if !matches!(a, Type::Unknown) => rem(a, a),
+            (Type::Unknown, Type::Unknown) => Type::Unknown,
+            _ => Type::Invalid,
+        }
+    }
+
     pub fn eq(a: &Type, b: &Type) -> Type {
+        if let Some(ty) = method("_eq", a, b) {
+            return ty;
+        }
         match (a, b) {
             (Type::Float, Type::Float) => Type::Bool,
             (Type::Int, Type::Int) => Type::Bool,
             (Type::String, Type::String) => Type::Bool,
             (Type::Bool, Type::Bool) => Type::Bool,
+            (Type::Ty, Type::Ty) => Type::Bool,
             (Type::Tuple(a), Type::Tuple(b)) if a.len() == b.len() => a
                 .iter()
                 .zip(b.iter())
@@ -1273,11 +1612,10 @@ mod op {
     }
 
     pub fn cmp(a: &Type, b: &Type) -> Type {
+        if promote(a, b).is_some() {
+            return Type::Bool;
+        }
         match (a, b) {
-            (Type::Float, Type::Float)
-            | (Type::Int, Type::Int)
-            | (Type::Float, Type::Int)
-            | (Type::Int, Type::Float) => Type::Bool,
             (Type::String, Type::String) => Type::Bool,
             (Type::Bool, Type::Bool) => Type::Bool,
             (Type::Tuple(a), Type::Tuple(b)) if a.len() == b.len() => a
@@ -1288,6 +1626,10 @@ mod op {
                     a => Some(a),
                 })
                 .unwrap_or(Type::Bool),
+            (Type::List(a), Type::List(b)) => match cmp(a, b) {
+                Type::Invalid => Type::Invalid,
+                _ => Type::Bool,
+            },
             (Type::Unknown, a) | (a, Type::Unknown) if !matches!(a, Type::Unknown) => cmp(a, a),
             (Type::Unknown, Type::Unknown) => Type::Unknown,
             _ => Type::Invalid,
@@ -1,9 +1,10 @@
 use colored::Colorize;
+use indexmap::{IndexMap, IndexSet};
 use std::borrow::Cow;
 use std::cell::RefCell;
-use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::collections::{hash_map::Entry, HashMap};
 use std::rc::Rc;
-use sylt_common::error::{Error, RuntimeError, RuntimePhase};
+use sylt_common::error::{Error, RuntimeError, RuntimePhase, TraceFrame};
 use sylt_common::{
     Block, BlockLinkState, Frame, Machine, Op, OpResult, BytecodeProg, RuntimeContext, RustFunction,
     Type, UpValue, Value,
@@ -33,6 +34,37 @@ macro_rules! one_op {
     };
 }
 
+/// Raises [RuntimeError::IntegerOverflow] if the operator would overflow on
+/// the values on top of the stack.
+macro_rules! check_overflow {
+    ( $self:expr, $op:expr, $checked:expr ) => {
+        if let [.., a, b] = $self.stack.as_slice() {
+            if op::overflows(a, b, $checked) {
+                let (a, b) = $self.poppop();
+                error!($self, RuntimeError::IntegerOverflow($op, vec![a, b]));
+            }
+        }
+    };
+}
+
+/// Calls the operator method - like `_add` for `+` - if the left operand on
+/// the stack is a blob that has one.
+macro_rules! operator_method {
+    ( $self:expr, $method:expr ) => {
+        let method = match $self.stack.as_slice() {
+            [.., Value::Blob(blob), _] => blob.borrow().get($method).cloned(),
+            _ => None,
+        };
+        if let Some(method) = method.filter(|method| !method.is_nil()) {
+            let (a, b) = $self.poppop();
+            $self.push(method);
+            $self.push(a);
+            $self.push(b);
+            return $self.eval_op(Op::Call(2));
+        }
+    };
+}
+
 macro_rules! two_op {
     ( $self:expr, $op:expr, $fun:expr ) => {
         let (a, b) = $self.poppop();
@@ -48,6 +80,9 @@ macro_rules! two_op {
     };
 }
 
+/// The call depth used when none is given.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 10000;
+
 pub struct VM {
     upvalues: HashMap<usize, Rc<RefCell<UpValue>>>,
 
@@ -61,8 +96,15 @@ pub struct VM {
 
     pub print_bytecode: bool,
     pub print_exec: bool,
+    /// The maximum number of nested calls before a [RuntimeError::StackOverflow].
+    pub max_call_depth: usize,
+    /// How many times loops may start over before a
+    /// [RuntimeError::ExecutionLimitExceeded], there's no limit if it's `None`.
+    pub max_loop_iterations: Option<usize>,
+    loop_iterations: usize,
 
     extern_functions: Vec<RustFunction>,
+    extern_function_names: Vec<String>,
 }
 
 impl VM {
@@ -80,11 +122,25 @@ impl VM {
 
             print_bytecode: false,
             print_exec: false,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            max_loop_iterations: None,
+            loop_iterations: 0,
 
             extern_functions: Vec::new(),
+            extern_function_names: Vec::new(),
         }
     }
 
+    /// Counts the jump if it goes back to the start of a loop, and returns
+    /// the limit if the loops have run more times than it allows.
+    fn loop_limit_exceeded(&mut self, line: usize) -> Option<usize> {
+        if line > self.frame().ip {
+            return None;
+        }
+        self.loop_iterations += 1;
+        self.max_loop_iterations.filter(|max| self.loop_iterations > *max)
+    }
+
     fn drop_upvalue(&mut self, slot: usize, value: Value) {
         if let Entry::Occupied(entry) = self.upvalues.entry(slot) {
             entry.get().borrow_mut().close(value);
@@ -145,7 +201,7 @@ impl VM {
                 "  {:>3}. {}:{:<4} in {:10}",
                 i,
                 frame.block.borrow().file.display(),
-                frame.block.borrow().line(self.frame().ip),
+                frame.block.borrow().line(frame.ip),
                 frame.block.borrow().name.blue()
             );
         }
@@ -171,13 +227,28 @@ impl VM {
 
     fn error(&self, kind: RuntimeError, message: Option<String>) -> Error {
         let frame = self.frames.last().unwrap();
-        self.print_stacktrace();
+        // The outermost frame is the preamble, which isn't written by the user.
+        let trace = self
+            .frames
+            .iter()
+            .skip(1)
+            .rev()
+            .map(|frame| {
+                let block = frame.block.borrow();
+                TraceFrame {
+                    function: block.name.clone(),
+                    file: block.file.clone(),
+                    line: block.line(frame.ip),
+                }
+            })
+            .collect();
         Error::RuntimeError {
             kind,
             phase: RuntimePhase::Runtime,
             file: frame.block.borrow().file.clone(),
             line: frame.block.borrow().line(frame.ip),
             message,
+            trace,
         }
     }
 
@@ -209,6 +280,7 @@ impl VM {
         self.args = Vec::from(args);
 
         self.extern_functions = prog.functions.clone();
+        self.extern_function_names = prog.function_names.clone();
         self.stack.clear();
         self.frames.clear();
 
@@ -248,6 +320,15 @@ impl VM {
             }
         }
     }
+
+    /// The external function linked with the name, callable like any other
+    /// value.
+    pub fn extern_function(&self, name: &str) -> Option<Value> {
+        self.extern_function_names
+            .iter()
+            .position(|function| function == name)
+            .map(Value::ExternFunction)
+    }
 }
 
 impl Machine for VM {
@@ -274,7 +355,11 @@ impl Machine for VM {
         let ip = self.frame().ip;
         self.eval_op(Op::Call(num_args))?;
 
-        if !matches!(callable, Value::ExternFunction(..)) {
+        let mut function = &callable;
+        while let Value::Partial(inner, _) = function {
+            function = inner;
+        }
+        if !matches!(function, Value::ExternFunction(..) | Value::Memoized(..)) {
             let cur_frame = self.frames.len();
             while self.frames.len() >= cur_frame {
                 #[cfg(debug_assertions)]
@@ -302,6 +387,17 @@ impl Machine for VM {
                 error!(self, RuntimeError::Unreachable);
             }
 
+            Op::AssertType(slot) => {
+                let ty = match &self.constants[slot] {
+                    Value::Ty(ty) => ty.clone(),
+                    _ => unreachable!(),
+                };
+                let got = Type::from(self.stack.last().unwrap());
+                if ty.fits(&got).is_err() {
+                    error!(self, RuntimeError::TypeAssertion(ty, got));
+                }
+            }
+
             Op::Pop => {
                 self.pop();
             }
@@ -311,13 +407,23 @@ impl Machine for VM {
                 self.stack.push(Value::Tuple(Rc::new(values)));
             }
 
+            Op::Unpack(size) => match self.pop() {
+                Value::Tuple(values) if values.len() == size => {
+                    self.stack.extend(values.iter().cloned());
+                }
+                value => {
+                    self.stack.extend(std::iter::repeat(Value::Nil).take(size));
+                    error!(self, RuntimeError::UnpackMismatch(value, size));
+                }
+            },
+
             Op::List(size) => {
                 let values = self.stack.split_off(self.stack.len() - size);
                 self.stack.push(Value::List(Rc::new(RefCell::new(values))));
             }
 
             Op::Set(size) => {
-                let values: HashSet<_> = self
+                let values: IndexSet<_> = self
                     .stack
                     .split_off(self.stack.len() - size)
                     .into_iter()
@@ -328,7 +434,7 @@ impl Machine for VM {
             Op::Dict(size) => {
                 assert!(size % 2 == 0);
                 let values = self.stack.split_off(self.stack.len() - size);
-                let values: HashMap<_, _> = values
+                let values: IndexMap<_, _> = values
                     .chunks_exact(2)
                     .map(|a| (a[0].clone(), a[1].clone()))
                     .collect();
@@ -446,6 +552,20 @@ impl Machine for VM {
                         }
                         self.stack.push(v[slot].clone());
                     }
+                    (Value::Range(range), Value::Int(slot)) => {
+                        let slot = slot as usize;
+                        match range.get(slot) {
+                            Some(i) => self.stack.push(Value::Int(i)),
+                            None => {
+                                self.stack.push(Value::Nil);
+                                let len = range.len();
+                                error!(
+                                    self,
+                                    RuntimeError::IndexOutOfBounds(Value::Range(range), len, slot)
+                                );
+                            }
+                        }
+                    }
                     (Value::Dict(dict), i) => {
                         self.push(
                             dict.as_ref()
@@ -491,6 +611,20 @@ impl Machine for VM {
                         }
                         self.stack.push(v[slot].clone());
                     }
+                    Value::Range(range) => {
+                        let slot = slot as usize;
+                        match range.get(slot) {
+                            Some(i) => self.stack.push(Value::Int(i)),
+                            None => {
+                                self.stack.push(Value::Nil);
+                                let len = range.len();
+                                error!(
+                                    self,
+                                    RuntimeError::IndexOutOfBounds(Value::Range(range), len, slot)
+                                );
+                            }
+                        }
+                    }
                     Value::Dict(dict) => {
                         self.push(
                             dict.as_ref()
@@ -530,6 +664,10 @@ impl Machine for VM {
                     (Value::Dict(rc_v), slot, n) => {
                         rc_v.as_ref().borrow_mut().insert(slot, n);
                     }
+                    (Value::Range(_), _, _) => {
+                        self.push(Value::Nil);
+                        error!(self, RuntimeError::ImmutableRange("an assignment".to_string()));
+                    }
                     (indexable, slot, _) => {
                         self.push(Value::Nil);
                         error!(self, RuntimeError::IndexError(indexable, slot.into()));
@@ -556,6 +694,12 @@ impl Machine for VM {
                     (Value::List(rc_v), e) => {
                         self.push(Value::Bool(rc_v.as_ref().borrow_mut().contains(&e)));
                     }
+                    (Value::Range(range), Value::Int(i)) => {
+                        self.push(Value::Bool(range.contains(i)));
+                    }
+                    (Value::Range(_), _) => {
+                        self.push(Value::Bool(false));
+                    }
                     (Value::Dict(rc_v), e) => {
                         self.push(Value::Bool(rc_v.as_ref().borrow_mut().contains_key(&e)));
                     }
@@ -633,26 +777,53 @@ impl Machine for VM {
             // TODO(ed): These look the same as in typechecker.rs, since the macros and functions hide the
             // rest, maybe merge them?
             Op::Neg => {
+                if let Some(a) = self.stack.last() {
+                    if op::overflows(&Value::Int(0), a, i64::checked_sub) {
+                        let a = self.pop();
+                        error!(self, RuntimeError::IntegerOverflow(Op::Neg, vec![a]));
+                    }
+                }
                 one_op!(self, Op::Neg, op::neg);
             }
 
             Op::Add => {
+                operator_method!(self, "_add");
+                check_overflow!(self, Op::Add, i64::checked_add);
                 two_op!(self, Op::Add, op::add);
             }
 
             Op::Sub => {
+                operator_method!(self, "_sub");
+                check_overflow!(self, Op::Sub, i64::checked_sub);
                 two_op!(self, Op::Sub, op::sub);
             }
 
             Op::Mul => {
+                operator_method!(self, "_mul");
+                // A string can't be repeated a negative number of times, or
+                // into a string longer than what can be allocated.
+                if let [.., Value::String(s), Value::Int(n)] | [.., Value::Int(n), Value::String(s)] = self.stack.as_slice() {
+                    if op::repeat_count(s, *n).is_none() {
+                        let (a, b) = self.poppop();
+                        error!(self, RuntimeError::ValueError(Op::Mul, vec![a, b]));
+                    }
+                }
+                check_overflow!(self, Op::Mul, i64::checked_mul);
                 two_op!(self, Op::Mul, op::mul);
             }
 
             Op::Div => {
+                operator_method!(self, "_div");
                 two_op!(self, Op::Div, op::div);
             }
 
+            Op::Mod => {
+                operator_method!(self, "_mod");
+                two_op!(self, Op::Mod, op::rem);
+            }
+
             Op::Equal => {
+                operator_method!(self, "_eq");
                 two_op!(self, Op::Equal, op::eq);
             }
 
@@ -677,6 +848,9 @@ impl Machine for VM {
             }
 
             Op::Jmp(line) => {
+                if let Some(max) = self.loop_limit_exceeded(line) {
+                    error!(self, RuntimeError::ExecutionLimitExceeded(max));
+                }
                 self.frame_mut().ip = line;
                 return Ok(OpResult::Continue);
             }
@@ -689,6 +863,9 @@ impl Machine for VM {
             }
 
             Op::JmpNPop(line, to_pop) => {
+                if let Some(max) = self.loop_limit_exceeded(line) {
+                    error!(self, RuntimeError::ExecutionLimitExceeded(max));
+                }
                 let hi = self.stack.len();
                 let lo = hi - to_pop;
                 for slot in lo..hi {
@@ -709,6 +886,18 @@ impl Machine for VM {
                 self.push(Value::Bool(true));
             }
 
+            Op::AssertMessage => {
+                let (condition, message) = self.poppop();
+                if matches!(condition, Value::Bool(false)) {
+                    let line = self.frame().block.borrow().line(self.frame().ip);
+                    let message = match message {
+                        Value::String(message) => Some(String::clone(&message)),
+                        _ => None,
+                    };
+                    error!(self, RuntimeError::AssertionFailed(line, message));
+                }
+            }
+
             Op::ReadUpvalue(slot) => {
                 let offset = self.frame().stack_offset;
                 let value = match &self.stack[offset] {
@@ -768,6 +957,12 @@ impl Machine for VM {
                         if args.len() != num_args {
                             error!(self, RuntimeError::ArgumentCount(args.len(), num_args));
                         }
+                        if self.frames.len() >= self.max_call_depth {
+                            error!(
+                                self,
+                                RuntimeError::StackOverflow(inner.name.clone(), self.frames.len())
+                            );
+                        }
 
                         #[cfg(debug_assertions)]
                         if self.print_bytecode {
@@ -781,10 +976,42 @@ impl Machine for VM {
                         });
                         return Ok(OpResult::Continue);
                     }
+                    Value::Memoized(function, cache) => {
+                        let key = Value::Tuple(Rc::new(self.stack[new_base + 1..].to_vec()));
+                        if !key.is_hashable() {
+                            error!(
+                                self,
+                                RuntimeError::ExternError(
+                                    "memoize".to_string(),
+                                    format!("Cannot use {:?} as a key", key),
+                                )
+                            );
+                        }
+                        let cached = cache.borrow().get(&key).cloned();
+                        let res = match cached {
+                            Some(value) => value,
+                            None => {
+                                let args = self.stack[new_base + 1..].to_vec();
+                                let args = args.iter().collect::<Vec<_>>();
+                                let value = self.eval_call(Value::clone(&function), &args)?;
+                                cache.borrow_mut().insert(key, value.clone());
+                                value
+                            }
+                        };
+                        self.stack.truncate(new_base);
+                        self.push(res);
+                    }
+                    Value::Partial(function, bound) => {
+                        self.stack[new_base] = Value::clone(&function);
+                        self.stack.insert(new_base + 1, Value::clone(&bound));
+                        return self.eval_op(Op::Call(num_args + 1));
+                    }
                     Value::ExternFunction(slot) => {
                         let extern_func = self.extern_functions[slot];
+                        let call_site = self.frame().block.borrow().span(self.frame().ip);
                         let ctx = RuntimeContext {
                             stack_base: new_base + 1,
+                            call_site,
                             machine: self,
                         };
                         let res = match extern_func(ctx) {
@@ -831,6 +1058,8 @@ impl Machine for VM {
 mod op {
     use super::Rc;
     use super::Value;
+    use std::convert::TryFrom;
+    use sylt_common::value::Promoted;
 
     fn tuple_bin_op(
         a: &Rc<Vec<Value>>,
@@ -842,6 +1071,26 @@ mod op {
         ))
     }
 
+    /// How many times the string can be repeated, `None` if `n` is negative or
+    /// the result would be too long to allocate.
+    pub fn repeat_count(s: &str, n: i64) -> Option<usize> {
+        let n = usize::try_from(n).ok()?;
+        s.len().checked_mul(n).filter(|len| *len <= isize::MAX as usize).map(|_| n)
+    }
+
+    /// If applying the integer operation to the values - or any pair of
+    /// integers in them - overflows. The operators below wrap instead.
+    pub fn overflows(a: &Value, b: &Value, checked: fn(i64, i64) -> Option<i64>) -> bool {
+        match (a, b) {
+            (Value::Int(a), Value::Int(b)) => checked(*a, *b).is_none(),
+            (Value::Tuple(a), Value::Tuple(b)) => {
+                a.iter().zip(b.iter()).any(|(a, b)| overflows(a, b, checked))
+            }
+            (Value::Int(_), Value::Tuple(b)) => b.iter().any(|b| overflows(a, b, checked)),
+            _ => false,
+        }
+    }
+
     fn tuple_un_op(a: &Rc<Vec<Value>>, f: fn(&Value) -> Value) -> Value {
         Value::Tuple(Rc::new(a.iter().map(f).collect()))
     }
@@ -849,7 +1098,7 @@ mod op {
     pub fn neg(value: &Value) -> Value {
         match value {
             Value::Float(a) => Value::Float(-*a),
-            Value::Int(a) => Value::Int(-*a),
+            Value::Int(a) => Value::Int(a.wrapping_neg()),
             Value::Tuple(a) => tuple_un_op(a, neg),
             _ => Value::Nil,
         }
@@ -863,10 +1112,24 @@ mod op {
         }
     }
 
+    /// Applies the arithmetic operator if both values are numbers.
+    fn arithmetic(
+        a: &Value,
+        b: &Value,
+        int: fn(i64, i64) -> i64,
+        float: fn(f64, f64) -> f64,
+    ) -> Option<Value> {
+        match Value::promote(a, b)? {
+            Promoted::Int(a, b) => Some(Value::Int(int(a, b))),
+            Promoted::Float(a, b) => Some(Value::Float(float(a, b))),
+        }
+    }
+
     pub fn add(a: &Value, b: &Value) -> Value {
+        if let Some(value) = arithmetic(a, b, i64::wrapping_add, |a, b| a + b) {
+            return value;
+        }
         match (a, b) {
-            (Value::Float(a), Value::Float(b)) => Value::Float(a + b),
-            (Value::Int(a), Value::Int(b)) => Value::Int(a + b),
             (Value::String(a), Value::String(b)) => Value::String(Rc::from(format!("{}{}", a, b))),
             (Value::Tuple(a), Value::Tuple(b)) if a.len() == b.len() => tuple_bin_op(a, b, add),
             _ => Value::Nil,
@@ -878,23 +1141,42 @@ mod op {
     }
 
     pub fn mul(a: &Value, b: &Value) -> Value {
+        if let Some(value) = arithmetic(a, b, i64::wrapping_mul, |a, b| a * b) {
+            return value;
+        }
         match (a, b) {
-            (Value::Float(a), Value::Float(b)) => Value::Float(a * b),
-            (Value::Int(a), Value::Int(b)) => Value::Int(a * b),
+            (Value::String(s), Value::Int(n)) | (Value::Int(n), Value::String(s)) => {
+                match repeat_count(s, *n) {
+                    Some(n) => Value::String(Rc::from(s.repeat(n))),
+                    None => Value::Nil,
+                }
+            }
             (Value::Tuple(a), Value::Tuple(b)) if a.len() == b.len() => tuple_bin_op(a, b, mul),
             _ => Value::Nil,
         }
     }
 
     pub fn div(a: &Value, b: &Value) -> Value {
+        if let Some(value) = arithmetic(a, b, |a, b| a / b, |a, b| a / b) {
+            return value;
+        }
         match (a, b) {
-            (Value::Float(a), Value::Float(b)) => Value::Float(a / b),
-            (Value::Int(a), Value::Int(b)) => Value::Int(a / b),
             (Value::Tuple(a), Value::Tuple(b)) if a.len() == b.len() => tuple_bin_op(a, b, div),
             _ => Value::Nil,
         }
     }
 
+    /// The remainder has the sign of `a`, like `%` in Rust.
+    pub fn rem(a: &Value, b: &Value) -> Value {
+        if let Some(value) = arithmetic(a, b, |a, b| a % b, |a, b| a % b) {
+            return value;
+        }
+        match (a, b) {
+            (Value::Tuple(a), Value::Tuple(b)) if a.len() == b.len() => tuple_bin_op(a, b, rem),
+            _ => Value::Nil,
+        }
+    }
+
     pub fn eq(a: &Value, b: &Value) -> Value {
         match (a, b) {
             (Value::Float(a), Value::Float(b)) => Value::Bool(a == b),
@@ -917,6 +1199,10 @@ mod op {
                 Value::Bool(true)
             }
             (Value::Nil, Value::Nil) => Value::Bool(true),
+            (Value::Ty(a), Value::Ty(b)) => Value::Bool(a == b),
+            (Value::Range(a), Value::Range(b)) => Value::Bool(a == b),
+            (Value::Range(range), list @ Value::List(_)) => eq(&range.to_list(), list),
+            (list @ Value::List(_), Value::Range(range)) => eq(list, &range.to_list()),
             (Value::List(a), Value::List(b)) => {
                 let a = a.borrow();
                 let b = b.borrow();
@@ -942,11 +1228,12 @@ mod op {
     }
 
     pub fn less(a: &Value, b: &Value) -> Value {
+        match Value::promote(a, b) {
+            Some(Promoted::Int(a, b)) => return Value::Bool(a < b),
+            Some(Promoted::Float(a, b)) => return Value::Bool(a < b),
+            None => {}
+        }
         match (a, b) {
-            (Value::Float(a), Value::Float(b)) => Value::Bool(a < b),
-            (Value::Float(a), Value::Int(b)) => Value::Bool(*a < (*b as f64)),
-            (Value::Int(a), Value::Float(b)) => Value::Bool((*a as f64) < *b),
-            (Value::Int(a), Value::Int(b)) => Value::Bool(a < b),
             (Value::String(a), Value::String(b)) => Value::Bool(a < b),
             (Value::Bool(a), Value::Bool(b)) => Value::Bool(a < b),
             (Value::Tuple(a), Value::Tuple(b)) if a.len() == b.len() => a
@@ -957,10 +1244,27 @@ mod op {
                     a => Some(a),
                 })
                 .unwrap_or(Value::Bool(true)),
+            (Value::List(a), Value::List(b)) => list_less(&a.borrow(), &b.borrow()),
+            (Value::Range(a), b @ (Value::List(_) | Value::Range(_))) => less(&a.to_list(), b),
+            (a @ Value::List(_), Value::Range(b)) => less(a, &b.to_list()),
             _ => Value::Nil,
         }
     }
 
+    /// Compares two lists lexicographically, a shorter list is less than a
+    /// longer list it is a prefix of.
+    fn list_less(a: &[Value], b: &[Value]) -> Value {
+        for (a, b) in a.iter().zip(b.iter()) {
+            match (less(a, b), less(b, a)) {
+                (Value::Bool(true), _) => return Value::Bool(true),
+                (_, Value::Bool(true)) => return Value::Bool(false),
+                (Value::Bool(false), Value::Bool(false)) => {}
+                _ => return Value::Nil,
+            }
+        }
+        Value::Bool(a.len() < b.len())
+    }
+
     pub fn greater(a: &Value, b: &Value) -> Value {
         less(b, a)
     }
@@ -981,3 +1285,80 @@ mod op {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::op;
+    use super::Value;
+
+    fn assert_int(value: Value, expected: i64) {
+        assert!(matches!(value, Value::Int(i) if i == expected), "{:?} isn't the int {}", value, expected);
+    }
+
+    fn assert_float(value: Value, expected: f64) {
+        assert!(matches!(value, Value::Float(f) if f == expected), "{:?} isn't the float {}", value, expected);
+    }
+
+    #[test]
+    fn add_promotes() {
+        assert_int(op::add(&Value::Int(1), &Value::Int(2)), 3);
+        assert_float(op::add(&Value::Int(1), &Value::Float(0.5)), 1.5);
+        assert_float(op::add(&Value::Float(0.5), &Value::Int(1)), 1.5);
+        assert_float(op::add(&Value::Float(0.5), &Value::Float(0.5)), 1.0);
+    }
+
+    #[test]
+    fn sub_promotes() {
+        assert_int(op::sub(&Value::Int(1), &Value::Int(2)), -1);
+        assert_float(op::sub(&Value::Int(1), &Value::Float(0.5)), 0.5);
+        assert_float(op::sub(&Value::Float(0.5), &Value::Int(1)), -0.5);
+    }
+
+    #[test]
+    fn mul_promotes() {
+        assert_int(op::mul(&Value::Int(3), &Value::Int(2)), 6);
+        assert_float(op::mul(&Value::Int(3), &Value::Float(0.5)), 1.5);
+        assert_float(op::mul(&Value::Float(0.5), &Value::Int(3)), 1.5);
+    }
+
+    #[test]
+    fn div_promotes() {
+        assert_int(op::div(&Value::Int(3), &Value::Int(2)), 1);
+        assert_float(op::div(&Value::Int(3), &Value::Float(2.0)), 1.5);
+        assert_float(op::div(&Value::Float(3.0), &Value::Int(2)), 1.5);
+    }
+
+    #[test]
+    fn rem_promotes() {
+        assert_int(op::rem(&Value::Int(7), &Value::Int(3)), 1);
+        assert_int(op::rem(&Value::Int(-7), &Value::Int(3)), -1);
+        assert_float(op::rem(&Value::Int(7), &Value::Float(2.5)), 2.0);
+        assert_float(op::rem(&Value::Float(7.5), &Value::Int(2)), 1.5);
+    }
+
+    #[test]
+    fn huge_string_repeats_are_nil() {
+        let ab = Value::from("ab");
+        assert!(matches!(op::mul(&ab, &Value::Int(2)), Value::String(s) if s.as_str() == "abab"));
+        assert!(matches!(op::mul(&Value::from(""), &Value::Int(i64::MAX)), Value::String(s) if s.is_empty()));
+        assert!(matches!(op::mul(&ab, &Value::Int(i64::MAX)), Value::Nil));
+        assert!(matches!(op::mul(&Value::Int(-1), &ab), Value::Nil));
+    }
+
+    #[test]
+    fn less_promotes() {
+        assert!(matches!(op::less(&Value::Int(1), &Value::Float(1.5)), Value::Bool(true)));
+        assert!(matches!(op::less(&Value::Float(1.5), &Value::Int(1)), Value::Bool(false)));
+    }
+
+    #[test]
+    fn eq_does_not_promote() {
+        assert!(matches!(op::eq(&Value::Int(1), &Value::Float(1.0)), Value::Nil));
+    }
+
+    #[test]
+    fn non_numbers_are_not_promoted() {
+        assert!(matches!(op::add(&Value::Int(1), &Value::Bool(true)), Value::Nil));
+        assert!(matches!(op::div(&Value::Nil, &Value::Float(1.0)), Value::Nil));
+    }
+}
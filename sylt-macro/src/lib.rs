@@ -1,6 +1,6 @@
 use lazy_static::lazy_static;
 use quote::{format_ident, quote};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
@@ -104,7 +104,7 @@ pub fn extern_function(tokens: proc_macro::TokenStream) -> proc_macro::TokenStre
         .collect();
 
     let tokens = quote! {
-        #[sylt_macro::sylt_doc(#link_name, #doc, #signature)]
+        #[sylt_macro::sylt_doc(#link_name, #module, #doc, #signature)]
         #[sylt_macro::sylt_link(#link_name, #module, #signature)]
         pub fn #function (
             ctx: ::sylt_common::RuntimeContext
@@ -115,9 +115,31 @@ pub fn extern_function(tokens: proc_macro::TokenStream) -> proc_macro::TokenStre
             let values = ctx.machine.stack_from_base(ctx.stack_base);
             match &*values {
                 #(#eval_blocks),*
+                // Ranges are lists that aren't built yet, so functions
+                // that only know about lists get them built. Functions
+                // that change lists have to refuse ranges themselves, the
+                // change would be lost with the built list.
+                _ if values.iter().any(|v| matches!(v, Range(_))) => {
+                    let values: Vec<_> = values
+                        .iter()
+                        .map(|v| match v {
+                            Range(range) => range.to_list(),
+                            v => v.clone(),
+                        })
+                        .collect();
+                    match &*values {
+                        #(#eval_blocks),*
+                        _ => Err(::sylt_common::error::RuntimeError::ExternTypeMismatch(
+                            stringify!(#function).to_string(),
+                            values.iter().map(|v| ::sylt_common::Type::from(v)).collect(),
+                            ctx.call_site,
+                        ))
+                    }
+                }
                 _ => Err(::sylt_common::error::RuntimeError::ExternTypeMismatch(
                     stringify!(#function).to_string(),
-                    values.iter().map(|v| ::sylt_common::Type::from(v)).collect()
+                    values.iter().map(|v| ::sylt_common::Type::from(v)).collect(),
+                    ctx.call_site,
                 ))
             }
         }
@@ -198,6 +220,8 @@ struct TestSettings {
     print: bool,
     // Used to tell lua there are runtime errors - since it doesn't care about the type.
     any_runtime_errors: bool,
+    // If the lua-compiler supports everything the test uses.
+    lua: bool,
 }
 
 impl Default for TestSettings {
@@ -206,6 +230,7 @@ impl Default for TestSettings {
             errors: String::new(),
             print: true,
             any_runtime_errors: false,
+            lua: true,
         }
     }
 }
@@ -240,6 +265,9 @@ fn parse_test_settings(contents: String) -> TestSettings {
                     "no_print" => {
                         settings.print = false;
                     }
+                    "no_lua" => {
+                        settings.lua = false;
+                    }
                     _ => {
                         panic!("Unknown test flag '{}'", flag);
                     }
@@ -280,11 +308,11 @@ fn find_test_paths(directory: &Path, macro_path: &syn::Path) -> proc_macro2::Tok
             let settings = parse_test_settings(std::fs::read_to_string(path.clone()).unwrap());
             let any_runtime_errors = settings.any_runtime_errors;
             let print = settings.print;
+            let lua = settings.lua;
             let wanted_errs: proc_macro2::TokenStream = settings.errors.parse().unwrap();
 
-            // TODO(ed): Make a flag for skipping the test
             let tokens = quote! {
-                #macro_path!(#test_name, #path_string, #print, #wanted_errs, #any_runtime_errors);
+                #macro_path!(#test_name, #path_string, #print, #wanted_errs, #any_runtime_errors, #lua);
             };
 
             tests.extend(tokens);
@@ -427,6 +455,8 @@ pub fn derive_numbered(item: proc_macro::TokenStream) -> proc_macro::TokenStream
         }
     }).collect();
 
+    let num_variants = parsed.variants.len();
+
     let item = quote! {
         impl Numbered for #ident {
             fn to_number(&self) -> usize {
@@ -435,6 +465,11 @@ pub fn derive_numbered(item: proc_macro::TokenStream) -> proc_macro::TokenStream
                 }
             }
         }
+
+        impl #ident {
+            /// How many numbers `to_number` can give, they go from 0 up to this.
+            pub const NUM_VARIANTS: usize = #num_variants;
+        }
     };
     proc_macro::TokenStream::from(item)
 }
@@ -505,6 +540,7 @@ pub fn sylt_link_gen(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream
 
 struct SyltDoc {
     name: syn::Ident,
+    module: syn::LitStr,
     comment: syn::LitStr,
     signature: syn::LitStr,
 }
@@ -513,12 +549,15 @@ impl Parse for SyltDoc {
     fn parse(input: ParseStream) -> Result<Self> {
         let name: syn::Ident = input.parse()?;
         let _comma: Token![,] = input.parse()?;
+        let module = input.parse()?;
+        let _comma: Token![,] = input.parse()?;
         let comment = input.parse()?;
         let _comma: Token![,] = input.parse()?;
         let signature = input.parse()?;
 
         Ok(SyltDoc {
             name,
+            module,
             comment,
             signature,
         })
@@ -526,7 +565,9 @@ impl Parse for SyltDoc {
 }
 
 struct DocFile {
-    docs: Vec<String>,
+    /// Sorted by module and then name, since the functions are documented in
+    /// whatever order the compiler happens to expand them.
+    docs: BTreeMap<(String, String), String>,
 }
 
 lazy_static! {
@@ -534,17 +575,21 @@ lazy_static! {
 }
 
 fn doc_file() -> Arc<Mutex<DocFile>> {
-    Arc::new(Mutex::new(DocFile { docs: Vec::new() }))
+    Arc::new(Mutex::new(DocFile { docs: BTreeMap::new() }))
 }
 
 impl DocFile {
+    // There's no way to know which function is documented last, so the whole
+    // file is written every time. It's the same file each time - save for the
+    // functions that are yet to be documented.
     fn dump(&mut self) {
         use std::fs::File;
         use std::io::prelude::*;
         match File::create(&Path::new("docs/docs.json")) {
             Err(_msg) => (), // TODO(gu) report errors
             Ok(mut file) => {
-                write!(file, "[\n{}\n]", self.docs.join(",\n")).unwrap();
+                let docs: Vec<_> = self.docs.values().cloned().collect();
+                write!(file, "[\n{}\n]", docs.join(",\n")).unwrap();
             }
         }
     }
@@ -557,6 +602,7 @@ pub fn sylt_doc(
 ) -> proc_macro::TokenStream {
     let doc: SyltDoc = parse_macro_input!(attrib);
 
+    let key = (doc.module.value(), doc.name.to_string());
     let doc = format!(
         "{{ \"name\": \"{}\", \"comment\": \"{}\", \"signature\": {}}}",
         doc.name.to_string(),
@@ -564,7 +610,7 @@ pub fn sylt_doc(
         doc.signature.value().split_whitespace().collect::<Vec<_>>().join(" "),
     );
     let mut doc_file = DOC.lock().unwrap();
-    doc_file.docs.push(doc);
+    doc_file.docs.insert(key, doc);
     doc_file.dump();
     drop(doc_file);
 
@@ -1,10 +1,11 @@
 use sylt_common::error::Error;
 
-use crate::statement::block;
+use crate::statement::{block, loop_statement};
 
 use super::*;
 
 #[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize)]
 pub enum ComparisonKind {
     Equals,
     NotEquals,
@@ -19,6 +20,7 @@ pub enum ComparisonKind {
 ///
 /// Expressions are recursive and evaluate to some kind of value.
 #[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize)]
 pub enum ExpressionKind {
     /// Read from an [Assignable]. Variables, function calls, module accesses,
     /// blob fields, list indexing, tuple indexing and dict indexing end up here.
@@ -35,13 +37,23 @@ pub enum ExpressionKind {
     Mul(Box<Expression>, Box<Expression>),
     /// `a / b`
     Div(Box<Expression>, Box<Expression>),
+    /// `a % b`
+    Mod(Box<Expression>, Box<Expression>),
     /// `-a`
     Neg(Box<Expression>),
 
     Comparison(Box<Expression>, ComparisonKind, Box<Expression>),
 
+    /// `a is int` - if the value is of the type.
+    Is(Box<Expression>, Type),
+
     /// `a <=> b`
     AssertEq(Box<Expression>, Box<Expression>),
+    /// `assert(a)` or `assert(a, "message")`, evaluates to `nil`.
+    Assert {
+        condition: Box<Expression>,
+        message: Option<String>,
+    },
 
     /// `a && b`
     And(Box<Expression>, Box<Expression>),
@@ -59,11 +71,19 @@ pub enum ExpressionKind {
         fail: Box<Expression>,
     },
 
+    /// A loop that evaluates to the value it's broken with, or `nil` if it
+    /// ends without a `break`. Always a [StatementKind::Loop].
+    ///
+    /// `a := loop <expression> <statement>`.
+    Loop(Box<Statement>),
+
     /// Functions and closures.
     Function {
         name: String,
         params: Vec<(Identifier, Type)>,
         ret: Type,
+        /// `const fn`s can be evaluated when compiling.
+        is_const: bool,
 
         body: Box<Statement>,
     },
@@ -91,6 +111,7 @@ pub enum ExpressionKind {
 
 /// Expressions evaluate to values. Contains any [ExpressionKind].
 #[derive(Debug, Clone)]
+#[derive(Serialize)]
 pub struct Expression {
     pub span: Span,
     pub kind: ExpressionKind,
@@ -114,6 +135,9 @@ fn function<'t>(ctx: Context<'t>) -> ParseResult<'t, Expression> {
     let ret = loop {
         match ctx.token() {
             T::Identifier(name) => {
+                if params.iter().any(|(param, _): &(Identifier, Type)| &param.name == name) {
+                    raise_syntax_error!(ctx, "Parameter '{}' is declared twice", name);
+                }
                 // Parameter name
                 let ident = Identifier {
                     span: ctx.span(),
@@ -136,15 +160,21 @@ fn function<'t>(ctx: Context<'t>) -> ParseResult<'t, Expression> {
             // Parse return type
             T::Arrow => {
                 ctx = ctx.skip(1);
-                break if let Ok((_ctx, ret)) = parse_type(ctx) {
-                    ctx = _ctx; // assign to outer
-                    ret
-                } else {
-                    Type {
+                break match parse_type(ctx) {
+                    // `fn -> { a }` could be a set type without a body, so a
+                    // `{` is only a type if a body follows it.
+                    Ok((_ctx, ret))
+                        if !matches!(ctx.token(), T::LeftBrace)
+                            || matches!(_ctx.token(), T::Do | T::LeftBrace | T::Equal) =>
+                    {
+                        ctx = _ctx; // assign to outer
+                        ret
+                    }
+                    _ => Type {
                         // If we couldn't parse the return type, we assume `-> Void`.
                         span: ctx.span(),
                         kind: Resolved(Void),
-                    }
+                    },
                 };
             }
 
@@ -162,28 +192,53 @@ fn function<'t>(ctx: Context<'t>) -> ParseResult<'t, Expression> {
         }
     };
 
-    // Parse the function statement.
-    let (ctx, mut statements) = block(ctx)?;
+    // Parse the function statement, `= <expression>` is short for a block
+    // that returns the expression.
+    let (ctx, mut statements) = if matches!(ctx.token(), T::Equal) {
+        let span = ctx.skip(1).span();
+        // Loops outside the function can't be broken out of from inside it.
+        let (ctx, value) = ctx.skip(1).with_loops(None, expression)?;
+        let ret = Statement {
+            span,
+            kind: StatementKind::Ret { value },
+            comments: Vec::new(),
+            trailing_comments: Vec::new(),
+            annotations: Vec::new(),
+        };
+        (ctx, vec![ret])
+    } else {
+        ctx.with_loops(None, block)?
+    };
 
     // If the return type isn't void, check for and apply implicit returns.
     if !matches!(ret.kind, Resolved(Void)) {
         // If the last statement is an expression statement,
-        // replace it with a return statement.
+        // replace it with a return statement. Comments kept above the end
+        // of the block come after it.
+        let closing = match statements.last() {
+            Some(Statement { kind: StatementKind::EmptyStatement, .. }) => statements.pop(),
+            _ => None,
+        };
         let last_statement = statements.pop();
         if let Some(Statement {
             span,
             kind: StatementKind::StatementExpression { value },
             comments,
+            trailing_comments,
+            annotations,
         }) = last_statement
         {
             statements.push(Statement {
                 span,
                 kind: StatementKind::Ret { value },
-                comments
+                comments,
+                trailing_comments,
+                annotations,
             });
         } else if let Some(statement) = last_statement {
             statements.push(statement);
         }
+        statements.extend(closing);
     }
 
     use ExpressionKind::Function;
@@ -191,10 +246,13 @@ fn function<'t>(ctx: Context<'t>) -> ParseResult<'t, Expression> {
         name: "lambda".into(),
         params,
         ret,
+        is_const: false,
         body: Box::new(Statement {
             span: ctx.span(),
             kind: StatementKind::Block { statements },
             comments: Vec::new(),
+            trailing_comments: Vec::new(),
+            annotations: Vec::new(),
         }),
     };
 
@@ -207,11 +265,41 @@ fn function<'t>(ctx: Context<'t>) -> ParseResult<'t, Expression> {
     ))
 }
 
+/// Parses `assert(<expression>)` or `assert(<expression>, "<message>")`.
+fn assert_expression<'t>(ctx: Context<'t>) -> ParseResult<'t, Expression> {
+    let span = ctx.span();
+    let ctx = expect!(ctx.skip(1), T::LeftParen, "Expected '(' after assert");
+    let (ctx, skip_newlines) = ctx.push_skip_newlines(true);
+    let (ctx, condition) = expression(ctx)?;
+    let (ctx, message) = if matches!(ctx.token(), T::Comma) {
+        let ctx = ctx.skip(1);
+        match ctx.token() {
+            T::String(message) => (ctx.skip(1), Some(message.clone())),
+            _ => raise_syntax_error!(ctx, "Expected a string as assert message"),
+        }
+    } else {
+        (ctx, None)
+    };
+    let ctx = ctx.pop_skip_newlines(skip_newlines);
+    let ctx = expect!(ctx, T::RightParen, "Expected ')' after assert");
+    Ok((
+        ctx,
+        Expression {
+            span,
+            kind: ExpressionKind::Assert { condition: Box::new(condition), message },
+        },
+    ))
+}
+
 /// Parse an expression until we reach a token with higher precedence.
 fn parse_precedence<'t>(ctx: Context<'t>, prec: Prec) -> ParseResult<'t, Expression> {
     // Initial value, e.g. a number value, assignable, ...
     let (mut ctx, mut expr) = prefix(ctx)?;
-    while prec <= precedence(ctx.token()) {
+    loop {
+        ctx = ctx.skip_chain_break();
+        if prec > precedence(ctx.token()) {
+            break;
+        }
         if let Ok((_ctx, _expr)) = infix(ctx, &expr) {
             // assign to outer
             ctx = _ctx;
@@ -228,13 +316,13 @@ fn parse_precedence<'t>(ctx: Context<'t>, prec: Prec) -> ParseResult<'t, Express
 /// See the documentation on [Prec] for how to interpret and compare the
 /// variants.
 #[rustfmt::skip]
-fn precedence(token: &T) -> Prec {
+pub(crate) fn precedence(token: &T) -> Prec {
     use Prec;
 
     match token {
         T::LeftBracket | T::Dot | T::LeftParen => Prec::Index,
 
-        T::Star | T::Slash => Prec::Factor,
+        T::Star | T::Slash | T::Percent => Prec::Factor,
 
         T::Minus | T::Plus => Prec::Term,
 
@@ -243,7 +331,8 @@ fn precedence(token: &T) -> Prec {
         | T::GreaterEqual
         | T::Less
         | T::LessEqual
-        | T::NotEqual => Prec::Comp,
+        | T::NotEqual
+        | T::Is => Prec::Comp,
 
         T::And => Prec::BoolAnd,
         T::Or => Prec::BoolOr,
@@ -281,7 +370,28 @@ fn prefix<'t>(ctx: Context<'t>) -> ParseResult<'t, Expression> {
 
     match ctx.token() {
         T::Fn => function(ctx),
+        T::Const => {
+            let (ctx, mut function) = function(ctx.skip(1))?;
+            if let ExpressionKind::Function { is_const, .. } = &mut function.kind {
+                *is_const = true;
+            }
+            Ok((ctx, function))
+        }
+
+        T::Loop => {
+            let span = ctx.span();
+            let (ctx, kind) = loop_statement(ctx, true)?;
+            let body = Statement {
+                span,
+                kind,
+                comments: Vec::new(),
+                trailing_comments: Vec::new(),
+                annotations: Vec::new(),
+            };
+            Ok((ctx.prev(), Expression { span, kind: ExpressionKind::Loop(Box::new(body)) }))
+        }
 
+        T::Assert => assert_expression(ctx),
         T::LeftParen => grouping_or_tuple(ctx),
         T::LeftBracket => list(ctx),
         T::LeftBrace => set_or_dict(ctx),
@@ -323,6 +433,20 @@ fn prefix<'t>(ctx: Context<'t>) -> ParseResult<'t, Expression> {
             }
         }
 
+        // These jump, so they have to be statements of their own - in a
+        // condition it'd be unclear what the condition evaluates to.
+        T::Break | T::Continue | T::Ret => {
+            raise_syntax_error!(
+                ctx,
+                "'{}' is a statement and can't be used inside an expression",
+                match ctx.token() {
+                    T::Break => "break",
+                    T::Continue => "continue",
+                    _ => "ret",
+                }
+            );
+        }
+
         t => {
             raise_syntax_error!(ctx, "No valid expression starts with '{:?}'", t);
         }
@@ -331,15 +455,17 @@ fn prefix<'t>(ctx: Context<'t>) -> ParseResult<'t, Expression> {
 
 /// Parse a unary operator followed by an expression, e.g. `-5`.
 fn unary<'t>(ctx: Context<'t>) -> ParseResult<'t, Expression> {
-    use ExpressionKind::{Neg, Not};
+    use ExpressionKind::{Float, Int, Neg, Not};
 
     let (op, span, ctx) = ctx.eat();
     let (ctx, expr) = parse_precedence(ctx, Prec::Factor)?;
-    let expr = Box::new(expr);
 
-    let kind = match op {
-        T::Minus => Neg(expr),
-        T::Not => Not(expr),
+    let kind = match (op, &expr.kind) {
+        // Negated number literals are folded, so they count as literals.
+        (T::Minus, Int(i)) => Int(-i),
+        (T::Minus, Float(f)) => Float(-f),
+        (T::Minus, _) => Neg(Box::new(expr)),
+        (T::Not, _) => Not(Box::new(expr)),
 
         _ => {
             raise_syntax_error!(ctx, "Invalid unary operator");
@@ -431,6 +557,15 @@ fn infix<'t>(ctx: Context<'t>, lhs: &Expression) -> ParseResult<'t, Expression>
         (T::Arrow, _) => {
             return arrow_call(ctx, lhs);
         }
+        // The right hand side is a type, not an expression.
+        (T::Is, _) => {
+            let span = ctx.span();
+            let (ctx, ty) = parse_type(ctx.skip(1))?;
+            return Ok((ctx, Expression {
+                span,
+                kind: Is(Box::new(lhs.clone()), ty),
+            }));
+        }
 
         (T::Prime | T::LeftParen | T::LeftBracket | T::Dot, _) => {
             let (ctx, ass) = sub_assignable(ctx, Assignable {
@@ -457,6 +592,7 @@ fn infix<'t>(ctx: Context<'t>, lhs: &Expression) -> ParseResult<'t, Expression>
         | T::Minus
         | T::Star
         | T::Slash
+        | T::Percent
         | T::EqualEqual
         | T::NotEqual
         | T::Greater
@@ -492,6 +628,7 @@ fn infix<'t>(ctx: Context<'t>, lhs: &Expression) -> ParseResult<'t, Expression>
         T::Minus => Sub(lhs, rhs),
         T::Star => Mul(lhs, rhs),
         T::Slash => Div(lhs, rhs),
+        T::Percent => Mod(lhs, rhs),
 
         // Comparisons
         T::EqualEqual => Comparison(lhs, Equals, rhs),
@@ -588,15 +725,28 @@ fn blob<'t>(ctx: Context<'t>) -> ParseResult<'t, Expression> {
                 break;
             }
 
-            // Another field, e.g. `b: 55`.
+            // Another field, e.g. `b: 55`, or `b` which is short for `b: b`.
             T::Identifier(name) => {
                 // Get the field name.
                 let name = name.clone();
 
-                ctx = expect!(ctx.skip(1), T::Colon, "Expected ':' after field name");
-                // Get the value; `55` in the example above.
-                let (_ctx, expr) = expression(ctx)?;
-                ctx = _ctx; // assign to outer
+                let expr = if matches!(ctx.skip(1).token(), T::Comma | T::RightBrace) {
+                    let span = ctx.span();
+                    ctx = ctx.skip(1);
+                    Expression {
+                        span,
+                        kind: ExpressionKind::Get(Assignable {
+                            span,
+                            kind: AssignableKind::Read(Identifier { span, name: name.clone() }),
+                        }),
+                    }
+                } else {
+                    ctx = expect!(ctx.skip(1), T::Colon, "Expected ':' after field name");
+                    // Get the value; `55` in the example above.
+                    let (_ctx, expr) = expression(ctx)?;
+                    ctx = _ctx; // assign to outer
+                    expr
+                };
 
                 if !matches!(ctx.token(), T::Comma | T::RightBrace) {
                     raise_syntax_error!(
@@ -689,8 +839,9 @@ fn set_or_dict<'t>(ctx: Context<'t>) -> ParseResult<'t, Expression> {
                 break;
             }
 
-            // Free-standing colon, i.e. "empty dict pair".
-            T::Colon => {
+            // Free-standing colon, i.e. "empty dict pair". A colon followed
+            // by anything else is a type constant, like `{:int: 1}`.
+            T::Colon if matches!(ctx.skip(1).token(), T::EOF | T::RightBrace | T::Comma) => {
                 // Only valid if we don't know yet.
                 if let Some(is_dict) = is_dict {
                     raise_syntax_error!(
@@ -706,18 +857,26 @@ fn set_or_dict<'t>(ctx: Context<'t>) -> ParseResult<'t, Expression> {
             // Something that's part of an inner expression.
             _ => {
                 // Parse the expression.
+                let key_ctx = ctx;
                 let (_ctx, expr) = detail_if_error!(expression(ctx), "failed to parse dict or set")?;
                 ctx = _ctx; // assign to outer
-                exprs.push(expr);
 
                 // If a) we know we're a dict or b) the next token is a colon, parse the value of the dict.
                 // Also, if we didn't know previously, store whether we're a dict or not.
                 if *is_dict.get_or_insert_with(|| matches!(ctx.token(), T::Colon)) {
+                    if let Some(key) = constant_key(&expr) {
+                        if exprs.iter().step_by(2).any(|other| other == &expr) {
+                            raise_syntax_error!(key_ctx, "Key {} is declared twice in dict", key);
+                        }
+                    }
+                    exprs.push(expr);
                     ctx = expect!(ctx, T::Colon, "Expected ':' for dict pair");
                     // Parse value expression.
                     let (_ctx, expr) = expression(ctx)?;
                     ctx = _ctx; // assign to outer
                     exprs.push(expr);
+                } else {
+                    exprs.push(expr);
                 }
 
                 if !matches!(ctx.token(), T::Comma | T::RightBrace) {
@@ -746,6 +905,20 @@ fn set_or_dict<'t>(ctx: Context<'t>) -> ParseResult<'t, Expression> {
     Ok((ctx, Expression { span, kind }))
 }
 
+/// Formats literal dict keys, which can be checked for duplicates
+/// when parsing.
+fn constant_key(expr: &Expression) -> Option<String> {
+    use ExpressionKind::*;
+    match &expr.kind {
+        Float(f) => Some(format!("{:?}", f)),
+        Int(i) => Some(format!("{}", i)),
+        Str(s) => Some(format!("\"{}\"", s)),
+        Bool(b) => Some(format!("{}", b)),
+        Nil => Some("nil".to_string()),
+        _ => None,
+    }
+}
+
 /// Parse a single expression.
 ///
 /// An expression is either a function expression or a "normal"
@@ -764,15 +937,19 @@ mod test {
     use super::ExpressionKind::*;
     use crate::expression;
     use crate::expression::ComparisonKind;
-    use crate::test;
+    use crate::{fail, test};
     use crate::Assignable;
     use crate::AssignableKind::*;
 
     test!(expression, value: "0" => Int(0));
     test!(expression, add: "0 + 1.0" => Add(_, _));
     test!(expression, mul: "\"abc\" * \"abc\"" => Mul(_, _));
+    test!(expression, modulo: "7 % 3" => Mod(_, _));
+    test!(expression, modulo_is_factor: "1 + 7 % 3" => Add(_, _));
     test!(expression, ident: "a" => Get(Assignable { kind: Read(_), .. }));
     test!(expression, access: "a.b" => Get(Assignable { kind: Access(_, _), .. }));
+    test!(expression, access_broken: "a\n    .b" => Get(Assignable { kind: Access(_, _), .. }));
+    test!(expression, arrow_broken: "a\n    -> b()\n    -> c()" => Get(Assignable { kind: ArrowCall(_, _, _), .. }));
     test!(expression, index_ident: "a[a]" => Get(Assignable { kind: Index(_, _), .. }));
     test!(expression, index_expr: "a[1 + 2 + 3]" => Get(Assignable { kind: Index(_, _), .. }));
     test!(expression, grouping: "(0 * 0) + 1" => Add(_, _));
@@ -785,6 +962,11 @@ mod test {
     test!(expression, dict: "{1: 1}" => Dict(_));
     test!(expression, zero_set: "{}" => Set(_));
     test!(expression, zero_dict: "{:}" => Dict(_));
+    test!(expression, dict_type_keys: "{:int: 1, :str: 2}" => Dict(_));
+    test!(expression, set_of_types: "{:int, :str}" => Set(_));
+    test!(expression, dict_same_variable_keys: "{a: 1, a: 2}" => Dict(_));
+    fail!(expression, dict_duplicate_key: "{1: 1, 2: 2, 1: 3}" => _);
+    fail!(expression, dict_duplicate_str_key: "{\"a\": 1, \"a\": 1}" => _);
 
     test!(expression, in_list: "a in [1, 2, 3]" => Comparison(_, ComparisonKind::In, _));
     test!(expression, in_set: "2 in {1, 1, 2}" => Comparison(_, ComparisonKind::In, _));
@@ -821,9 +1003,27 @@ mod test {
     test!(expression, blob: "A { a: 1 + 1, b: nil }" => Blob { .. });
     test!(expression, blob_more: "A { a: 2, \n c: 2 }" => Blob { .. });
     test!(expression, blob_empty: "A {}" => Blob { .. });
+    test!(expression, blob_shorthand: "A { a, b }" => Blob { .. });
+    test!(expression, blob_shorthand_mixed: "A { a, b: 1 }" => Blob { .. });
 
     test!(expression, simple: "fn -> do end" => _);
     test!(expression, argument: "fn a: int -> int do ret a + 1 end" => _);
+    test!(expression, assert_expr: "assert(a)" => Assert { message: None, .. });
+    test!(expression, assert_expr_message: "assert(a,\n \"x\")" => Assert { message: Some(_), .. });
+    fail!(expression, assert_expr_not_string: "assert(a, b)" => _);
+    test!(expression, brace_body: "fn a: int -> int {\n a\n}" => Function { .. });
+    test!(expression, brace_body_one_line: "fn a: int -> int { a }" => Function { .. });
+    test!(expression, brace_body_void: "fn -> { a }" => Function { ret: crate::Type { kind: crate::TypeKind::Resolved(crate::RuntimeType::Void), .. }, .. });
+    test!(expression, brace_body_set_return: "fn -> {int} { {1} }" => Function { ret: crate::Type { kind: crate::TypeKind::Set(_), .. }, .. });
+    test!(expression, brace_body_set_return_do: "fn -> {int} do {1} end" => Function { ret: crate::Type { kind: crate::TypeKind::Set(_), .. }, .. });
+    test!(expression, set_argument: "f({1, 2})" => Get(_));
+    test!(expression, set_argument_after_fn: "f(fn -> int { 1 }, {1, 2})" => Get(_));
+    fail!(expression, break_in_expression: "break" => _);
+    fail!(expression, continue_in_grouping: "(continue)" => _);
+    fail!(expression, ret_in_call: "f(ret)" => _);
+    test!(expression, terse: "fn a: int -> int = a + 1" => Function { .. });
+    test!(expression, terse_no_params: "fn -> str = \"a\"" => Function { .. });
+    fail!(expression, terse_without_return_type: "fn a: int = a + 1" => _);
 
     test!(expression, booleans: "true and false or not false" => _);
     test!(expression, bool_and: "true and a" => _);
@@ -850,7 +1050,25 @@ mod test {
     test!(expression, type_expr_custom_chaining: ":A.b.C" => _);
 
     test!(expression, void_simple: "fn do end" => _);
+    test!(expression, const_fn: "const fn a: int -> int do ret a end" => Function { is_const: true, .. });
+    test!(expression, not_const_fn: "fn a: int -> int do ret a end" => Function { is_const: false, .. });
+    fail!(expression, const_without_fn: "const 1" => _);
     test!(expression, void_argument: "fn a: int do ret a + 1 end" => _);
+    fail!(expression, duplicate_parameter: "fn a: int, b: int, a: int do end" => _);
+
+    test!(expression, neg_int: "-1" => Int(-1));
+    test!(expression, neg_float: "-1.5" => Float(_));
+    test!(expression, neg_neg_int: "--1" => Int(1));
+    test!(expression, neg_variable: "-a" => Neg(_));
+    test!(expression, neg_paren: "-(1)" => Neg(_));
+    test!(expression, neg_mul: "-1 * 2" => Neg(_));
+    fail!(expression, dict_duplicate_neg_key: "{-1: 1, -1: 2}" => _);
+
+    test!(expression, is_primitive: "a is int" => Is(_, _));
+    test!(expression, is_union: "a is int | str" => Is(_, _));
+    test!(expression, is_blob: "a is b.C" => Is(_, _));
+    test!(expression, is_and: "a is int and b" => And(_, _));
+    test!(expression, is_if_expr: "1 if a is int else 2" => IfExpression { .. });
 
     test!(expression, if_expr: "a if b else c" => IfExpression { .. });
     test!(expression, if_expr_more: "1 + 1 + 1 if b else 2 + 2 + 2" => IfExpression { .. });
@@ -860,6 +1078,7 @@ impl PrettyPrint for Expression {
     fn pretty_print(&self, f: &mut std::fmt::Formatter<'_>, indent: usize) -> std::fmt::Result {
         use ExpressionKind as EK;
         write_indent(f, indent)?;
+        write_span(f, &self.span)?;
         match &self.kind {
             EK::Get(e) => {
                 write!(f, "Get ")?;
@@ -889,6 +1108,11 @@ impl PrettyPrint for Expression {
                 a.pretty_print(f, indent + 1)?;
                 b.pretty_print(f, indent + 1)?;
             }
+            EK::Mod(a, b) => {
+                writeln!(f, "Mod")?;
+                a.pretty_print(f, indent + 1)?;
+                b.pretty_print(f, indent + 1)?;
+            }
             EK::Neg(a) => {
                 write!(f, "Neg\n")?;
                 a.pretty_print(f, indent + 1)?;
@@ -898,11 +1122,19 @@ impl PrettyPrint for Expression {
                 a.pretty_print(f, indent + 1)?;
                 b.pretty_print(f, indent + 1)?;
             }
+            EK::Is(a, ty) => {
+                write!(f, "Is {}\n", ty)?;
+                a.pretty_print(f, indent + 1)?;
+            }
             EK::AssertEq(a, b) => {
                 write!(f, "AssertEq\n")?;
                 a.pretty_print(f, indent + 1)?;
                 b.pretty_print(f, indent + 1)?;
             }
+            EK::Assert { condition, message } => {
+                writeln!(f, "Assert {:?}", message)?;
+                condition.pretty_print(f, indent + 1)?;
+            }
             EK::And(a, b) => {
                 write!(f, "And\n")?;
                 a.pretty_print(f, indent + 1)?;
@@ -933,7 +1165,14 @@ impl PrettyPrint for Expression {
                 write!(f, "fail:\n")?;
                 fail.pretty_print(f, indent + 1)?;
             }
-            EK::Function { name, params, ret, body } => {
+            EK::Loop(body) => {
+                writeln!(f, "Loop")?;
+                body.pretty_print(f, indent + 1)?;
+            }
+            EK::Function { name, params, ret, is_const, body } => {
+                if *is_const {
+                    write!(f, "Const ")?;
+                }
                 write!(f, "Fn {} ", name)?;
                 for (i, (name, ty)) in params.iter().enumerate() {
                     if i != 0 { write!(f, ", ")?; }
@@ -1,18 +1,22 @@
 use self::expression::expression;
 use self::statement::outer_statement;
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Debug};
+use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
 use sylt_common::error::Error;
 use sylt_common::Type as RuntimeType;
-use sylt_tokenizer::{PlacedToken, Token, ZERO_SPAN, string_to_tokens};
+use sylt_tokenizer::{PlacedToken, Token, ZERO_SPAN, shebang, string_to_tokens};
 
 pub mod expression;
 pub mod statement;
+pub mod visitor;
 pub use self::expression::{Expression, ExpressionKind};
 pub use self::statement::{Statement, StatementKind};
 
-pub use sylt_tokenizer::Span;
+pub use sylt_tokenizer::{Comment, Span};
 
 type T = Token;
 
@@ -26,17 +30,60 @@ pub trait Numbered {
 
 /// Contains modules.
 #[derive(Debug, Clone)]
+#[derive(Serialize)]
 pub struct AST {
     pub modules: Vec<(PathBuf, Module)>,
 }
 
 /// Contains statements.
 #[derive(Debug, Clone)]
+#[derive(Serialize)]
 pub struct Module {
     pub span: Span,
+    /// The `#!...` line the file starts with, if any.
+    pub shebang: Option<String>,
     pub statements: Vec<Statement>,
 }
 
+/// Modules parsed by earlier calls to [tree_with_cache], keyed on the file
+/// they came from, the root its `use /...` paths were resolved against and a
+/// hash of its content.
+///
+/// The cache is owned by the caller so it can be kept between runs, e.g. when
+/// watching files for changes.
+#[derive(Debug, Clone, Default)]
+pub struct ParseCache {
+    modules: HashMap<PathBuf, (PathBuf, u64, Module, Vec<PathBuf>)>,
+    hits: usize,
+}
+
+impl ParseCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How many modules have been reused instead of parsed.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    fn get(&mut self, file: &Path, root: &Path, hash: u64) -> Option<(Module, Vec<PathBuf>)> {
+        match self.modules.get(file) {
+            Some((cached_root, cached, module, next)) if cached_root == root && *cached == hash => {
+                self.hits += 1;
+                Some((module.clone(), next.clone()))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn content_hash(source: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    hasher.finish()
+}
+
 /// The precedence of an operator.
 ///
 /// A higher precedence means that something should be more tightly bound. For
@@ -64,6 +111,7 @@ pub enum Prec {
 /// Forced variable kinds are a signal to the type checker that the type is
 /// assumed and shouldn't be checked.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Serialize)]
 pub enum VarKind {
     Const,
     Mutable,
@@ -81,17 +129,20 @@ impl VarKind {
     }
 }
 
-/// The different kinds of assignment operators: `+=`, `-=`, `*=`, `/=` and `=`.
+/// The different kinds of assignment operators: `+=`, `-=`, `*=`, `/=`, `%=` and `=`.
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Serialize)]
 pub enum Op {
     Nop,
     Add,
     Sub,
     Mul,
     Div,
+    Mod,
 }
 
 #[derive(Debug, Clone)]
+#[derive(Serialize)]
 pub struct Identifier {
     pub span: Span,
     pub name: String,
@@ -126,6 +177,7 @@ impl PartialEq for Identifier {
 /// )
 /// ```
 #[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize)]
 pub enum AssignableKind {
     Read(Identifier),
     /// A function call.
@@ -149,6 +201,7 @@ pub enum AssignableKind {
 /// )
 /// ```
 #[derive(Debug, Clone)]
+#[derive(Serialize)]
 pub struct Assignable {
     pub span: Span,
     pub kind: AssignableKind,
@@ -161,6 +214,7 @@ impl PartialEq for Assignable {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize)]
 pub enum TypeKind {
     /// An unspecified type that is left to the type checker.
     Implied,
@@ -188,6 +242,7 @@ pub enum TypeKind {
 
 /// A parsed type. Contains any [TypeKind].
 #[derive(Debug, Clone)]
+#[derive(Serialize)]
 pub struct Type {
     pub span: Span,
     pub kind: TypeKind,
@@ -201,6 +256,14 @@ impl PartialEq for Type {
 
 type ParseResult<'t, T> = Result<(Context<'t>, T), (Context<'t>, Vec<Error>)>;
 
+/// A loop the parser is inside of, linked to the loops around it.
+#[derive(Debug, Copy, Clone)]
+struct EnclosingLoop<'a> {
+    label: Option<&'a str>,
+    is_expression: bool,
+    outer: Option<&'a EnclosingLoop<'a>>,
+}
+
 /// Keeps track of where the parser is currently parsing.
 #[derive(Debug, Copy, Clone)]
 pub struct Context<'a> {
@@ -220,6 +283,8 @@ pub struct Context<'a> {
     pub file: &'a Path,
     /// The source root - the top most folder.
     pub root: &'a Path,
+    /// The innermost loop around the current token, in the same function.
+    loops: Option<&'a EnclosingLoop<'a>>,
 }
 
 impl<'a> Context<'a> {
@@ -231,8 +296,51 @@ impl<'a> Context<'a> {
             spans,
             curr: 0,
             file,
-            root
+            root,
+            loops: None,
+        }
+    }
+
+    /// Continues from where `inner` stopped, inside the same loops as before.
+    fn resume(&self, inner: Context<'_>) -> Self {
+        Self {
+            skip_newlines: inner.skip_newlines,
+            last_statement: inner.last_statement,
+            curr: inner.curr,
+            ..*self
+        }
+    }
+
+    /// Parses with only `loops` around, `None` for the body of a function.
+    fn with_loops<'l, R>(
+        &self,
+        loops: Option<&'l EnclosingLoop<'l>>,
+        parse: impl FnOnce(Context<'l>) -> ParseResult<'l, R>,
+    ) -> ParseResult<'a, R>
+    where
+        'a: 'l,
+    {
+        match parse(Context { loops, ..*self }) {
+            Ok((inner, result)) => Ok((self.resume(inner), result)),
+            Err((inner, errors)) => Err((self.resume(inner), errors)),
+        }
+    }
+
+    /// Whether a loop around the current token has the label.
+    fn loop_labeled(&self, name: &str) -> bool {
+        let mut enclosing = self.loops;
+        while let Some(current) = enclosing {
+            if current.label == Some(name) {
+                return true;
+            }
+            enclosing = current.outer;
         }
+        false
+    }
+
+    /// Whether the innermost loop around the current token is a loop expression.
+    fn in_loop_expression(&self) -> bool {
+        matches!(self.loops, Some(current) if current.is_expression)
     }
 
     /// Get a [Span] representing the current location of the parser.
@@ -240,7 +348,7 @@ impl<'a> Context<'a> {
         *self.peek().1
     }
 
-    fn comments_since_last_statement(&self) -> Vec<String> {
+    fn comments_since_last_statement(&self) -> Vec<Comment> {
         self.tokens
             .iter()
             .skip(self.last_statement)
@@ -252,6 +360,42 @@ impl<'a> Context<'a> {
             .collect()
     }
 
+    /// Takes the comments since the last statement up to the newline that
+    /// ended the current one, and starts the next statement there. Comments
+    /// on the lines after it belong to the next statement.
+    ///
+    /// Returns the comments above the last line of the statement, and the
+    /// ones trailing on it.
+    fn comments_until_newline(&self) -> (Self, Vec<Comment>, Vec<Comment>) {
+        let mut end = self.curr;
+        while end > self.last_statement && matches!(self.tokens[end - 1], T::Comment(_)) {
+            end -= 1;
+        }
+        if end == 0 || !matches!(self.tokens[end - 1], T::Newline) {
+            end = self.curr;
+        }
+        let last_line = (self.last_statement..end)
+            .rev()
+            .find(|&i| !matches!(self.tokens[i], T::Comment(_) | T::Newline))
+            .map(|i| self.spans[i].line);
+        let mut above = Vec::new();
+        let mut trailing = Vec::new();
+        for i in self.last_statement..end {
+            if let T::Comment(comment) = &self.tokens[i] {
+                match last_line {
+                    Some(line) if self.spans[i].line >= line => trailing.push(comment.clone()),
+                    _ => above.push(comment.clone()),
+                }
+            }
+        }
+        // A line with only comments on it isn't an empty line.
+        let mut next = Self { last_statement: end, ..*self };
+        while matches!(next.token(), T::Newline) && matches!(self.tokens[next.curr - 1], T::Comment(_)) {
+            next = next.skip(1);
+        }
+        (next, above, trailing)
+    }
+
     /// Move to the next nth token.
     fn skip(&self, n: usize) -> Self {
         let mut new = *self;
@@ -307,6 +451,21 @@ impl<'a> Context<'a> {
         }
     }
 
+    /// Skip line breaks followed by a `->` or a `.`, since those continue a
+    /// chain of calls from the line before - or by an `and` or an `or`, which
+    /// continue a condition.
+    fn skip_chain_break(&self) -> Self {
+        let mut new = *self;
+        while matches!(new.token(), T::Newline) {
+            new.curr += 1;
+        }
+        if matches!(new.token(), T::Arrow | T::Dot | T::And | T::Or) {
+            new
+        } else {
+            *self
+        }
+    }
+
     fn skip_if(&self, token: T) -> Self {
         if self.token() == &token {
             self.skip(1)
@@ -315,6 +474,47 @@ impl<'a> Context<'a> {
         }
     }
 
+    /// Skip past a statement starting at the current token, that failed to
+    /// parse at `error`.
+    ///
+    /// Brackets and blocks opened by the statement are skipped up to their
+    /// matching closers, so a mistake inside a block doesn't spill over into
+    /// the code following it. Stops at the first newline after the error where
+    /// everything is closed, or at a closer belonging to an enclosing block.
+    /// Always moves at least one token.
+    fn recover(&self, error: Self) -> Self {
+        let error = error.prev().curr;
+        let mut open = Vec::new();
+        let mut new = *self;
+        loop {
+            match new.token() {
+                T::EOF => break,
+                T::Newline if open.is_empty() && new.curr >= error => break,
+                T::LeftParen | T::LeftBracket | T::LeftBrace | T::Do => open.push(new.token().clone()),
+                closer @ (T::RightParen | T::RightBracket | T::RightBrace | T::End | T::Else) => {
+                    let opener = match closer {
+                        T::RightParen => T::LeftParen,
+                        T::RightBracket => T::LeftBracket,
+                        T::RightBrace => T::LeftBrace,
+                        _ => T::Do,
+                    };
+                    // Unclosed brackets inside the matching pair are dropped.
+                    match open.iter().rposition(|t| t == &opener) {
+                        Some(at) => open.truncate(at),
+                        None => break,
+                    }
+                }
+                _ => {}
+            }
+            new.curr += 1;
+        }
+        if new.curr == self.curr {
+            self.skip(1)
+        } else {
+            new
+        }
+    }
+
     fn _skip_if_any<const N: usize>(&self, tokens: [T; N]) -> Self {
         if tokens.iter().any(|t| self.token() == t) {
             self.skip(1)
@@ -461,7 +661,7 @@ macro_rules! skip_until {
 
 /// Parse a [Type] definition, e.g. `fn int, int, bool -> bool`.
 pub fn parse_type<'t>(ctx: Context<'t>) -> ParseResult<'t, Type> {
-    use RuntimeType::{Bool, Float, Int, String, Void};
+    use RuntimeType::{Bool, Float, Int, String, Ty, Void};
     use TypeKind::*;
     let span = ctx.span();
     let (ctx, kind) = match ctx.token() {
@@ -471,6 +671,7 @@ pub fn parse_type<'t>(ctx: Context<'t>) -> ParseResult<'t, Type> {
             "float" => (ctx.skip(1), Resolved(Float)),
             "bool" => (ctx.skip(1), Resolved(Bool)),
             "str" => (ctx.skip(1), Resolved(String)),
+            "type" => (ctx.skip(1), Resolved(Ty)),
             _ => {
                 let (ctx, assignable) = assignable(ctx)?;
                 (ctx, UserDefined(assignable))
@@ -732,6 +933,7 @@ fn assignable_dot<'t>(ctx: Context<'t>, accessed: Assignable) -> ParseResult<'t,
 
 /// Parse a (maybe empty) "sub-assignable", i.e. either a call or indexable.
 fn sub_assignable<'t>(ctx: Context<'t>, assignable: Assignable) -> ParseResult<'t, Assignable> {
+    let ctx = ctx.skip_chain_break();
     match ctx.token() {
         T::Prime | T::LeftParen => assignable_call(ctx, assignable),
         T::LeftBracket => assignable_index(ctx, assignable),
@@ -773,6 +975,18 @@ fn assignable<'t>(ctx: Context<'t>) -> ParseResult<'t, Assignable> {
     sub_assignable(ctx.skip(1), ident)
 }
 
+/// The tokens the parser works on. A `;` ends a statement the same way a
+/// newline does, so they are read as newlines.
+fn parser_tokens(token_stream: &[PlacedToken]) -> Vec<Token> {
+    token_stream
+        .iter()
+        .map(|p| match p.token {
+            T::Semicolon => T::Newline,
+            _ => p.token.clone(),
+        })
+        .collect()
+}
+
 /// Parses a file's tokens. Returns a list of files it refers to (via `use`s) and
 /// the parsed statements.
 ///
@@ -782,19 +996,20 @@ fn assignable<'t>(ctx: Context<'t>) -> ParseResult<'t, Assignable> {
 /// continuation is performed, so errored statements are skipped until a newline
 /// or EOF.
 fn module(path: &Path, root: &Path, token_stream: &[PlacedToken]) -> (Vec<PathBuf>, Result<Module, Vec<Error>>) {
-    let tokens: Vec<_> = token_stream.iter().map(|p| p.token.clone()).collect();
+    let tokens = parser_tokens(token_stream);
     let spans: Vec<_> = token_stream.iter().map(|p| p.span).collect();
     let mut ctx = Context::new(&tokens, &spans, path, root);
     let mut errors = Vec::new();
     let mut use_files = Vec::new();
     let mut statements = Vec::new();
     while !matches!(ctx.token(), T::EOF) {
-        // Ignore newlines.
-        if matches!(ctx.token(), T::Newline) {
+        // Ignore newlines. Comments are picked up by the next statement.
+        if matches!(ctx.token(), T::Newline | T::Comment(_)) {
             ctx = ctx.skip(1);
             continue;
         }
         // Parse an outer statement.
+        let start = ctx;
         ctx = match outer_statement(ctx) {
             Ok((ctx, statement)) => {
                 use StatementKind::*;
@@ -809,17 +1024,22 @@ fn module(path: &Path, root: &Path, token_stream: &[PlacedToken]) -> (Vec<PathBu
                 errors.append(&mut errs);
 
                 // "Error recovery"
-                skip_until!(ctx, T::Newline)
+                start.recover(ctx)
             }
         }
     }
 
+    errors.append(&mut imported_name_collisions(path, &statements));
+    errors.append(&mut misplaced_loop_jumps(path, &statements));
+
     let trailing_comments = ctx.comments_since_last_statement();
     if !trailing_comments.is_empty() {
         statements.push(Statement {
             span: ctx.span(),
             kind: StatementKind::EmptyStatement,
             comments: trailing_comments,
+            trailing_comments: Vec::new(),
+            annotations: Vec::new(),
         });
     }
 
@@ -828,6 +1048,7 @@ fn module(path: &Path, root: &Path, token_stream: &[PlacedToken]) -> (Vec<PathBu
             use_files,
             Ok(Module {
                 span: Span::zero(),
+                shebang: None,
                 statements,
             }),
         )
@@ -836,6 +1057,182 @@ fn module(path: &Path, root: &Path, token_stream: &[PlacedToken]) -> (Vec<PathBu
     }
 }
 
+/// Find names imported with `use <file> { <name> }` that are already used for
+/// something else in the same file.
+fn imported_name_collisions(path: &Path, statements: &[Statement]) -> Vec<Error> {
+    use statement::NameIdentifier;
+    use StatementKind::*;
+
+    let mut names = HashSet::new();
+    for statement in statements.iter() {
+        match &statement.kind {
+            | Use { name: NameIdentifier::Implicit(Identifier { name, .. }), .. }
+            | Use { name: NameIdentifier::Alias(Identifier { name, .. }), .. }
+            | Blob { name, .. }
+            | Definition { ident: Identifier { name, .. }, .. }
+            | ExternalDefinition { ident: Identifier { name, .. }, .. } => {
+                names.insert(name.as_str());
+            }
+            _ => {}
+        }
+    }
+
+    let mut errors = Vec::new();
+    for statement in statements.iter() {
+        if let Use { name: NameIdentifier::Select(selected), .. } = &statement.kind {
+            for (_, alias) in selected.iter() {
+                if !names.insert(alias.name.as_str()) {
+                    errors.push(Error::SyntaxError {
+                        file: path.to_path_buf(),
+                        span: alias.span,
+                        message: format!("Cannot import '{}', the name is already taken", alias.name),
+                    });
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// Find `break` and `continue` statements that aren't in a loop, or with
+/// labels that don't belong to any of the loops they are in.
+fn misplaced_loop_jumps(path: &Path, statements: &[Statement]) -> Vec<Error> {
+    use visitor::{walk_expression, walk_statement, Visitor};
+
+    struct Labels<'p> {
+        path: &'p Path,
+        labels: Vec<String>,
+        /// How many loops there are around the statement.
+        loops: usize,
+        errors: Vec<Error>,
+    }
+
+    impl Labels<'_> {
+        fn undefined(&mut self, label: &Identifier) {
+            self.errors.push(Error::SyntaxError {
+                file: self.path.to_path_buf(),
+                span: label.span,
+                message: format!("There is no loop labeled '{}' here", label.name),
+            });
+        }
+
+        fn visit_loop(&mut self, statement: &Statement) {
+            let label = match &statement.kind {
+                StatementKind::Loop { label, .. } => label,
+                _ => return,
+            };
+            if let Some(label) = label {
+                self.labels.push(label.name.clone());
+            }
+            self.loops += 1;
+            walk_statement(self, statement);
+            self.loops -= 1;
+            if label.is_some() {
+                self.labels.pop();
+            }
+        }
+    }
+
+    impl Visitor for Labels<'_> {
+        fn visit_statement(&mut self, statement: &Statement) {
+            use StatementKind::*;
+            match &statement.kind {
+                Loop { .. } => self.visit_loop(statement),
+                Break { label: Some(label), .. } | Continue { label: Some(label) }
+                    if !self.labels.contains(&label.name) =>
+                {
+                    self.undefined(label);
+                }
+                Break { .. } | Continue { .. } if self.loops == 0 => {
+                    let jump = if matches!(statement.kind, Break { .. }) { "break" } else { "continue" };
+                    self.errors.push(Error::SyntaxError {
+                        file: self.path.to_path_buf(),
+                        span: statement.span,
+                        message: format!("'{}' can only be used inside a loop", jump),
+                    });
+                }
+                _ => walk_statement(self, statement),
+            }
+        }
+
+        fn visit_expression(&mut self, expression: &Expression) {
+            match &expression.kind {
+                // Loops can't be broken out of from inside a function.
+                ExpressionKind::Function { .. } => {
+                    let labels = std::mem::take(&mut self.labels);
+                    let loops = std::mem::take(&mut self.loops);
+                    walk_expression(self, expression);
+                    self.labels = labels;
+                    self.loops = loops;
+                }
+                ExpressionKind::Loop(body) => self.visit_loop(body),
+                _ => walk_expression(self, expression),
+            }
+        }
+    }
+
+    let mut labels = Labels {
+        path,
+        labels: Vec::new(),
+        loops: 0,
+        errors: Vec::new(),
+    };
+    for statement in statements.iter() {
+        labels.visit_statement(statement);
+    }
+    labels.errors
+}
+
+/// Find statements that come right after a `ret`, `break` or `continue` in
+/// the same block, they can never run. Only the first one in each block is
+/// reported.
+fn unreachable_code(path: &Path, statements: &[Statement]) -> Vec<Error> {
+    use visitor::{walk_statement, Visitor};
+
+    struct Unreachable<'p> {
+        path: &'p Path,
+        warnings: Vec<Error>,
+    }
+
+    impl Visitor for Unreachable<'_> {
+        fn visit_statement(&mut self, statement: &Statement) {
+            use StatementKind::*;
+            if let Block { statements } = &statement.kind {
+                let dead = statements
+                    .iter()
+                    .skip_while(|s| !matches!(s.kind, Ret { .. } | Break { .. } | Continue { .. }))
+                    .skip(1)
+                    .find(|s| !matches!(s.kind, EmptyStatement));
+                if let Some(dead) = dead {
+                    self.warnings.push(Error::Warning {
+                        file: self.path.to_path_buf(),
+                        span: dead.span,
+                        message: "This code is unreachable".to_string(),
+                    });
+                }
+            }
+            walk_statement(self, statement);
+        }
+    }
+
+    let mut unreachable = Unreachable { path, warnings: Vec::new() };
+    for statement in statements.iter() {
+        unreachable.visit_statement(statement);
+    }
+    unreachable.warnings
+}
+
+/// Finds things in the tree that are allowed, but most likely mistakes.
+///
+/// Returns a [Vec] of [Error::Warning]s, which shouldn't stop the program
+/// from running.
+pub fn warnings(tree: &AST) -> Vec<Error> {
+    tree.modules
+        .iter()
+        .flat_map(|(path, module)| unreachable_code(path, &module.statements))
+        .collect()
+}
+
 /// Look for git conflict markers (`<<<<<<<`) in a file.
 ///
 /// Since conflict markers might be present anywhere, we don't even try to save
@@ -866,6 +1263,19 @@ pub fn find_conflict_markers(file: &Path, source: &str) -> Vec<Error> {
     errs
 }
 
+/// Finds block comments which are never closed.
+fn unterminated_comments(file: &Path, tokens: &[PlacedToken]) -> Vec<Error> {
+    tokens
+        .iter()
+        .filter(|placed| placed.token == T::UnterminatedComment)
+        .map(|placed| Error::SyntaxError {
+            file: file.to_path_buf(),
+            span: placed.span,
+            message: "Unterminated block comment, it needs a matching '*/'".to_string(),
+        })
+        .collect()
+}
+
 /// Parses the contents of a file as well as all files this file refers to and so
 /// on.
 ///
@@ -876,6 +1286,15 @@ pub fn find_conflict_markers(file: &Path, source: &str) -> Vec<Error> {
 /// Returns any errors that occured when parsing the file(s). Basic error
 /// continuation is performed as documented in [module].
 pub fn tree<F>(path: &Path, reader: F) -> Result<AST, Vec<Error>>
+where
+    F: Fn(&Path) -> Result<String, Error>
+{
+    tree_with_cache(path, reader, &mut ParseCache::new())
+}
+
+/// Like [tree], but modules whose content hasn't changed since they were put
+/// in the `cache` aren't parsed again.
+pub fn tree_with_cache<F>(path: &Path, reader: F, cache: &mut ParseCache) -> Result<AST, Vec<Error>>
 where
     F: Fn(&Path) -> Result<String, Error>
 {
@@ -903,11 +1322,31 @@ where
                     continue;
                 }
 
+                let hash = content_hash(&source);
+                if let Some((module, mut next)) = cache.get(&file, root, hash) {
+                    modules.push((file.clone(), module));
+                    to_visit.append(&mut next);
+                    visited.insert(file);
+                    continue;
+                }
+
                 let tokens = string_to_tokens(&source);
+                // The rest of the file would be parsed as code, which only gives
+                // confusing errors.
+                let mut comment_errors = unterminated_comments(&file, &tokens);
+                if !comment_errors.is_empty() {
+                    errors.append(&mut comment_errors);
+                    visited.insert(file);
+                    continue;
+                }
                 // Parse the module.
                 let (mut next, result) = module(&file, &root, &tokens);
                 match result {
-                    Ok(module) => modules.push((file.clone(), module)),
+                    Ok(mut module) => {
+                        module.shebang = shebang(&source).map(String::from);
+                        cache.modules.insert(file.clone(), (root.to_path_buf(), hash, module.clone(), next.clone()));
+                        modules.push((file.clone(), module));
+                    }
                     Err(mut errs) => errors.append(&mut errs),
                 }
                 to_visit.append(&mut next);
@@ -935,6 +1374,155 @@ where
     }
 }
 
+/// Parses a single [Expression] from a string, e.g. `1 + a.b(2)`.
+///
+/// # Errors
+///
+/// Returns the syntax errors if the string isn't a valid expression, or if
+/// anything is left after the expression.
+pub fn parse_expression(source: &str) -> Result<Expression, Vec<Error>> {
+    parse_str(source, expression)
+}
+
+/// Parses a single [Type] from a string, e.g. `fn int, str -> bool?`.
+///
+/// # Errors
+///
+/// Returns the syntax errors if the string isn't a valid type, or if anything
+/// is left after the type.
+pub fn parse_type_from_str(source: &str) -> Result<Type, Vec<Error>> {
+    parse_str(source, parse_type)
+}
+
+/// The result of [parse_incremental].
+#[derive(Debug)]
+pub enum Incremental {
+    /// The input is one whole outer statement.
+    Complete(Box<Statement>),
+    /// The input ended while the statement was still open, e.g. inside a
+    /// block or halfway through an expression. More input might finish it.
+    Incomplete,
+    /// The input is wrong, no matter what comes after it.
+    SyntaxError(Vec<Error>),
+}
+
+/// Parses a single outer [Statement] from input that might not be finished
+/// yet, like the lines typed into a REPL so far.
+///
+/// Syntax errors caused by the input ending too early - at the very end, or
+/// inside a bracket that is never closed - mean the parser wants more, so the
+/// input is [Incremental::Incomplete] rather than wrong. Statements in blocks
+/// end at newlines, so errors there are real even if the block isn't closed.
+/// Errors inside an unclosed bracket are only reported once it is closed.
+pub fn parse_incremental(source: &str) -> Incremental {
+    let source = format!("{}\n", source.trim_end());
+    let token_stream = string_to_tokens(&source);
+    let tokens = parser_tokens(&token_stream);
+    let spans: Vec<_> = token_stream.iter().map(|p| p.span).collect();
+    let path = PathBuf::from("<str>");
+    let ctx = Context::new(&tokens, &spans, &path, &path);
+    match outer_statement(ctx) {
+        Ok((ctx, statement)) => {
+            let ctx = skip_while!(ctx, T::Newline);
+            if matches!(ctx.token(), T::EOF) {
+                Incremental::Complete(Box::new(statement))
+            } else {
+                Incremental::SyntaxError(vec![syntax_error!(
+                    ctx,
+                    "Unexpected {:?} after the end",
+                    ctx.token()
+                )])
+            }
+        }
+        Err((_, errs)) => {
+            let unclosed = first_unclosed_bracket(&token_stream);
+            let ran_out = |err: &Error| match err {
+                // There are no tokens past the end, so errors there get the zero span.
+                Error::SyntaxError { span, .. } if *span == ZERO_SPAN => true,
+                Error::SyntaxError { span, .. } => unclosed.is_some_and(|open| {
+                    (span.line, span.col_start) > (open.line, open.col_start)
+                }),
+                _ => false,
+            };
+            if errs.iter().all(ran_out) {
+                Incremental::Incomplete
+            } else {
+                Incremental::SyntaxError(errs)
+            }
+        }
+    }
+}
+
+/// Finds the outermost `(`, `[` or `{` that doesn't have a matching closer.
+fn first_unclosed_bracket(tokens: &[PlacedToken]) -> Option<Span> {
+    let mut open = Vec::new();
+    for placed in tokens.iter() {
+        let opener = match placed.token {
+            T::LeftParen | T::LeftBracket | T::LeftBrace => {
+                open.push((placed.token.clone(), placed.span));
+                continue;
+            }
+            T::RightParen => T::LeftParen,
+            T::RightBracket => T::LeftBracket,
+            T::RightBrace => T::LeftBrace,
+            _ => continue,
+        };
+        if let Some(at) = open.iter().rposition(|(t, _)| t == &opener) {
+            open.truncate(at);
+        }
+    }
+    open.first().map(|(_, span)| *span)
+}
+
+fn parse_str<P>(
+    source: &str,
+    parser: for<'t> fn(Context<'t>) -> ParseResult<'t, P>,
+) -> Result<P, Vec<Error>> {
+    let token_stream = string_to_tokens(source);
+    let tokens = parser_tokens(&token_stream);
+    let spans: Vec<_> = token_stream.iter().map(|p| p.span).collect();
+    let path = PathBuf::from("<str>");
+    let ctx = Context::new(&tokens, &spans, &path, &path);
+    let (ctx, parsed) = parser(ctx).map_err(|(_, errs)| errs)?;
+    let ctx = skip_while!(ctx, T::Newline);
+    if !matches!(ctx.token(), T::EOF) {
+        return Err(vec![syntax_error!(ctx, "Unexpected {:?} after the end", ctx.token())]);
+    }
+    Ok(parsed)
+}
+
+/// A top-level declaration and the comments written above it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocComment {
+    pub name: String,
+    pub span: Span,
+    pub comments: Vec<Comment>,
+}
+
+/// Pairs the blobs and definitions in the outer scope of a module with the
+/// comments right above them, for generating docs. Declarations without
+/// comments are left out.
+pub fn doc_comments(module: &Module) -> Vec<DocComment> {
+    module
+        .statements
+        .iter()
+        .filter(|statement| !statement.comments.is_empty())
+        .filter_map(|statement| {
+            let name = match &statement.kind {
+                StatementKind::Blob { name, .. } => name.clone(),
+                StatementKind::Definition { ident, .. }
+                | StatementKind::ExternalDefinition { ident, .. } => ident.name.clone(),
+                _ => return None,
+            };
+            Some(DocComment {
+                name,
+                span: statement.span,
+                comments: statement.comments.clone(),
+            })
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -945,7 +1533,7 @@ mod test {
             #[test]
             fn $name() {
                 let token_stream = ::sylt_tokenizer::string_to_tokens($str);
-                let tokens: Vec<_> = token_stream.iter().map(|p| p.token.clone()).collect();
+                let tokens = $crate::parser_tokens(&token_stream);
                 let spans: Vec<_> = token_stream.iter().map(|p| p.span).collect();
                 let path = ::std::path::PathBuf::from(stringify!($name));
                 let result = $f($crate::Context::new(&tokens, &spans, &path, &path));
@@ -978,7 +1566,7 @@ mod test {
             #[test]
             fn $name() {
                 let token_stream = ::sylt_tokenizer::string_to_tokens($str);
-                let tokens: Vec<_> = token_stream.iter().map(|p| p.token.clone()).collect();
+                let tokens = $crate::parser_tokens(&token_stream);
                 let spans: Vec<_> = token_stream.iter().map(|p| p.span).collect();
                 let path = ::std::path::PathBuf::from(stringify!($name));
                 let result = $f($crate::Context::new(&tokens, &spans, &path, &path));
@@ -1007,6 +1595,7 @@ mod test {
         test!(parse_type, type_int: "int" => Resolved(RT::Int));
         test!(parse_type, type_float: "float" => Resolved(RT::Float));
         test!(parse_type, type_str: "str" => Resolved(RT::String));
+        test!(parse_type, type_type: "type" => Resolved(RT::Ty));
         test!(parse_type, type_unknown_access: "a.A | int" => Union(_, _));
         test!(parse_type, type_unknown_access_call: "a.b().A | int" => Union(_, _));
         test!(parse_type, type_unknown: "blargh" => UserDefined(_));
@@ -1033,12 +1622,237 @@ mod test {
         test!(parse_type, type_dict_one: "{int : int}" => Dict(_, _));
         test!(parse_type, type_dict_complex: "{int | float? : int | int | int?}" => Dict(_, _));
     }
+
+    mod from_str {
+        use super::*;
+
+        #[test]
+        fn expression() {
+            let parsed = parse_expression("1 + a.b(2)\n").unwrap();
+            assert!(matches!(parsed.kind, ExpressionKind::Add(_, _)));
+        }
+
+        #[test]
+        fn expression_trailing() {
+            let errs = parse_expression("1 + 2 3").unwrap_err();
+            assert!(matches!(errs.as_slice(), [Error::SyntaxError { .. }]));
+        }
+
+        #[test]
+        fn expression_invalid() {
+            assert!(parse_expression("1 +").is_err());
+        }
+
+        #[test]
+        fn ty() {
+            let parsed = parse_type_from_str("fn int, str -> bool?").unwrap();
+            assert!(matches!(parsed.kind, TypeKind::Fn(_, _)));
+        }
+
+        #[test]
+        fn ty_trailing() {
+            let errs = parse_type_from_str("[int] int").unwrap_err();
+            assert!(matches!(errs.as_slice(), [Error::SyntaxError { .. }]));
+        }
+    }
+
+    #[test]
+    fn doc_comment_on_blob() {
+        let source = "// A point.\n\
+                      // In 2D.\n\
+                      Point :: blob {\n    x: int,\n    y: int,\n}\n\
+                      \n\
+                      start :: fn do end\n\
+                      // The origin.\n\
+                      origin :: Point { x: 0, y: 0 }\n";
+        let path = PathBuf::from("doc_comment_on_blob");
+        let (_, module) = module(&path, &path, &string_to_tokens(source));
+        let docs = doc_comments(&module.unwrap());
+        assert_eq!(docs.len(), 2);
+        assert_eq!(docs[0].name, "Point");
+        assert_eq!(
+            docs[0].comments,
+            vec![Comment::Line("A point.".to_string()), Comment::Line("In 2D.".to_string())]
+        );
+        assert_eq!(docs[0].span.line, 3);
+        assert_eq!(docs[1].name, "origin");
+        assert_eq!(docs[1].comments, vec![Comment::Line("The origin.".to_string())]);
+    }
+
+    #[test]
+    fn trailing_comments() {
+        let source = "a :: 1 /* one */ // two\n\
+                      // three\n\
+                      b :: 2\n";
+        let path = PathBuf::from("trailing_comments");
+        let (_, module) = module(&path, &path, &string_to_tokens(source));
+        let statements = module.unwrap().statements;
+        assert_eq!(statements.len(), 2);
+        assert!(statements[0].comments.is_empty());
+        assert_eq!(
+            statements[0].trailing_comments,
+            vec![Comment::Block("/* one */".to_string()), Comment::Line("two".to_string())]
+        );
+        assert_eq!(statements[1].comments, vec![Comment::Line("three".to_string())]);
+        assert!(statements[1].trailing_comments.is_empty());
+    }
+
+    mod parse_cache {
+        use super::*;
+        use std::cell::{Cell, RefCell};
+
+        const MAIN: &str = "use other\nstart :: fn do\n    other.f()\nend\n";
+        const OTHER: &str = "f :: fn do end\n";
+
+        #[test]
+        fn unchanged_files_are_reused() {
+            let reads = Cell::new(0);
+            let other = RefCell::new(OTHER.to_string());
+            let reader = |p: &Path| {
+                reads.set(reads.get() + 1);
+                Ok(if p.ends_with("other.sy") { other.borrow().clone() } else { MAIN.to_string() })
+            };
+            let path = PathBuf::from("parse_cache/main.sy");
+            let mut cache = ParseCache::new();
+
+            let first = tree_with_cache(&path, &reader, &mut cache).unwrap();
+            assert_eq!(first.modules.len(), 2);
+            assert_eq!(reads.get(), 2);
+            assert_eq!(cache.hits(), 0);
+
+            let second = tree_with_cache(&path, &reader, &mut cache).unwrap();
+            assert_eq!(second.modules.len(), 2);
+            assert_eq!(reads.get(), 4);
+            assert_eq!(cache.hits(), 2);
+
+            *other.borrow_mut() = "f :: fn do\n    1\nend\n".to_string();
+            tree_with_cache(&path, &reader, &mut cache).unwrap();
+            assert_eq!(cache.hits(), 3);
+        }
+
+        #[test]
+        fn uses_are_resolved_against_the_new_root() {
+            let reader = |p: &Path| {
+                Ok(if p.ends_with("main.sy") {
+                    "use lib/a\nstart :: fn do end\n".to_string()
+                } else if p.ends_with("a.sy") {
+                    "use /b\n".to_string()
+                } else {
+                    OTHER.to_string()
+                })
+            };
+            let mut cache = ParseCache::new();
+
+            let lib = tree_with_cache(&PathBuf::from("parse_cache/lib/a.sy"), &reader, &mut cache).unwrap();
+            assert!(lib.modules.iter().any(|(file, _)| file == Path::new("parse_cache/lib/b.sy")));
+
+            let main = tree_with_cache(&PathBuf::from("parse_cache/main.sy"), &reader, &mut cache).unwrap();
+            assert!(main.modules.iter().any(|(file, _)| file == Path::new("parse_cache/b.sy")));
+            assert_eq!(cache.hits(), 0);
+        }
+    }
+
+    mod dead_code {
+        use super::*;
+
+        fn unreachable_lines(file: &str) -> Vec<usize> {
+            let path = PathBuf::from(format!("../tests/dead_code/{}", file));
+            let tree = tree(&path, |p: &Path| Ok(std::fs::read_to_string(p).unwrap())).unwrap();
+            warnings(&tree)
+                .iter()
+                .map(|warning| match warning {
+                    Error::Warning { span, .. } => span.line,
+                    _ => unreachable!(),
+                })
+                .collect()
+        }
+
+        #[test]
+        fn after_ret() {
+            assert_eq!(unreachable_lines("after_ret.sy"), vec![3]);
+        }
+
+        #[test]
+        fn after_break() {
+            assert_eq!(unreachable_lines("after_break.sy"), vec![6]);
+        }
+
+        #[test]
+        fn after_continue() {
+            assert_eq!(unreachable_lines("after_continue.sy"), vec![6]);
+        }
+
+        #[test]
+        fn branches() {
+            assert_eq!(unreachable_lines("branches.sy"), Vec::<usize>::new());
+        }
+    }
+
+    mod incremental {
+        use super::*;
+
+        macro_rules! incremental {
+            ($name:ident: $str:expr => $ans:pat) => {
+                #[test]
+                fn $name() {
+                    let result = parse_incremental($str);
+                    assert!(
+                        matches!(result, $ans),
+                        "\nExpected: {}, but got: {:?}",
+                        stringify!($ans),
+                        result
+                    );
+                }
+            };
+        }
+
+        incremental!(definition: "a := 1" => Incremental::Complete(_));
+        incremental!(function: "f :: fn do\n    a := 1\nend\n" => Incremental::Complete(_));
+        incremental!(empty: "" => Incremental::Complete(_));
+
+        incremental!(open_block: "f :: fn do" => Incremental::Incomplete);
+        incremental!(open_nested_block: "f :: fn do\n    if a do\n        b()\n    end\n" => Incremental::Incomplete);
+        incremental!(open_paren: "f :: fn do\n    a := (1 +" => Incremental::Incomplete);
+        incremental!(open_list: "a := [1, 2," => Incremental::Incomplete);
+        incremental!(open_blob: "A :: blob {\n    a: int," => Incremental::Incomplete);
+
+        incremental!(two_values: "a := 1 2" => Incremental::SyntaxError(_));
+        incremental!(unmatched_closer: "a := )" => Incremental::SyntaxError(_));
+        incremental!(error_in_open_block: "f :: fn do\n    a := 1 2\n" => Incremental::SyntaxError(_));
+        incremental!(missing_value: "a :=" => Incremental::SyntaxError(_));
+        incremental!(two_statements: "a := 1\nb := 2" => Incremental::SyntaxError(_));
+    }
+
+    #[test]
+    fn pretty_print_with_spans() {
+        let path = PathBuf::from("spans");
+        let source = "start :: fn do\n    a := 1 + b\nend\n";
+        let (_, result) = module(&path, &path, &string_to_tokens(source));
+        let ast = AST { modules: vec![(path, result.unwrap())] };
+        let printed = ast.pretty_print_with_spans();
+        assert!(printed.contains("1:1-6 <Def> start"));
+        assert!(printed.contains("2:5-6 <Def> a"));
+        assert!(printed.contains("2:12-13 Add"));
+        assert!(printed.contains("2:10-11 Int(1)"));
+        assert!(printed.contains("2:14-15 Get 2:14-15 [Read] b"));
+        assert!(!format!("{}", ast).contains("1:1-6"));
+    }
 }
 
 trait PrettyPrint {
     fn pretty_print(&self, f: &mut std::fmt::Formatter<'_>, indent: usize) -> std::fmt::Result;
 }
 
+impl AST {
+    /// Pretty prints the tree like [Display] does, but with the [Span] of
+    /// every statement, expression and assignable written out as
+    /// `line:col_start-col_end`.
+    pub fn pretty_print_with_spans(&self) -> String {
+        format!("{:#}", self)
+    }
+}
+
+/// The alternate flag, `{:#}`, also writes out the spans of the nodes.
 impl Display for AST {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for (name, modu) in self.modules.iter() {
@@ -1049,6 +1863,14 @@ impl Display for AST {
     }
 }
 
+/// Writes the span if the spans are asked for, see [AST::pretty_print_with_spans].
+fn write_span(f: &mut std::fmt::Formatter<'_>, span: &Span) -> std::fmt::Result {
+    if f.alternate() {
+        write!(f, "{}:{}-{} ", span.line, span.col_start, span.col_end)?;
+    }
+    Ok(())
+}
+
 const INDENT_SPACING: &str = "  ";
 fn write_indent(f: &mut std::fmt::Formatter<'_>, indent: usize) -> std::fmt::Result {
     for _ in 0..indent {
@@ -1070,7 +1892,11 @@ impl PrettyPrint for Statement {
     fn pretty_print(&self, f: &mut std::fmt::Formatter<'_>, indent: usize) -> std::fmt::Result {
         use StatementKind as SK;
         write_indent(f, indent)?;
-        write!(f, "{} ", self.span.line)?;
+        if f.alternate() {
+            write_span(f, &self.span)?;
+        } else {
+            write!(f, "{} ", self.span.line)?;
+        }
         match &self.kind {
             SK::Use { path, name, file } => {
                 write!(f, "<Use> {} {}", path.name, name)?;
@@ -1093,6 +1919,20 @@ impl PrettyPrint for Statement {
                 write!(f, "<ExtDef> {} {:?} {}\n", ident.name, kind, ty)?;
                 return Ok(());
             }
+            SK::DestructuringDefinition { idents, kind, value } => {
+                let names: Vec<_> = idents.iter().map(|ident| ident.name.as_str()).collect();
+                writeln!(f, "<DestructDef> ({}) {:?}", names.join(", "), kind)?;
+                value.pretty_print(f, indent + 1)?;
+                return Ok(());
+            }
+            SK::DestructuringAssignment { targets, value } => {
+                writeln!(f, "<DestructAss>")?;
+                for target in targets.iter() {
+                    target.pretty_print(f, indent + 1)?;
+                }
+                value.pretty_print(f, indent + 1)?;
+                return Ok(());
+            }
             SK::Assignment { kind, target, value } => {
                 write!(f, "<Ass> {:?}\n", kind)?;
                 target.pretty_print(f, indent + 1)?;
@@ -1106,17 +1946,52 @@ impl PrettyPrint for Statement {
                 fail.pretty_print(f, indent + 1)?;
                 return Ok(());
             }
-            SK::Loop { condition, body } => {
-                write!(f, "<Loop>\n")?;
+            SK::Loop { label, condition, body } => {
+                write!(f, "<Loop>")?;
+                if let Some(label) = label {
+                    write!(f, " {}", label.name)?;
+                }
+                write!(f, "\n")?;
                 condition.pretty_print(f, indent + 1)?;
                 body.pretty_print(f, indent + 1)?;
                 return Ok(());
             }
-            SK::Break => {
+            SK::TypeMatch { value, arms, fallback, .. } => {
+                writeln!(f, "<TypeMatch>")?;
+                value.pretty_print(f, indent + 1)?;
+                for (ty, body) in arms.iter() {
+                    write_indent(f, indent + 1)?;
+                    writeln!(f, "{} ->", ty)?;
+                    body.pretty_print(f, indent + 2)?;
+                }
+                if let Some(fallback) = fallback {
+                    write_indent(f, indent + 1)?;
+                    writeln!(f, "_ ->")?;
+                    fallback.pretty_print(f, indent + 2)?;
+                }
+                return Ok(());
+            }
+            SK::Defer { body } => {
+                writeln!(f, "<Defer>")?;
+                body.pretty_print(f, indent + 1)?;
+                return Ok(());
+            }
+            SK::Break { label, value } => {
                 write!(f, "<Break>")?;
+                if let Some(label) = label {
+                    write!(f, " {}", label.name)?;
+                }
+                if let Some(value) = value {
+                    writeln!(f)?;
+                    value.pretty_print(f, indent + 1)?;
+                    return Ok(());
+                }
             }
-            SK::Continue => {
+            SK::Continue { label } => {
                 write!(f, "<Continue>")?;
+                if let Some(label) = label {
+                    write!(f, " {}", label.name)?;
+                }
             }
             SK::IsCheck { lhs, rhs } => {
                 write!(f, "<Is> {} {}", lhs, rhs)?;
@@ -1138,6 +2013,11 @@ impl PrettyPrint for Statement {
             SK::Unreachable => {
                 write!(f, "<!>")?;
             }
+            SK::Assert { expr, message } => {
+                write!(f, "<Assert> {:?}\n", message)?;
+                expr.pretty_print(f, indent + 1)?;
+                return Ok(());
+            }
             SK::EmptyStatement => {
                 write!(f, "<>")?;
             }
@@ -1202,6 +2082,7 @@ impl Display for Type {
 impl PrettyPrint for Assignable {
     fn pretty_print(&self, f: &mut std::fmt::Formatter<'_>, indent: usize) -> std::fmt::Result {
         // Deliberately doesn't write out the indentation
+        write_span(f, &self.span)?;
         match &self.kind {
             AssignableKind::Read(ident) => {
                 write!(f, "[Read] {}", ident.name)?;
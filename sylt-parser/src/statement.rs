@@ -1,12 +1,21 @@
 use super::*;
+use crate::expression::precedence;
 
 /// The different ways a namespace is introduced by a use statement.
 #[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize)]
 pub enum NameIdentifier {
     /// When the identifier is implicit from the path. For example, `use a/b` introduces `b`.
     Implicit(Identifier),
     /// When the identifier is an alias. For example, `use a/b as c` introduces `c`.
     Alias(Identifier),
+    /// When specific names are imported from the file, as pairs of the name in
+    /// the file and the name it is introduced as. For example, `use a/b.c as d`
+    /// introduces `d` and `use a/b { c, d }` introduces `c` and `d`.
+    Select(Vec<(Identifier, Identifier)>),
+    /// When all names in the file are imported. For example, `use a/b.*`
+    /// introduces every name in `b`, including the names `b` imports in turn.
+    All,
 }
 
 /// The different kinds of [Statement]s.
@@ -17,6 +26,7 @@ pub enum NameIdentifier {
 ///
 /// Note that this shouldn't be read as a formal language specification.
 #[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize)]
 pub enum StatementKind {
     /// "Imports" another file.
     ///
@@ -27,6 +37,9 @@ pub enum StatementKind {
     /// `use / as <alias>`.
     /// `use <file> as <alias>`.
     /// `use <folder>/ as <alias>`.
+    /// `use <file>.<name>`.
+    /// `use <file>.<name> as <alias>`.
+    /// `use <file> { <name> [as <alias>].. }`.
     Use {
         path: Identifier,
         name: NameIdentifier,
@@ -61,6 +74,23 @@ pub enum StatementKind {
         value: Expression,
     },
 
+    /// Unpacks a tuple into new variables, one for each element.
+    ///
+    /// `(<name>, <name>..) := <expression>` or `(<name>, <name>..) :: <expression>`.
+    DestructuringDefinition {
+        idents: Vec<Identifier>,
+        kind: VarKind,
+        value: Expression,
+    },
+
+    /// Unpacks a tuple and assigns each element to its own target.
+    ///
+    /// `(<assignable>, <assignable>..) = <expression>`.
+    DestructuringAssignment {
+        targets: Vec<Assignable>,
+        value: Expression,
+    },
+
     /// Defines a an external variable - here the type is required.
     ///
     /// Example: `a: int = external`.
@@ -82,22 +112,46 @@ pub enum StatementKind {
     },
 
     /// Do something as long as something else evaluates to true.
+    /// The label lets `break` and `continue` in nested loops refer to it.
     ///
-    /// `loop <expression> <statement>`.
+    /// `[<label>:] loop <expression> <statement>`.
     Loop {
+        label: Option<Identifier>,
         condition: Expression,
         body: Box<Statement>,
     },
 
-    /// Jump out of a loop.
+    /// Jump out of the innermost loop, or the loop with the label. A loop
+    /// expression evaluates to the value.
+    ///
+    /// `break [<label>] [<expression>]`.
+    Break {
+        label: Option<Identifier>,
+        value: Option<Expression>,
+    },
+
+    /// Go back to the start of the innermost loop, or the loop with the label.
     ///
-    /// `break`.
-    Break,
+    /// `continue [<label>]`.
+    Continue { label: Option<Identifier> },
 
-    /// Go back to the start of the loop.
+    /// Runs the first arm whose type the value has when the program runs.
+    /// If the value is a variable, the variable has the type of the arm
+    /// inside it. The `_` arm runs if no other arm matches.
     ///
-    /// `continue`.
-    Continue,
+    /// `type_match <expression> do [<type> -> <statement>].. [_ -> <statement>] end`.
+    TypeMatch {
+        value: Expression,
+        binding: Option<Identifier>,
+        arms: Vec<(Type, Statement)>,
+        fallback: Option<Box<Statement>>,
+    },
+
+    /// Runs the statement when the enclosing block is left, however it's
+    /// left. Deferred statements run in the reverse order they were reached.
+    ///
+    /// `defer <statement>`.
+    Defer { body: Box<Statement> },
 
     /// Handles compile time checks of types.
     ///
@@ -128,18 +182,44 @@ pub enum StatementKind {
 
     /// Throws an error if it is ever evaluated.
     ///
-    /// `<!>`.
+    /// `<!>` or `unreachable`.
     Unreachable,
 
+    /// Throws an error with the optional message if the expression is false.
+    ///
+    /// `assert <expression>`.
+    /// `assert <expression>, "<message>"`.
+    Assert {
+        expr: Expression,
+        message: Option<String>,
+    },
+
     EmptyStatement,
 }
 
+/// An `@name` or `@name(args)` written on the lines above a definition.
+///
+/// Annotations are only metadata, the compiler ignores the ones it doesn't
+/// know about.
+#[derive(Debug, Clone)]
+#[derive(Serialize)]
+pub struct Annotation {
+    pub span: Span,
+    pub name: Identifier,
+    pub args: Vec<Expression>,
+}
+
 /// What makes up a program. Contains any [StatementKind].
 #[derive(Debug, Clone)]
+#[derive(Serialize)]
 pub struct Statement {
     pub span: Span,
     pub kind: StatementKind,
-    pub comments: Vec<String>,
+    /// The comments on the lines above the statement.
+    pub comments: Vec<Comment>,
+    /// The comments on the last line of the statement.
+    pub trailing_comments: Vec<Comment>,
+    pub annotations: Vec<Annotation>,
 }
 
 impl PartialEq for Statement {
@@ -173,9 +253,39 @@ pub fn path<'t>(ctx: Context<'t>) -> ParseResult<'t, Identifier> {
     Ok((ctx, Identifier { span, name: result }))
 }
 
+/// Parse a single name imported from a file, `<name> [as <alias>]`.
+fn selected_name<'t>(ctx: Context<'t>) -> ParseResult<'t, (Identifier, Identifier)> {
+    let name = match ctx.token() {
+        T::Identifier(name) => Identifier {
+            span: ctx.span(),
+            name: name.clone(),
+        },
+        _ => raise_syntax_error!(ctx, "Expected a name to import"),
+    };
+    let ctx = ctx.skip(1);
+    match &ctx.tokens_lookahead::<2>() {
+        [T::As, T::Identifier(alias)] => {
+            let alias = Identifier {
+                span: ctx.skip(1).span(),
+                name: alias.clone(),
+            };
+            Ok((ctx.skip(2), (name, alias)))
+        }
+        [T::As, ..] => raise_syntax_error!(ctx.skip(1), "Expected alias"),
+        [..] => Ok((ctx, (name.clone(), name))),
+    }
+}
+
 pub fn block<'t>(ctx: Context<'t>) -> ParseResult<'t, Vec<Statement>> {
+    if matches!(ctx.token(), T::LeftBrace) {
+        return brace_block(ctx);
+    }
     // To allow implicit block-openings, like "fn ->"
     let mut ctx = ctx.skip_if(T::Do);
+    // Empty lines at the start of the block aren't kept.
+    while matches!(ctx.token(), T::Newline) {
+        ctx = ctx.skip(1);
+    }
 
     let mut errs = Vec::new();
     let mut statements = Vec::new();
@@ -187,12 +297,14 @@ pub fn block<'t>(ctx: Context<'t>) -> ParseResult<'t, Vec<Statement>> {
                 statements.push(stmt);
             }
             Err((_ctx, mut err)) => {
-                ctx = _ctx.pop_skip_newlines(false); // assign to outer
-                ctx = skip_until!(ctx, T::Newline).skip_if(T::Newline);
+                ctx = ctx.recover(_ctx).pop_skip_newlines(false); // assign to outer
+                ctx = ctx.skip_if(T::Newline);
                 errs.append(&mut err);
             }
         }
     }
+    let (ctx, closing) = closing_comments(ctx);
+    statements.extend(closing);
 
     if errs.is_empty() {
         // Special case for chaining if-else-statements
@@ -207,6 +319,193 @@ pub fn block<'t>(ctx: Context<'t>) -> ParseResult<'t, Vec<Statement>> {
     }
 }
 
+/// Parses `{ <statements> }`. Only function bodies can be written like this,
+/// a `{` in statement position is a set or a dict.
+fn brace_block<'t>(ctx: Context<'t>) -> ParseResult<'t, Vec<Statement>> {
+    let mut ctx = expect!(ctx, T::LeftBrace, "Expected '{{' to start block");
+    while matches!(ctx.token(), T::Newline) {
+        ctx = ctx.skip(1);
+    }
+
+    let mut errs = Vec::new();
+    let mut statements = Vec::new();
+    while !matches!(ctx.token(), T::RightBrace | T::EOF) {
+        match statement(ctx) {
+            Ok((_ctx, stmt)) => {
+                ctx = _ctx; // assign to outer
+                statements.push(stmt);
+            }
+            Err((_ctx, mut err)) => {
+                ctx = ctx.recover(_ctx).pop_skip_newlines(false); // assign to outer
+                ctx = ctx.skip_if(T::Newline);
+                errs.append(&mut err);
+            }
+        }
+    }
+
+    let (ctx, closing) = closing_comments(ctx);
+    statements.extend(closing);
+
+    if errs.is_empty() {
+        let ctx = expect!(ctx, T::RightBrace, "Expected '}}' after block");
+        Ok((ctx, statements))
+    } else {
+        Err((ctx, errs))
+    }
+}
+
+/// The comments between the last statement of a block and the end of it,
+/// as an empty statement so they stay above the end.
+fn closing_comments<'t>(ctx: Context<'t>) -> (Context<'t>, Option<Statement>) {
+    let comments = ctx.comments_since_last_statement();
+    if comments.is_empty() {
+        return (ctx, None);
+    }
+    let statement = Statement {
+        span: ctx.span(),
+        kind: StatementKind::EmptyStatement,
+        comments,
+        trailing_comments: Vec::new(),
+        annotations: Vec::new(),
+    };
+    (ctx.push_last_statement_location(), Some(statement))
+}
+
+/// Parses the optional label after `break` and `continue`.
+fn loop_label<'t>(ctx: Context<'t>) -> (Context<'t>, Option<Identifier>) {
+    match ctx.token() {
+        T::Identifier(name) => (
+            ctx.skip(1),
+            Some(Identifier { name: name.clone(), span: ctx.span() }),
+        ),
+        _ => (ctx, None),
+    }
+}
+
+/// Parse a [StatementKind::Loop], `[<label>:] loop <expression> <statement>`.
+/// `is_expression` tells if the loop is used as a value.
+pub fn loop_statement<'t>(ctx: Context<'t>, is_expression: bool) -> ParseResult<'t, StatementKind> {
+    let (ctx, label) = match ctx.token() {
+        T::Identifier(name) => (
+            ctx.skip(3),
+            Some(Identifier { name: name.clone(), span: ctx.span() }),
+        ),
+        _ => (ctx.skip(1), None),
+    };
+    let (ctx, condition) = if matches!(ctx.token(), T::Do) {
+        (
+            ctx,
+            Expression {
+                span: ctx.span(),
+                kind: ExpressionKind::Bool(true),
+            },
+        )
+    } else {
+        expression(ctx)?
+    };
+    let enclosing = EnclosingLoop {
+        label: label.as_ref().map(|label| label.name.as_str()),
+        is_expression,
+        outer: ctx.loops,
+    };
+    let (ctx, body) = ctx.with_loops(Some(&enclosing), statement)?;
+    Ok((
+        ctx,
+        StatementKind::Loop {
+            label,
+            condition,
+            body: Box::new(body),
+        },
+    ))
+}
+
+/// Parse the label and value of a `break`. An identifier is a label if a loop
+/// around it has that label, and it doesn't continue into an expression. Only
+/// loop expressions can be broken with a value, so elsewhere a lone identifier
+/// is a label even if no loop has it.
+fn break_label_and_value<'t>(ctx: Context<'t>) -> ParseResult<'t, (Option<Identifier>, Option<Expression>)> {
+    let ends_statement = |token: &T| matches!(token, T::Newline | T::End | T::Else | T::RightBrace | T::EOF);
+    let (ctx, label) = match &ctx.tokens_lookahead::<2>() {
+        [T::Identifier(name), next]
+            if ctx.loop_labeled(name) && (ends_statement(next) || precedence(next) == Prec::No) =>
+        {
+            loop_label(ctx)
+        }
+        [T::Identifier(_), next] if ends_statement(next) && !ctx.in_loop_expression() => loop_label(ctx),
+        _ => (ctx, None),
+    };
+    if ends_statement(ctx.token()) {
+        Ok((ctx, (label, None)))
+    } else {
+        let (ctx, value) = expression(ctx)?;
+        Ok((ctx, (label, Some(value))))
+    }
+}
+
+/// Whether the parenthesis the tokens start with is closed right before the
+/// statement ends.
+fn closes_statement(tokens: &[T]) -> bool {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            T::LeftParen => depth += 1,
+            T::RightParen => {
+                depth -= 1;
+                if depth == 0 {
+                    return matches!(
+                        tokens.get(i + 1),
+                        None | Some(T::Newline | T::End | T::Else | T::RightBrace | T::EOF)
+                    );
+                }
+            }
+            T::EOF => return false,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Functions defined directly in a definition are named after it, so the name
+/// shows up in stack traces.
+fn named_function(mut value: Expression, ident: &Identifier) -> Expression {
+    if let ExpressionKind::Function { name, .. } = &mut value.kind {
+        *name = ident.name.clone();
+    }
+    value
+}
+
+/// Parses the annotations above a definition, each on its own line.
+fn annotations<'t>(ctx: Context<'t>) -> ParseResult<'t, Vec<Annotation>> {
+    let mut ctx = ctx;
+    let mut annotations = Vec::new();
+    while matches!(ctx.token(), T::At) {
+        let span = ctx.span();
+        let name = match ctx.skip(1).token() {
+            T::Identifier(name) => Identifier { span: ctx.skip(1).span(), name: name.clone() },
+            _ => raise_syntax_error!(ctx.skip(1), "Expected a name after '@'"),
+        };
+        ctx = ctx.skip(2);
+
+        let mut args = Vec::new();
+        if matches!(ctx.token(), T::LeftParen) {
+            let (mut inner, skip_newlines) = ctx.skip(1).push_skip_newlines(true);
+            while !matches!(inner.token(), T::RightParen) {
+                let (_ctx, arg) = expression(inner)?;
+                inner = _ctx; // assign to outer
+                args.push(arg);
+                if !matches!(inner.token(), T::Comma | T::RightParen) {
+                    raise_syntax_error!(inner, "Expected ',' or ')' after annotation argument");
+                }
+                inner = inner.skip_if(T::Comma);
+            }
+            ctx = inner.pop_skip_newlines(skip_newlines).skip(1);
+        }
+        ctx = expect!(ctx, T::Newline, "Expected newline after annotation");
+        annotations.push(Annotation { span, name, args });
+    }
+    Ok((ctx, annotations))
+}
+
 /// Parse a single [Statement].
 pub fn statement<'t>(ctx: Context<'t>) -> ParseResult<'t, Statement> {
     use StatementKind::*;
@@ -218,6 +517,8 @@ pub fn statement<'t>(ctx: Context<'t>) -> ParseResult<'t, Statement> {
     let mut comments = ctx.comments_since_last_statement();
     let ctx = ctx.push_last_statement_location();
 
+    let (ctx, annotations) = annotations(ctx)?;
+
     let span = ctx.span();
     //NOTE(gu): Explicit lookahead.
     let (ctx, kind) = match &ctx.tokens_lookahead::<3>() {
@@ -273,6 +574,33 @@ pub fn statement<'t>(ctx: Context<'t>) -> ParseResult<'t, Statement> {
                 })
             };
             let (ctx, alias) = match &ctx.tokens_lookahead::<2>() {
+                [T::Dot, T::Star] => (ctx.skip(2), NameIdentifier::All),
+                [T::Dot, ..] => {
+                    let (ctx, selected) = selected_name(ctx.skip(1))?;
+                    (ctx, NameIdentifier::Select(vec![selected]))
+                }
+                [T::LeftBrace, ..] => {
+                    let (mut ctx, skip_newlines) = ctx.skip(1).push_skip_newlines(true);
+                    let mut selected: Vec<(Identifier, Identifier)> = Vec::new();
+                    while !matches!(ctx.token(), T::RightBrace) {
+                        let (_ctx, (name, alias)) = selected_name(ctx)?;
+                        if selected.iter().any(|(_, other)| other.name == alias.name) {
+                            raise_syntax_error!(ctx, "'{}' is imported twice", alias.name);
+                        }
+                        ctx = _ctx; // assign to outer
+                        selected.push((name, alias));
+
+                        if !matches!(ctx.token(), T::Comma | T::RightBrace) {
+                            raise_syntax_error!(ctx, "Expected a name deliminator ','");
+                        }
+                        ctx = ctx.skip_if(T::Comma);
+                    }
+                    if selected.is_empty() {
+                        raise_syntax_error!(ctx, "Expected at least one name to import");
+                    }
+                    let ctx = ctx.pop_skip_newlines(skip_newlines);
+                    (ctx.skip(1), NameIdentifier::Select(selected))
+                }
                 [T::As, T::Identifier(alias), ..] => (
                     ctx.skip(2),
                     NameIdentifier::Alias(Identifier {
@@ -327,10 +655,96 @@ pub fn statement<'t>(ctx: Context<'t>) -> ParseResult<'t, Statement> {
             (ctx, IsCheck { lhs, rhs })
         }
 
-        [T::Break, ..] => (ctx.skip(1), Break),
-        [T::Continue, ..] => (ctx.skip(1), Continue),
+        // `assert(<expression>)` is the expression form, but only if the
+        // parenthesis ends the statement, `assert (a) and b` is a statement.
+        [T::Assert, T::LeftParen, ..] if closes_statement(&ctx.tokens[ctx.curr + 1..]) => {
+            let (ctx, value) = expression(ctx)?;
+            (ctx, StatementExpression { value })
+        }
+
+        // `assert <expression>, "<message>"`
+        [T::Assert, ..] => {
+            let (ctx, expr) = expression(ctx.skip(1))?;
+            let (ctx, message) = match &ctx.tokens_lookahead::<2>() {
+                [T::Comma, T::String(message)] => (ctx.skip(2), Some(message.clone())),
+                [T::Comma, ..] => raise_syntax_error!(ctx.skip(1), "Expected a string as assert message"),
+                [..] => (ctx, None),
+            };
+            (ctx, Assert { expr, message })
+        }
+
+        [T::Break, ..] => {
+            let (ctx, (label, value)) = break_label_and_value(ctx.skip(1))?;
+            (ctx, Break { label, value })
+        }
+        [T::Continue, ..] => {
+            let (ctx, label) = loop_label(ctx.skip(1));
+            (ctx, Continue { label })
+        }
         [T::Unreachable, ..] => (ctx.skip(1), Unreachable),
 
+        // `type_match <expression> do <arms> end`
+        [T::TypeMatch, ..] => {
+            let (ctx, skip_newlines) = ctx.push_skip_newlines(true);
+            let (ctx, value) = expression(ctx.skip(1))?;
+            let ctx = ctx.pop_skip_newlines(skip_newlines);
+            let mut ctx = expect!(ctx, T::Do, "Expected 'do' after the value to type_match on");
+
+            // Variables are narrowed down to the type of the arm.
+            let binding = match &value.kind {
+                ExpressionKind::Get(Assignable { kind: AssignableKind::Read(ident), .. }) => {
+                    Some(ident.clone())
+                }
+                _ => None,
+            };
+
+            let mut arms = Vec::new();
+            let mut fallback = None;
+            loop {
+                match ctx.token() {
+                    T::Newline => {
+                        ctx = ctx.skip(1);
+                    }
+                    T::End => {
+                        break;
+                    }
+                    T::EOF => {
+                        raise_syntax_error!(ctx, "Expected 'end' after type_match");
+                    }
+                    // The fallback arm, e.g. `_ -> print' 1`.
+                    T::Identifier(name) if name == "_" => {
+                        if fallback.is_some() {
+                            raise_syntax_error!(ctx, "A type_match can only have one '_' arm");
+                        }
+                        ctx = expect!(ctx.skip(1), T::Arrow, "Expected '->' after '_'");
+                        let (_ctx, body) = statement(ctx)?;
+                        ctx = _ctx; // assign to outer
+                        fallback = Some(Box::new(body));
+                    }
+                    // An arm, e.g. `int -> print' 1`.
+                    _ => {
+                        if fallback.is_some() {
+                            raise_syntax_error!(ctx, "The '_' arm has to be the last arm of a type_match");
+                        }
+                        let (_ctx, ty) = parse_type(ctx)?;
+                        ctx = expect!(_ctx, T::Arrow, "Expected '->' after the type of the arm");
+                        let (_ctx, body) = statement(ctx)?;
+                        ctx = _ctx; // assign to outer
+                        arms.push((ty, body));
+                    }
+                }
+            }
+
+            (ctx.skip(1), TypeMatch { value, binding, arms, fallback })
+        }
+
+        // `defer <statement>`
+        [T::Defer, ..] => {
+            let (ctx, body) = statement(ctx.skip(1))?;
+            // The inner statement has already eaten the newline.
+            (ctx.prev(), Defer { body: Box::new(body) })
+        }
+
         // `ret <expression>`
         [T::Ret, ..] => {
             let ctx = ctx.skip(1);
@@ -348,28 +762,10 @@ pub fn statement<'t>(ctx: Context<'t>) -> ParseResult<'t, Statement> {
             (ctx, Ret { value })
         }
 
-        // `loop <expression> <statement>`, e.g. `loop a < 10 { a += 1 }`
-        [T::Loop, ..] => {
-            let ctx = ctx.skip(1);
-            let (ctx, condition) = if matches!(ctx.token(), T::Do) {
-                (
-                    ctx,
-                    Expression {
-                        span: ctx.span(),
-                        kind: ExpressionKind::Bool(true),
-                    },
-                )
-            } else {
-                expression(ctx)?
-            };
-            let (ctx, body) = statement(ctx)?;
-            (
-                ctx.prev(),
-                Loop {
-                    condition,
-                    body: Box::new(body),
-                },
-            )
+        // `[<label>:] loop <expression> <statement>`, e.g. `loop a < 10 { a += 1 }`
+        [T::Loop, ..] | [T::Identifier(_), T::Colon, T::Loop] => {
+            let (ctx, kind) = loop_statement(ctx, false)?;
+            (ctx.prev(), kind)
         }
 
         // `if <expression> <statement> [else <statement>]`. Note that the else is optional.
@@ -390,6 +786,8 @@ pub fn statement<'t>(ctx: Context<'t>) -> ParseResult<'t, Statement> {
                         span: ctx.span(),
                         kind: EmptyStatement,
                         comments: Vec::new(),
+                        trailing_comments: Vec::new(),
+                        annotations: Vec::new(),
                     },
                 )
             };
@@ -463,6 +861,7 @@ pub fn statement<'t>(ctx: Context<'t>) -> ParseResult<'t, Statement> {
             } else {
                 // The value to assign.
                 let (ctx, value) = expression(ctx)?;
+                let value = named_function(value, &ident);
 
                 (
                     ctx,
@@ -493,6 +892,7 @@ pub fn statement<'t>(ctx: Context<'t>) -> ParseResult<'t, Statement> {
             } else {
                 // The value to assign.
                 let (ctx, value) = expression(ctx)?;
+                let value = named_function(value, &ident);
 
                 (
                     ctx,
@@ -547,6 +947,7 @@ pub fn statement<'t>(ctx: Context<'t>) -> ParseResult<'t, Statement> {
             } else {
                 // The value to define the variable to.
                 let (ctx, value) = expression(ctx)?;
+                let value = named_function(value, &ident);
 
                 ( ctx, Definition { ident, kind, ty, value } )
             }
@@ -563,6 +964,7 @@ pub fn statement<'t>(ctx: Context<'t>) -> ParseResult<'t, Statement> {
                     T::MinusEqual => Op::Sub,
                     T::StarEqual => Op::Mul,
                     T::SlashEqual => Op::Div,
+                    T::PercentEqual => Op::Mod,
                     T::Equal => Op::Nop,
 
                     t => {
@@ -581,13 +983,73 @@ pub fn statement<'t>(ctx: Context<'t>) -> ParseResult<'t, Statement> {
                 ))
             }
 
-            match (assignment(ctx), expression(ctx)) {
-                (Ok((ctx, kind)), _) => (ctx, kind),
-                (_, Ok((ctx, value))) => (ctx, StatementExpression { value }),
-                (Err((_, mut ass_errs)), Err((_, mut expr_errs))) => {
-                    ass_errs.append(&mut expr_errs);
-                    ass_errs.push(syntax_error!(ctx, "Neither an assignment or an expression"));
-                    return Err((ctx, ass_errs));
+            /// `(a, b) := c` or `(a, b.c) = d`.
+            fn destructuring<'t>(ctx: Context<'t>) -> ParseResult<'t, StatementKind> {
+                let mut ctx = expect!(ctx, T::LeftParen, "Expected '(' to start destructuring");
+                let mut targets = Vec::new();
+                while !matches!(ctx.token(), T::RightParen) {
+                    let (_ctx, target) = assignable(ctx)?;
+                    ctx = _ctx; // assign to outer
+                    targets.push(target);
+
+                    ctx = if matches!(ctx.token(), T::Comma | T::RightParen) {
+                        ctx.skip_if(T::Comma)
+                    } else {
+                        raise_syntax_error!(ctx, "Expected ',' or ')' after destructuring target")
+                    };
+                }
+                let ctx = ctx.skip(1);
+                if targets.len() < 2 {
+                    raise_syntax_error!(ctx, "Expected at least two targets to destructure into");
+                }
+
+                match ctx.token() {
+                    T::ColonColon | T::ColonEqual => {
+                        let kind = if matches!(ctx.token(), T::ColonColon) {
+                            VarKind::Const
+                        } else {
+                            VarKind::Mutable
+                        };
+                        let mut idents = Vec::new();
+                        for target in targets {
+                            match target.kind {
+                                AssignableKind::Read(ident) => idents.push(ident),
+                                _ => {
+                                    raise_syntax_error!(ctx, "Only names can be defined when destructuring");
+                                }
+                            }
+                        }
+                        let (ctx, value) = expression(ctx.skip(1))?;
+                        Ok((ctx, DestructuringDefinition { idents, kind, value }))
+                    }
+                    T::Equal => {
+                        let (ctx, value) = expression(ctx.skip(1))?;
+                        Ok((ctx, DestructuringAssignment { targets, value }))
+                    }
+                    t => {
+                        raise_syntax_error!(
+                            ctx,
+                            "Expected ':=', '::' or '=' after destructuring targets, but got '{:?}'",
+                            t
+                        );
+                    }
+                }
+            }
+
+            let destructured = if matches!(ctx.token(), T::LeftParen) {
+                destructuring(ctx)
+            } else {
+                Err((ctx, Vec::new()))
+            };
+            match (destructured, assignment(ctx), expression(ctx)) {
+                (Ok((ctx, kind)), _, _) => (ctx, kind),
+                (_, Ok((ctx, kind)), _) => (ctx, kind),
+                (_, _, Ok((ctx, value))) => (ctx, StatementExpression { value }),
+                (Err((_, mut errs)), Err((_, mut ass_errs)), Err((_, mut expr_errs))) => {
+                    errs.append(&mut ass_errs);
+                    errs.append(&mut expr_errs);
+                    errs.push(syntax_error!(ctx, "Neither an assignment or an expression"));
+                    return Err((ctx, errs));
                 }
             }
         }
@@ -595,20 +1057,33 @@ pub fn statement<'t>(ctx: Context<'t>) -> ParseResult<'t, Statement> {
 
     // Newline, RightBrace and Else can end a statment.
     // If a statement does not end, we only report it as a missing newline.
-    let ctx = if matches!(ctx.token(), T::End | T::Else) {
+    let ctx = if matches!(ctx.token(), T::End | T::Else | T::RightBrace) {
         ctx
     } else {
         expect!(ctx, T::Newline, "Expected newline to end statement")
     };
+    match annotations.first() {
+        Some(annotation) if !matches!(kind, Definition { .. } | ExternalDefinition { .. } | Blob { .. }) => {
+            let error = Error::SyntaxError {
+                file: ctx.file.to_path_buf(),
+                span: annotation.span,
+                message: "Only definitions can be annotated".into(),
+            };
+            return Err((ctx, vec![error]));
+        }
+        _ => {}
+    }
     let ctx = ctx.pop_skip_newlines(skip_newlines);
-    comments.append(&mut ctx.comments_since_last_statement());
-    let ctx = ctx.push_last_statement_location();
+    let (ctx, mut above, trailing_comments) = ctx.comments_until_newline();
+    comments.append(&mut above);
     Ok((
         ctx,
         Statement {
             span,
             kind,
             comments,
+            trailing_comments,
+            annotations,
         },
     ))
 }
@@ -643,6 +1118,7 @@ mod test {
     test!(statement, statement_break: "break\n" => _);
     test!(statement, statement_continue: "continue\n" => _);
     test!(statement, statement_mut_declaration: "a := 1 + 1\n" => _);
+    test!(statement, statement_semicolon: "a := 1;" => _);
     test!(statement, statement_const_declaration: "a :: 1 + 1\n" => _);
     test!(statement, statement_mut_type_declaration: "a :int= 1 + 1\n" => _);
     test!(statement, statement_const_type_declaration: "a :int: 1 + 1\n" => _);
@@ -652,9 +1128,41 @@ mod test {
     test!(statement, statement_if_else: "if 1 { a } else { b }\n" => _);
     test!(statement, statement_loop: "loop 1 { a }\n" => _);
     test!(statement, statement_loop_no_condition: "loop { a }\n" => _);
+    test!(statement, statement_loop_label: "outer: loop 1 { a }\n" => Loop { label: Some(_), .. });
+    test!(statement, statement_break_label: "break outer\n" => Break { label: Some(_), value: None });
+    test!(statement, statement_break_enclosing_label: "outer: loop do\n break outer\n end\n" => Loop { .. });
+    test!(statement, statement_loop_expression_break_name: "a := loop do break b end\n" => Definition { value: Expression { kind: ExpressionKind::Loop(_), .. }, .. });
+    test!(statement, statement_loop_expression: "a := loop do break 1 end\n" => Definition { value: Expression { kind: ExpressionKind::Loop(_), .. }, .. });
+    test!(statement, statement_loop_expression_condition: "a := loop a < 1 do a += 1 end\n" => Definition { value: Expression { kind: ExpressionKind::Loop(_), .. }, .. });
+    test!(statement, statement_break_value: "break 42\n" => Break { label: None, value: Some(_) });
+    test!(statement, statement_break_label_value: "outer: loop do\n break outer 42\n end\n" => Loop { .. });
+    test!(statement, statement_break_expression: "break a + 1\n" => Break { label: None, value: Some(_) });
+    test!(statement, statement_break_grouped: "break (a)\n" => Break { label: None, value: Some(_) });
+    test!(statement, statement_continue_label: "continue outer\n" => Continue { label: Some(_) });
     test!(statement, statement_ret: "ret 1 + 1\n" => _);
+    test!(statement, statement_type_match: "type_match a do\n int -> b\n str -> c\n end\n" => TypeMatch { fallback: None, .. });
+    test!(statement, statement_type_match_fallback: "type_match a do\n int -> b\n _ -> c\n end\n" => TypeMatch { binding: Some(_), fallback: Some(_), .. });
+    test!(statement, statement_type_match_expression: "type_match f(1) do\n _ -> do\n b\n end\n end\n" => TypeMatch { binding: None, .. });
+    test!(statement, statement_type_match_empty: "type_match a do\n end\n" => TypeMatch { .. });
+    fail!(statement, statement_type_match_fallback_first: "type_match a do\n _ -> b\n int -> c\n end\n" => _);
+    fail!(statement, statement_type_match_two_fallbacks: "type_match a do\n _ -> b\n _ -> c\n end\n" => _);
+    fail!(statement, statement_type_match_no_arrow: "type_match a do\n int b\n end\n" => _);
+    test!(statement, statement_defer: "defer print(1)\n" => Defer { .. });
+    test!(statement, statement_defer_block: "defer do\n a = 1\n end\n" => Defer { .. });
     test!(statement, statement_ret_newline: "ret \n" => _);
     test!(statement, statement_unreach: "<!>\n" => _);
+    test!(statement, statement_unreach_keyword: "unreachable\n" => Unreachable);
+    test!(statement, statement_annotated: "@inline\na :: 1\n" => Definition { .. });
+    test!(statement, statement_annotated_args: "@doc(\"a\", 1)\n@inline\na :: 1\n" => Definition { .. });
+    fail!(statement, statement_annotated_expression: "@inline\nf()\n" => _);
+    fail!(statement, statement_loop_break_condition: "loop break do\n end\n" => _);
+    fail!(statement, statement_if_continue_condition: "if continue do\n end\n" => _);
+    fail!(statement, statement_loop_continue_in_condition: "loop a < 3 and (continue) do\n end\n" => _);
+    fail!(statement, statement_annotation_same_line: "@inline a :: 1\n" => _);
+    test!(statement, statement_assert: "assert a < 1\n" => Assert { message: None, .. });
+    test!(statement, statement_assert_message: "assert a, \"a is false\"\n" => Assert { message: Some(_), .. });
+    test!(statement, statement_assert_expression: "assert(a, \"a is false\")\n" => StatementExpression { .. });
+    test!(statement, statement_assert_parenthesized: "assert (a) and b, \"a and b\"\n" => Assert { message: Some(_), .. });
     test!(statement, statement_blob_empty: "A :: blob {}\n" => _);
     test!(statement, statement_blob_comma: "A :: blob { a: int, b: int }\n" => _);
     test!(statement, statement_blob_comma_newline: "A :: blob { a: int,\n b: int }\n" => _);
@@ -664,9 +1172,14 @@ mod test {
     test!(statement, statement_sub_assign: "a -= 2\n" => _);
     test!(statement, statement_mul_assign: "a *= 2\n" => _);
     test!(statement, statement_div_assign: "a /= 2\n" => _);
+    test!(statement, statement_mod_assign: "a %= 2\n" => _);
     test!(statement, statement_assign_call: "a().b() += 2\n" => _);
     test!(statement, statement_assign_call_index: "a.c().c.b /= 4\n" => _);
     test!(statement, statement_idek: "a'.c'.c.b()().c = 0\n" => _);
+    test!(statement, statement_destructuring_mut: "(a, b) := c\n" => DestructuringDefinition { kind: VarKind::Mutable, .. });
+    test!(statement, statement_destructuring_const: "(a, b, c) :: d\n" => DestructuringDefinition { kind: VarKind::Const, .. });
+    test!(statement, statement_destructuring_assign: "(a.b, c[1]) = d\n" => DestructuringAssignment { .. });
+    test!(statement, statement_tuple_expression: "(a, b)\n" => StatementExpression { .. });
 
     test!(statement, statement_is_check: ":A is :B\n" => IsCheck { .. });
     test!(statement, statement_is_check_nested: ":A.c.d is :B.d.d\n" => IsCheck { .. });
@@ -676,7 +1189,7 @@ mod test {
     test!(statement, statement_skip_newline: "(1 \n\n+\n 1\n\n)\n" => _);
     test!(statement, statement_skip_newline_list: "[\n\n 1 \n\n,\n 1\n\n,]\n" => _);
     test!(statement, statement_skip_newline_set: "{\n\n 1 \n\n,\n 1\n\n,}\n" => _);
-    test!(statement, statement_skip_newline_dict: "{\n\n 1: \n3\n,\n 1\n\n:1,}\n" => _);
+    test!(statement, statement_skip_newline_dict: "{\n\n 1: \n3\n,\n 2\n\n:1,}\n" => _);
 
     test!(outer_statement, outer_statement_blob: "B :: blob {}\n" => _);
     test!(outer_statement, outer_statement_blob_no_last_comma: "B :: blob { \na: A\n }\n" => _);
@@ -686,9 +1199,18 @@ mod test {
     test!(outer_statement, outer_statement_use_rename: "use a as b\n" => _);
     test!(outer_statement, outer_statement_use_subdir: "use a/b/c/d/e\n" => _);
     test!(outer_statement, outer_statement_use_subdir_rename: "use a/b as c\n" => _);
+    test!(outer_statement, outer_statement_use_select: "use a.b\n" => Use { name: NameIdentifier::Select(_), .. });
+    test!(outer_statement, outer_statement_use_select_rename: "use a/b.c as d\n" => Use { name: NameIdentifier::Select(_), .. });
+    test!(outer_statement, outer_statement_use_select_many: "use a { b, c as d }\n" => Use { name: NameIdentifier::Select(_), .. });
+    test!(outer_statement, outer_statement_use_select_many_newline: "use a {\n b,\n c,\n}\n" => Use { name: NameIdentifier::Select(_), .. });
+    test!(outer_statement, outer_statement_use_all: "use a/b.*\n" => Use { name: NameIdentifier::All, .. });
     test!(outer_statement, outer_statement_empty: "\n" => _);
+    test!(outer_statement, outer_statement_semicolons: "f :: fn do a := 1; b := 2 end\n" => _);
 
     fail!(statement, statement_blob_newline: "A :: blob { a: int\n b: int }\n" => _);
+    fail!(outer_statement, outer_statement_use_select_twice: "use a { b, c as b }\n" => _);
+    fail!(outer_statement, outer_statement_use_select_empty: "use a {}\n" => _);
+    fail!(outer_statement, outer_statement_use_select_no_name: "use a.\n" => _);
 }
 
 impl Display for NameIdentifier {
@@ -702,6 +1224,19 @@ impl Display for NameIdentifier {
                 write!(f, "Alias(")?;
                 ident
             }
+            NameIdentifier::Select(names) => {
+                write!(f, "Select(")?;
+                for (i, (name, alias)) in names.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{} as {}", name.name, alias.name)?;
+                }
+                return write!(f, ")");
+            }
+            NameIdentifier::All => {
+                return write!(f, "All");
+            }
         };
         write!(f, "{})", ident.name)
     }
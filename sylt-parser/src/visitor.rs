@@ -0,0 +1,332 @@
+use super::*;
+
+/// Walks the syntax tree, calling back for every node it passes.
+///
+/// Every method defaults to recursing into the children of the node with the
+/// matching `walk_*` function and does nothing else. Override the methods for
+/// the nodes you care about - and call the `walk_*` function from the override
+/// to keep going deeper.
+///
+/// # Example
+///
+/// ```ignore
+/// struct Loops(usize);
+///
+/// impl Visitor for Loops {
+///     fn visit_statement(&mut self, statement: &Statement) {
+///         if matches!(statement.kind, StatementKind::Loop { .. }) {
+///             self.0 += 1;
+///         }
+///         walk_statement(self, statement);
+///     }
+/// }
+/// ```
+pub trait Visitor {
+    fn visit_ast(&mut self, ast: &AST) {
+        walk_ast(self, ast);
+    }
+
+    fn visit_module(&mut self, module: &Module) {
+        walk_module(self, module);
+    }
+
+    fn visit_statement(&mut self, statement: &Statement) {
+        walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        walk_expression(self, expression);
+    }
+
+    fn visit_assignable(&mut self, assignable: &Assignable) {
+        walk_assignable(self, assignable);
+    }
+
+    fn visit_type(&mut self, ty: &Type) {
+        walk_type(self, ty);
+    }
+
+    fn visit_identifier(&mut self, _identifier: &Identifier) {}
+}
+
+pub fn walk_ast<V: Visitor + ?Sized>(visitor: &mut V, ast: &AST) {
+    for (_, module) in ast.modules.iter() {
+        visitor.visit_module(module);
+    }
+}
+
+pub fn walk_module<V: Visitor + ?Sized>(visitor: &mut V, module: &Module) {
+    for statement in module.statements.iter() {
+        visitor.visit_statement(statement);
+    }
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &Statement) {
+    use statement::NameIdentifier;
+    use StatementKind::*;
+    match &statement.kind {
+        Use { path, name, .. } => {
+            visitor.visit_identifier(path);
+            match name {
+                NameIdentifier::Implicit(name) | NameIdentifier::Alias(name) => {
+                    visitor.visit_identifier(name)
+                }
+                NameIdentifier::Select(names) => {
+                    for (name, alias) in names.iter() {
+                        visitor.visit_identifier(name);
+                        visitor.visit_identifier(alias);
+                    }
+                }
+                NameIdentifier::All => {}
+            }
+        }
+        Blob { fields, .. } => {
+            for ty in fields.values() {
+                visitor.visit_type(ty);
+            }
+        }
+        Assignment { target, value, .. } => {
+            visitor.visit_assignable(target);
+            visitor.visit_expression(value);
+        }
+        Definition { ident, ty, value, .. } => {
+            visitor.visit_identifier(ident);
+            visitor.visit_type(ty);
+            visitor.visit_expression(value);
+        }
+        ExternalDefinition { ident, ty, .. } => {
+            visitor.visit_identifier(ident);
+            visitor.visit_type(ty);
+        }
+        DestructuringDefinition { idents, value, .. } => {
+            for ident in idents.iter() {
+                visitor.visit_identifier(ident);
+            }
+            visitor.visit_expression(value);
+        }
+        DestructuringAssignment { targets, value } => {
+            for target in targets.iter() {
+                visitor.visit_assignable(target);
+            }
+            visitor.visit_expression(value);
+        }
+        If { condition, pass, fail } => {
+            visitor.visit_expression(condition);
+            visitor.visit_statement(pass);
+            visitor.visit_statement(fail);
+        }
+        TypeMatch { value, arms, fallback, .. } => {
+            visitor.visit_expression(value);
+            for (ty, body) in arms.iter() {
+                visitor.visit_type(ty);
+                visitor.visit_statement(body);
+            }
+            if let Some(fallback) = fallback {
+                visitor.visit_statement(fallback);
+            }
+        }
+        Defer { body } => visitor.visit_statement(body),
+        Loop { label, condition, body } => {
+            if let Some(label) = label {
+                visitor.visit_identifier(label);
+            }
+            visitor.visit_expression(condition);
+            visitor.visit_statement(body);
+        }
+        Break { label, value } => {
+            if let Some(label) = label {
+                visitor.visit_identifier(label);
+            }
+            if let Some(value) = value {
+                visitor.visit_expression(value);
+            }
+        }
+        Continue { label } => {
+            if let Some(label) = label {
+                visitor.visit_identifier(label);
+            }
+        }
+        IsCheck { lhs, rhs } => {
+            visitor.visit_type(lhs);
+            visitor.visit_type(rhs);
+        }
+        Ret { value } | StatementExpression { value } | Assert { expr: value, .. } => {
+            visitor.visit_expression(value);
+        }
+        Block { statements } => {
+            for statement in statements.iter() {
+                visitor.visit_statement(statement);
+            }
+        }
+        Unreachable | EmptyStatement => {}
+    }
+}
+
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &Expression) {
+    use ExpressionKind::*;
+    match &expression.kind {
+        Get(assignable) => visitor.visit_assignable(assignable),
+        TypeConstant(ty) => visitor.visit_type(ty),
+        Add(a, b)
+        | Sub(a, b)
+        | Mul(a, b)
+        | Div(a, b)
+        | Mod(a, b)
+        | Comparison(a, _, b)
+        | AssertEq(a, b)
+        | And(a, b)
+        | Or(a, b) => {
+            visitor.visit_expression(a);
+            visitor.visit_expression(b);
+        }
+        Neg(a) | Not(a) | Parenthesis(a) | Assert { condition: a, .. } => visitor.visit_expression(a),
+        Is(a, ty) => {
+            visitor.visit_expression(a);
+            visitor.visit_type(ty);
+        }
+        IfExpression { condition, pass, fail } => {
+            visitor.visit_expression(condition);
+            visitor.visit_expression(pass);
+            visitor.visit_expression(fail);
+        }
+        Loop(body) => visitor.visit_statement(body),
+        Function { params, ret, body, .. } => {
+            for (ident, ty) in params.iter() {
+                visitor.visit_identifier(ident);
+                visitor.visit_type(ty);
+            }
+            visitor.visit_type(ret);
+            visitor.visit_statement(body);
+        }
+        Blob { blob, fields } => {
+            visitor.visit_assignable(blob);
+            for (_, value) in fields.iter() {
+                visitor.visit_expression(value);
+            }
+        }
+        Tuple(values) | List(values) | Set(values) | Dict(values) => {
+            for value in values.iter() {
+                visitor.visit_expression(value);
+            }
+        }
+        Float(_) | Int(_) | Str(_) | Bool(_) | Nil => {}
+    }
+}
+
+pub fn walk_assignable<V: Visitor + ?Sized>(visitor: &mut V, assignable: &Assignable) {
+    use AssignableKind::*;
+    match &assignable.kind {
+        Read(ident) => visitor.visit_identifier(ident),
+        Call(callee, args) => {
+            visitor.visit_assignable(callee);
+            for arg in args.iter() {
+                visitor.visit_expression(arg);
+            }
+        }
+        ArrowCall(first, callee, args) => {
+            visitor.visit_expression(first);
+            visitor.visit_assignable(callee);
+            for arg in args.iter() {
+                visitor.visit_expression(arg);
+            }
+        }
+        Access(accessed, field) => {
+            visitor.visit_assignable(accessed);
+            visitor.visit_identifier(field);
+        }
+        Index(indexed, index) => {
+            visitor.visit_assignable(indexed);
+            visitor.visit_expression(index);
+        }
+        Expression(expression) => visitor.visit_expression(expression),
+    }
+}
+
+pub fn walk_type<V: Visitor + ?Sized>(visitor: &mut V, ty: &Type) {
+    use TypeKind::*;
+    match &ty.kind {
+        Implied | Resolved(_) => {}
+        UserDefined(assignable) => visitor.visit_assignable(assignable),
+        Union(a, b) | Dict(a, b) => {
+            visitor.visit_type(a);
+            visitor.visit_type(b);
+        }
+        Fn(params, ret) => {
+            for param in params.iter() {
+                visitor.visit_type(param);
+            }
+            visitor.visit_type(ret);
+        }
+        Tuple(tys) => {
+            for ty in tys.iter() {
+                visitor.visit_type(ty);
+            }
+        }
+        List(ty) | Set(ty) | Grouping(ty) => visitor.visit_type(ty),
+        Generic(ident) => visitor.visit_identifier(ident),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Counts the function expressions in a module.
+    struct FunctionCounter(usize);
+
+    impl Visitor for FunctionCounter {
+        fn visit_expression(&mut self, expression: &Expression) {
+            if matches!(expression.kind, ExpressionKind::Function { .. }) {
+                self.0 += 1;
+            }
+            walk_expression(self, expression);
+        }
+    }
+
+    fn parse(source: &str) -> Module {
+        let path = PathBuf::from("visitor");
+        let (_, result) = module(&path, &path, &string_to_tokens(source));
+        result.unwrap()
+    }
+
+    #[test]
+    fn count_functions() {
+        let module = parse(
+            "f :: fn a: int -> int do\n\
+                 ret a + 1\n\
+             end\n\
+             g :: fn do\n\
+                 h := fn x: int -> fn int -> int do\n\
+                     ret fn y: int -> int do ret x + y end\n\
+                 end\n\
+                 loop true do\n\
+                     [fn do end, fn do end]\n\
+                 end\n\
+             end\n\
+             start :: fn do end\n",
+        );
+        let mut counter = FunctionCounter(0);
+        counter.visit_module(&module);
+        assert_eq!(counter.0, 7);
+    }
+
+    /// Collects every identifier read in a module.
+    struct Reads(Vec<String>);
+
+    impl Visitor for Reads {
+        fn visit_assignable(&mut self, assignable: &Assignable) {
+            if let AssignableKind::Read(ident) = &assignable.kind {
+                self.0.push(ident.name.clone());
+            }
+            walk_assignable(self, assignable);
+        }
+    }
+
+    #[test]
+    fn collect_reads() {
+        let module = parse("start :: fn do\n a := b.c(d, 1 + e)\n a[f] = -g\nend\n");
+        let mut reads = Reads(Vec::new());
+        reads.visit_module(&module);
+        assert_eq!(reads.0, vec!["b", "d", "e", "a", "f", "g"]);
+    }
+}
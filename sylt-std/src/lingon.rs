@@ -639,7 +639,7 @@ sylt_macro::extern_function!(
     l_random
     "Returns a uniformly sampled random float between 0 and 1"
     [] -> Type::Float => {
-        Ok(Float(Uniform.sample().into()))
+        Ok(Float(crate::sylt::random_float()))
     },
 );
 
@@ -648,16 +648,16 @@ sylt_macro::extern_function!(
     l_random_range
     "Returns a randomized integer in the given range"
     [One(Int(lo)), One(Int(hi))] -> Type::Int => {
-        Ok(Int(*lo + (Uniform.sample() * ((hi - lo + 1) as f32)) as i64))
+        Ok(Int(*lo + (crate::sylt::random_float() * ((hi - lo + 1) as f64)) as i64))
     },
     [Two(Int(lo), Int(hi))] -> Type::Int => {
-        Ok(Int(*lo + (Uniform.sample() * ((hi - lo + 1) as f32)) as i64))
+        Ok(Int(*lo + (crate::sylt::random_float() * ((hi - lo + 1) as f64)) as i64))
     },
     [One(Float(lo)), One(Float(hi))] -> Type::Float => {
-        Ok(Float(*lo + Uniform.sample() as f64 * (hi - lo)))
+        Ok(Float(*lo + crate::sylt::random_float() * (hi - lo)))
     },
     [Two(Float(lo), Float(hi))] -> Type::Float => {
-        Ok(Float(*lo + Uniform.sample() as f64 * (hi - lo)))
+        Ok(Float(*lo + crate::sylt::random_float() * (hi - lo)))
     },
 );
 
@@ -871,7 +871,7 @@ pub fn sylt_str(s: &str) -> Value {
     Value::String(Rc::new(s.to_string()))
 }
 
-#[sylt_macro::sylt_doc(l_load_image, "Loads an image and turns it into a sprite sheet",
+#[sylt_macro::sylt_doc(l_load_image, "sylt_std::lingon", "Loads an image and turns it into a sprite sheet",
   [One(String(path)), Two(Float, Float)] Type::Tuple)]
 #[sylt_macro::sylt_link(l_load_image, "sylt_std::lingon")]
 pub fn l_load_image<'t>(ctx: RuntimeContext<'t>) -> Result<Value, RuntimeError> {
@@ -898,11 +898,12 @@ pub fn l_load_image<'t>(ctx: RuntimeContext<'t>) -> Result<Value, RuntimeError>
         (values, _) => Err(RuntimeError::ExternTypeMismatch(
             "l_load_image".to_string(),
             values.iter().map(Type::from).collect(),
+            ctx.call_site,
         )),
     }
 }
 
-#[sylt_macro::sylt_doc(l_load_audio,
+#[sylt_macro::sylt_doc(l_load_audio, "sylt_std::lingon",
   "Loads a sound and lets you play it using <a href='l_audio_play'>l_audio_play</a>",
   [One(String(path))] Type::Tuple)]
 #[sylt_macro::sylt_link(l_load_audio, "sylt_std::lingon")]
@@ -919,6 +920,7 @@ pub fn l_load_audio<'t>(ctx: RuntimeContext<'t>) -> Result<Value, RuntimeError>
         (values, _) => Err(RuntimeError::ExternTypeMismatch(
             "l_load_image".to_string(),
             values.iter().map(Type::from).collect(),
+            ctx.call_site,
         )),
     }
 }
@@ -2,12 +2,15 @@
 use crate as sylt_std;
 
 use colored::Colorize;
-use std::collections::HashMap;
+use indexmap::{IndexMap, IndexSet};
+use std::collections::{HashMap, HashSet};
 use std::cell::RefCell;
 use std::rc::Rc;
 use sungod::Ra;
-use sylt_common::error::RuntimeError;
+use sylt_common::error::{Error, RuntimeError};
+use sylt_common::value::UniqueId;
 use sylt_common::{RuntimeContext, Type, Value};
+use sylt_tokenizer::Token;
 
 sylt_macro::extern_function!(
     "sylt_std::sylt",
@@ -35,31 +38,97 @@ sylt_macro::extern_function!(
 );
 
 
+thread_local! {
+    /// Set by [seed_random], `None` means the random numbers aren't
+    /// reproducible.
+    static SEEDED: RefCell<Option<Ra>> = const { RefCell::new(None) };
+}
+
+/// Makes every random number after this follow from the seed, so a run can
+/// be reproduced. `None` makes them unpredictable again.
+pub fn seed_random(seed: Option<u64>) {
+    SEEDED.with(|rng| *rng.borrow_mut() = seed.map(Ra::new));
+}
+
+/// Every random builtin gets its numbers from here, so they all follow the
+/// seed.
+pub(crate) fn random_usize() -> usize {
+    SEEDED.with(|rng| match rng.borrow_mut().as_mut() {
+        Some(rng) => rng.sample::<usize>(),
+        None => Ra::ggen::<usize>(),
+    })
+}
+
+/// A random float from 0 up to, but not including, 1.
+#[cfg(feature = "lingon")]
+pub(crate) fn random_float() -> f64 {
+    random_usize() as f64 / (usize::MAX as f64 + 1.0)
+}
+
 sylt_macro::extern_function!(
     "sylt_std::sylt",
     random_choice,
     ? "Selects an element randomly from a list",
     -> "fn [#ITEM] -> #ITEM",
     [Value::List(list)] => {
-        Ok(list.borrow()[Ra::ggen::<usize>() % list.borrow().len()].clone())
+        Ok(list.borrow()[random_usize() % list.borrow().len()].clone())
     }
 );
 
+/// Calls a function given to a builtin. Errors from inside the call are
+/// passed on as they are, anything else is blamed on the builtin `$name`.
+macro_rules! call_callback {
+    ($ctx:expr, $name:expr, $callable:expr, $args:expr) => {
+        match $ctx.machine.eval_call($callable.clone(), $args) {
+            Ok(value) => Ok(value),
+            Err(Error::RuntimeError { kind, .. }) => Err(kind),
+            Err(err) => Err(RuntimeError::ExternError($name.to_string(), err.to_string())),
+        }
+    };
+}
+
 sylt_macro::extern_function!(
     "sylt_std::sylt",
     for_each,
     ? "Does something for each element in a list",
     -> "fn [#ITEM], fn #ITEM -> void -> void",
+    [Range(range), callable] => {
+        let callable = callable.clone();
+        for element in range.iter() {
+            call_callback!(ctx, "for_each", &callable, &[&Int(element)])?;
+        }
+        Ok(Nil)
+    },
     [List(list), callable] => {
         let list = Rc::clone(list);
         let callable = callable.clone();
         for element in list.borrow().iter() {
-            ctx.machine.eval_call(callable.clone(), &[element]).unwrap();
+            call_callback!(ctx, "for_each", &callable, &[element])?;
         }
         Ok(Nil)
     }
 );
 
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    repeat_call,
+    ? "Calls a function n times and collects what it returns in a list",
+    -> "fn fn -> #ITEM, int -> [#ITEM]",
+    [callable, Int(n)] => {
+        if *n < 0 {
+            return Err(RuntimeError::ExternError(
+                "repeat_call".to_string(),
+                format!("Cannot call a function {} times", n),
+            ));
+        }
+        let callable = callable.clone();
+        let mut results = Vec::new();
+        for _ in 0..*n {
+            results.push(call_callback!(ctx, "repeat_call", &callable, &[])?);
+        }
+        Ok(List(Rc::new(RefCell::new(results))))
+    }
+);
 
 sylt_macro::extern_function!(
     "sylt_std::sylt",
@@ -131,13 +200,244 @@ sylt_macro::extern_function!(
     }
 );
 
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    count,
+    ? "Counts the elements that pass the test function",
+    -> "fn [#ITEM], fn #ITEM -> bool -> int",
+    [List(list), callable] => {
+        let list = Rc::clone(list);
+        let callable = callable.clone();
+        let mut count = 0;
+        for element in list.borrow().iter() {
+            if call_callback!(ctx, "count", &callable, &[element])? == Bool(true) {
+                count += 1;
+            }
+        }
+        Ok(Int(count))
+    }
+);
+
+/// The number of elements at the start of the list that pass the test
+/// function, shared by `take_while` and `drop_while`.
+macro_rules! passing_prefix {
+    ($ctx:expr, $name:literal, $list:expr, $callable:expr) => {{
+        let mut prefix = 0;
+        for element in $list.iter() {
+            match call_callback!($ctx, $name, $callable, &[element])? {
+                Bool(true) => prefix += 1,
+                _ => break,
+            }
+        }
+        prefix
+    }};
+}
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    take_while,
+    ? "Creates a new list with the elements before the first one that fails the test function",
+    -> "fn [#ITEM], fn #ITEM -> bool -> [#ITEM]",
+    [List(list), callable] => {
+        let list = Rc::clone(list);
+        let callable = callable.clone();
+        let prefix = passing_prefix!(ctx, "take_while", list.borrow(), callable);
+        let taken = list.borrow()[..prefix].to_vec();
+        Ok(List(Rc::new(RefCell::new(taken))))
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    drop_while,
+    ? "Creates a new list with the elements from the first one that fails the test function and on",
+    -> "fn [#ITEM], fn #ITEM -> bool -> [#ITEM]",
+    [List(list), callable] => {
+        let list = Rc::clone(list);
+        let callable = callable.clone();
+        let prefix = passing_prefix!(ctx, "drop_while", list.borrow(), callable);
+        let rest = list.borrow()[prefix..].to_vec();
+        Ok(List(Rc::new(RefCell::new(rest))))
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    sum_by,
+    ? "Sums what the function gives for each element, the sum of an empty list is 0 or 0.0",
+    -> "fn [#ITEM], fn #ITEM -> #NUM -> #NUM",
+    [List(list), callable] => {
+        use sylt_common::value::Promoted;
+        let list = Rc::clone(list);
+        let callable = callable.clone();
+        // The empty sum has the type the function returns.
+        let mut sum = match Type::from(&callable) {
+            Type::Function(_, ret) if matches!(*ret, Type::Float) => Float(0.0),
+            _ => Int(0),
+        };
+        for element in list.borrow().iter() {
+            let value = call_callback!(ctx, "sum_by", &callable, &[element])?;
+            sum = match Value::promote(&sum, &value) {
+                Some(Promoted::Int(a, b)) => match a.checked_add(b) {
+                    Some(sum) => Int(sum),
+                    None => return Err(RuntimeError::IntegerOverflow(sylt_common::Op::Add, vec![Int(a), Int(b)])),
+                },
+                Some(Promoted::Float(a, b)) => Float(a + b),
+                None => {
+                    return Err(RuntimeError::ExternError(
+                        "sum_by".to_string(),
+                        format!("Can only sum numbers, but got {}", value),
+                    ))
+                }
+            };
+        }
+        Ok(sum)
+    }
+);
+
+/// Compares two keys for `min_by` and `max_by`. Numbers are promoted like
+/// they are for `<`.
+fn compare_keys(a: &Value, b: &Value) -> Option<std::cmp::Ordering> {
+    use sylt_common::value::Promoted;
+    match (Value::promote(a, b), a, b) {
+        (Some(Promoted::Int(a, b)), _, _) => Some(a.cmp(&b)),
+        (Some(Promoted::Float(a, b)), _, _) => a.partial_cmp(&b),
+        (None, Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (None, Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+/// Picks the element whose key compares as `wanted` to all others, the first
+/// one wins when several keys are equal.
+fn pick_by(list: &[Value], keys: &[Value], wanted: std::cmp::Ordering) -> Result<Value, std::string::String> {
+    let mut best: Option<usize> = None;
+    for (i, key) in keys.iter().enumerate() {
+        best = match best {
+            Some(b) => match compare_keys(key, &keys[b]) {
+                Some(ordering) if ordering == wanted => Some(i),
+                Some(_) => Some(b),
+                None => return Err(format!("Cannot compare the keys {} and {}", key, keys[b])),
+            },
+            None => Some(i),
+        };
+    }
+    Ok(best.map(|i| list[i].clone()).unwrap_or(Value::Nil))
+}
+
+/// The shared body of `min_by` and `max_by`, `$name` is used in the errors.
+macro_rules! extreme_by {
+    ($ctx:expr, $name:literal, $list:expr, $callable:expr, $wanted:expr) => {{
+        let list = $list.borrow().clone();
+        let callable = $callable.clone();
+        let mut keys = Vec::new();
+        for element in list.iter() {
+            keys.push(call_callback!($ctx, $name, &callable, &[element])?);
+        }
+        pick_by(&list, &keys, $wanted).map_err(|msg| RuntimeError::ExternError($name.to_string(), msg))
+    }};
+}
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    min_by,
+    ? "Returns the element with the smallest key, the first one if there are ties. Returns nil if the list is empty",
+    -> "fn [#ITEM], fn #ITEM -> #KEY -> #ITEM?",
+    [List(list), callable] => {
+        extreme_by!(ctx, "min_by", list, callable, std::cmp::Ordering::Less)
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    max_by,
+    ? "Returns the element with the largest key, the first one if there are ties. Returns nil if the list is empty",
+    -> "fn [#ITEM], fn #ITEM -> #KEY -> #ITEM?",
+    [List(list), callable] => {
+        extreme_by!(ctx, "max_by", list, callable, std::cmp::Ordering::Greater)
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    zip,
+    ? "Pairs up the elements of two lists, stopping at the end of the shorter list",
+    -> "fn [#A], [#B] -> [(#A, #B)]",
+    [List(a), List(b)] => {
+        let zipped = a
+            .borrow()
+            .iter()
+            .zip(b.borrow().iter())
+            .map(|(a, b)| Tuple(Rc::new(vec![a.clone(), b.clone()])))
+            .collect();
+        Ok(List(Rc::new(RefCell::new(zipped))))
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    enumerate,
+    ? "Pairs up each element of a list with its index",
+    -> "fn [#ITEM] -> [(int, #ITEM)]",
+    [List(list)] => {
+        let enumerated = list
+            .borrow()
+            .iter()
+            .enumerate()
+            .map(|(i, element)| Tuple(Rc::new(vec![Int(i as i64), element.clone()])))
+            .collect();
+        Ok(List(Rc::new(RefCell::new(enumerated))))
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    memoize,
+    ? "Wraps a function so it only runs once for every tuple of arguments, the arguments have to be hashable",
+    -> "fn #F -> #F",
+    [function @ (Function(..) | ExternFunction(_))] => {
+        Ok(Memoized(Rc::new(function.clone()), Rc::new(RefCell::new(IndexMap::new()))))
+    },
+    [memoized @ Memoized(..)] => {
+        Ok(memoized.clone())
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    partial,
+    ? "Binds the first argument of a function, the rest are given when the returned function is called",
+    -> "(fn fn #A -> #R, #A -> fn -> #R) | (fn fn #A, #B -> #R, #A -> fn #B -> #R) | (fn fn #A, #B, #C -> #R, #A -> fn #B, #C -> #R)",
+    [function @ (Function(..) | ExternFunction(_) | Memoized(..) | Partial(..)), bound] => {
+        Ok(Partial(Rc::new(function.clone()), Rc::new(bound.clone())))
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    apply,
+    ? "Calls the function with the elements of the list as arguments",
+    -> "(fn fn -> #R, [#X] -> #R) | (fn fn #X -> #R, [#X] -> #R) | (fn fn #X, #X -> #R, [#X] -> #R) | (fn fn #X, #X, #X -> #R, [#X] -> #R)",
+    [callable, List(list)] => {
+        let args = list.borrow().clone();
+        if let Type::Function(params, _) = Type::from(callable) {
+            if params.len() != args.len() {
+                return Err(RuntimeError::ArgumentCount(params.len(), args.len()));
+            }
+        }
+        let args = args.iter().collect::<Vec<_>>();
+        let callable = callable.clone();
+        call_callback!(ctx, "apply", &callable, &args)
+    }
+);
+
 sylt_macro::extern_function!(
     "sylt_std::sylt",
     args,
     ? "Returns the args parsed into a dict, split on =",
     -> "fn -> {str:str}",
     values => {
-        let mut args = HashMap::new();
+        let mut args = IndexMap::new();
         args.insert(Value::from("prog"), Value::from(ctx.machine.args()[0].as_str()));
 
         for arg in ctx.machine.args().iter().skip(1) {
@@ -156,6 +456,9 @@ sylt_macro::extern_function!(
     [List(ls), v] => {
         ls.borrow_mut().push(v.clone());
         Ok(Nil)
+    },
+    [Range(_), ..] => {
+        Err(RuntimeError::ImmutableRange("push".to_string()))
     }
 );
 
@@ -168,6 +471,48 @@ sylt_macro::extern_function!(
         // NOTE(ed): Deliberately no type checking.
         ls.borrow_mut().insert(0, v.clone());
         Ok(Value::Nil)
+    },
+    [Range(_), ..] => {
+        Err(RuntimeError::ImmutableRange("prepend".to_string()))
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    swap,
+    ? "Swaps two elements of the list in place, crashes if an index is out of bounds",
+    -> "fn [#ITEM], int, int -> void",
+    [List(ls), Int(i), Int(j)] => {
+        let len = ls.borrow().len();
+        for slot in [*i as usize, *j as usize] {
+            if slot >= len {
+                return Err(RuntimeError::IndexOutOfBounds(List(Rc::clone(ls)), len, slot));
+            }
+        }
+        ls.borrow_mut().swap(*i as usize, *j as usize);
+        Ok(Nil)
+    },
+    [Range(_), ..] => {
+        Err(RuntimeError::ImmutableRange("swap".to_string()))
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    set,
+    ? "Replaces the element at the index in place, crashes if the index is out of bounds",
+    -> "fn [#ITEM], int, #ITEM -> void",
+    [List(ls), Int(i), v] => {
+        let len = ls.borrow().len();
+        let slot = *i as usize;
+        if slot >= len {
+            return Err(RuntimeError::IndexOutOfBounds(List(Rc::clone(ls)), len, slot));
+        }
+        ls.borrow_mut()[slot] = v.clone();
+        Ok(Nil)
+    },
+    [Range(_), ..] => {
+        Err(RuntimeError::ImmutableRange("set".to_string()))
     }
 );
 
@@ -192,11 +537,207 @@ sylt_macro::extern_function!(
     [List(ls)] => {
         Ok(Int(ls.borrow().len() as i64))
     },
+    [Range(range)] => {
+        Ok(Int(range.len().min(i64::MAX as usize) as i64))
+    },
     [Dict(ls)] => {
         Ok(Int(ls.borrow().len() as i64))
     }
 );
 
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    get,
+    ? "Looks up the key in the dict, gives nil if it isn't there",
+    -> "fn {#KEY: #VALUE}, #KEY -> #VALUE?",
+    [Dict(dict), key] => {
+        if !key.is_hashable() {
+            return Err(RuntimeError::ExternError(
+                "get".to_string(),
+                format!("Cannot use {:?} as a key", key),
+            ));
+        }
+        Ok(dict.borrow().get(key).cloned().unwrap_or(Nil))
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    contains_key,
+    ? "Checks if the key is in the dict",
+    -> "fn {#KEY: #VALUE}, #KEY -> bool",
+    [Dict(dict), key] => {
+        if !key.is_hashable() {
+            return Err(RuntimeError::ExternError(
+                "contains_key".to_string(),
+                format!("Cannot use {:?} as a key", key),
+            ));
+        }
+        Ok(Bool(dict.borrow().contains_key(key)))
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    to_set,
+    ? "Makes a set of the elements in a list, skipping duplicates",
+    -> "fn [#ITEM] -> {#ITEM}",
+    [List(list)] => {
+        let list = list.borrow();
+        if let Some(element) = list.iter().find(|element| !element.is_hashable()) {
+            return Err(RuntimeError::ExternError(
+                "to_set".to_string(),
+                format!("Cannot put {:?} in a set", element),
+            ));
+        }
+        Ok(Set(Rc::new(RefCell::new(list.iter().cloned().collect()))))
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    to_list,
+    ? "Makes a list of the elements in a set, in the order they were added",
+    -> "fn {#ITEM} -> [#ITEM]",
+    [Set(set)] => {
+        Ok(List(Rc::new(RefCell::new(set.borrow().iter().cloned().collect()))))
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    frequencies,
+    ? "Counts how many times each element occurs in a list",
+    -> "fn [#ITEM] -> {#ITEM: int}",
+    [List(list)] => {
+        let mut counts = IndexMap::new();
+        for element in list.borrow().iter() {
+            if !element.is_hashable() {
+                return Err(RuntimeError::ExternError(
+                    "frequencies".to_string(),
+                    format!("Cannot count {:?}, since it can't be a key in a dict", element),
+                ));
+            }
+            *counts.entry(element.clone()).or_insert(0) += 1;
+        }
+        let counts = counts.into_iter().map(|(element, count)| (element, Int(count))).collect();
+        Ok(Dict(Rc::new(RefCell::new(counts))))
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    value_in,
+    ? "Checks if the value is one of the values in the dict, use `in` to check the keys",
+    -> "fn #VALUE, {#KEY: #VALUE} -> bool",
+    [value, Dict(dict)] => {
+        Ok(Bool(dict.borrow().values().any(|v| v == value)))
+    }
+);
+
+/// The keys of the dict in sorted order, or an error if two of them can't be
+/// compared.
+fn sorted_keys_of(dict: &IndexMap<Value, Value>) -> Result<Vec<Value>, std::string::String> {
+    let mut keys: Vec<_> = dict.keys().cloned().collect();
+    if let Some(first) = keys.first() {
+        for key in keys.iter() {
+            if compare_keys(key, first).is_none() {
+                return Err(format!("Cannot compare the keys {} and {}", key, first));
+            }
+        }
+    }
+    keys.sort_by(|a, b| compare_keys(a, b).unwrap_or(std::cmp::Ordering::Equal));
+    Ok(keys)
+}
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    sorted_keys,
+    ? "The keys of the dict in sorted order",
+    -> "fn {#KEY: #VALUE} -> [#KEY]",
+    [Dict(dict)] => {
+        match sorted_keys_of(&dict.borrow()) {
+            Ok(keys) => Ok(List(Rc::new(RefCell::new(keys)))),
+            Err(err) => Err(RuntimeError::ExternError("sorted_keys".to_string(), err)),
+        }
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    sorted_items,
+    ? "The keys and values of the dict as tuples, sorted by key",
+    -> "fn {#KEY: #VALUE} -> [(#KEY, #VALUE)]",
+    [Dict(dict)] => {
+        let dict = dict.borrow();
+        match sorted_keys_of(&dict) {
+            Ok(keys) => {
+                let items = keys
+                    .into_iter()
+                    .map(|key| {
+                        let value = dict[&key].clone();
+                        Tuple(Rc::new(vec![key, value]))
+                    })
+                    .collect();
+                Ok(List(Rc::new(RefCell::new(items))))
+            }
+            Err(err) => Err(RuntimeError::ExternError("sorted_items".to_string(), err)),
+        }
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    range,
+    ? "The integers from the first argument up to, but not including, the second",
+    -> "fn int, int -> [int]",
+    [Int(start), Int(end)] => {
+        Ok(Range(sylt_common::value::Range { start: *start, end: *end, step: 1, inclusive: false }))
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    range_inclusive,
+    ? "The integers from the first argument up to and including the second",
+    -> "fn int, int -> [int]",
+    [Int(start), Int(end)] => {
+        Ok(Range(sylt_common::value::Range { start: *start, end: *end, step: 1, inclusive: true }))
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    range_step,
+    ? "The integers from the first argument up to, but not including, the second, stepping by the third",
+    -> "fn int, int, int -> [int]",
+    [Int(_), Int(_), Int(0)] => {
+        Err(RuntimeError::ExternError(
+            "range_step".to_string(),
+            "Cannot step a range by 0".to_string(),
+        ))
+    },
+    [Int(start), Int(end), Int(step)] => {
+        Ok(Range(sylt_common::value::Range { start: *start, end: *end, step: *step, inclusive: false }))
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    range_list,
+    ? "Like `range_step`, but builds the list of integers right away",
+    -> "fn int, int, int -> [int]",
+    [Int(_), Int(_), Int(0)] => {
+        Err(RuntimeError::ExternError(
+            "range_list".to_string(),
+            "Cannot step a range by 0".to_string(),
+        ))
+    },
+    [Int(start), Int(end), Int(step)] => {
+        Ok(sylt_common::value::Range { start: *start, end: *end, step: *step, inclusive: false }.to_list())
+    }
+);
+
 sylt_macro::extern_function!(
     "sylt_std::sylt",
     sin,
@@ -237,12 +778,55 @@ sylt_macro::extern_function!(
     }
 );
 
+/// Converts a float without decimals to an int. Fails for nan, inf and
+/// floats outside of the range of an int, instead of clamping them like `as`.
+fn rounded_to_int(name: &str, t: f64) -> Result<Value, RuntimeError> {
+    // The smallest int is -2^63, and 2^63 is right after the largest.
+    if t >= i64::MIN as f64 && t < -(i64::MIN as f64) {
+        Ok(Value::Int(t as i64))
+    } else {
+        Err(RuntimeError::ExternError(name.to_string(), format!("Cannot convert {} to an int", t)))
+    }
+}
+
 sylt_macro::extern_function!(
     "sylt_std::sylt",
     floor,
-    ? "Rounds a float down (towards -inf)",
+    ? "Rounds a float down (towards -inf). Fails for nan, inf and floats too big for an int",
+    -> "fn float -> int",
+    [Float(t)] => { rounded_to_int("floor", t.floor()) }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    ceil,
+    ? "Rounds a float up (towards +inf). Fails for nan, inf and floats too big for an int",
     -> "fn float -> int",
-    [Float(t)] => { Ok(Int(t.floor() as i64)) }
+    [Float(t)] => { rounded_to_int("ceil", t.ceil()) }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    round,
+    ? "Rounds a float to the closest int, halfway cases away from zero. Fails for nan, inf and floats too big for an int",
+    -> "fn float -> int",
+    [Float(t)] => { rounded_to_int("round", t.round()) }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    to_float,
+    ? "Converts an int to a float",
+    -> "fn int -> float",
+    [Int(t)] => { Ok(Float(*t as f64)) }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    to_int,
+    ? "Converts a float to an int by dropping the decimals (rounding towards zero). Fails for nan, inf and floats too big for an int",
+    -> "fn float -> int",
+    [Float(t)] => { rounded_to_int("to_int", t.trunc()) }
 );
 
 sylt_macro::extern_function!(
@@ -255,17 +839,37 @@ sylt_macro::extern_function!(
         let c = match chars.next() {
             Some(c) => c,
             // TODO(gu): Actually what went wrong
-            None => return Err(RuntimeError::ExternTypeMismatch("as_char".to_string(), vec![Type::String])),
+            None => return Err(RuntimeError::ExternTypeMismatch("as_char".to_string(), vec![Type::String], ctx.call_site)),
         };
         if chars.next().is_none() {
             Ok(Int(c as i64))
         } else {
             // TODO(gu): Actually what went wrong
-            Err(RuntimeError::ExternTypeMismatch("as_char".to_string(), vec![Type::String]))
+            Err(RuntimeError::ExternTypeMismatch("as_char".to_string(), vec![Type::String], ctx.call_site))
         }
     }
 );
 
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    parse_int,
+    ? "Parses an int from a string, ignoring surrounding whitespace. Returns nil if it isn't an int",
+    -> "fn str -> int?",
+    [Value::String(s)] => {
+        Ok(s.trim().parse().map(Int).unwrap_or(Nil))
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    parse_float,
+    ? "Parses a float from a string, ignoring surrounding whitespace. Returns nil if it isn't a number",
+    -> "fn str -> float?",
+    [Value::String(s)] => {
+        Ok(s.trim().parse().map(Float).unwrap_or(Nil))
+    }
+);
+
 sylt_macro::extern_function!(
     "sylt_std::sylt",
     as_chars,
@@ -292,6 +896,38 @@ sylt_macro::extern_function!(
     },
 );
 
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    to_bytes,
+    ? "Encodes a string as UTF-8, one int between 0 and 255 for each byte",
+    -> "fn str -> [int]",
+    [Value::String(s)] => {
+        let bytes = s.bytes().map(|b| Int(b as i64)).collect();
+        Ok(List(Rc::new(RefCell::new(bytes))))
+    },
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    from_bytes,
+    ? "Decodes UTF-8 bytes into a string, returns nil if they aren't valid UTF-8",
+    -> "fn [int] -> str?",
+    [List(list)] => {
+        let bytes: Option<Vec<u8>> = list
+            .borrow()
+            .iter()
+            .map(|b| match b {
+                Int(b) if (0..=255).contains(b) => Some(*b as u8),
+                _ => None,
+            })
+            .collect();
+        Ok(bytes
+            .and_then(|bytes| std::string::String::from_utf8(bytes).ok())
+            .map(|s| Value::String(Rc::new(s)))
+            .unwrap_or(Nil))
+    },
+);
+
 sylt_macro::extern_function!(
     "sylt_std::sylt",
     sqrt,
@@ -351,6 +987,30 @@ sylt_macro::extern_function!(
     [Int(x), Int(y)] => { Ok(Int(x.rem_euclid(*y))) }
 );
 
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    wrapping_add,
+    ? "Adds the integers, wrapping around instead of failing on overflow",
+    -> "fn int, int -> int",
+    [Int(x), Int(y)] => { Ok(Int(x.wrapping_add(*y))) }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    wrapping_sub,
+    ? "Subtracts the integers, wrapping around instead of failing on overflow",
+    -> "fn int, int -> int",
+    [Int(x), Int(y)] => { Ok(Int(x.wrapping_sub(*y))) }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    wrapping_mul,
+    ? "Multiplies the integers, wrapping around instead of failing on overflow",
+    -> "fn int, int -> int",
+    [Int(x), Int(y)] => { Ok(Int(x.wrapping_mul(*y))) }
+);
+
 sylt_macro::extern_function!(
     "sylt_std::sylt",
     pow,
@@ -466,6 +1126,19 @@ sylt_macro::extern_function!(
     -> "fn [#ITEM] -> #ITEM?",
     [List(ls)] => {
         Ok(ls.borrow_mut().pop().unwrap_or(Nil))
+    },
+    [Range(_), ..] => {
+        Err(RuntimeError::ImmutableRange("pop".to_string()))
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    first,
+    ? "Returns the first element in a list, or nil if it's empty",
+    -> "fn [#ITEM] -> #ITEM?",
+    [List(ls)] => {
+        Ok(ls.borrow().first().cloned().unwrap_or(Nil))
     }
 );
 
@@ -479,6 +1152,17 @@ sylt_macro::extern_function!(
     }
 );
 
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    rest,
+    ? "Returns a new list with all but the first element, which is empty if the list is",
+    -> "fn [#ITEM] -> [#ITEM]",
+    [List(ls)] => {
+        let rest = ls.borrow().iter().skip(1).cloned().collect();
+        Ok(List(Rc::new(RefCell::new(rest))))
+    }
+);
+
 
 sylt_macro::extern_function!(
     "sylt_std::sylt",
@@ -491,6 +1175,187 @@ sylt_macro::extern_function!(
     },
 );
 
+fn deep_copy(value: &Value, copies: &mut HashMap<UniqueId, Value>) -> Value {
+    use Value::*;
+    if let Some(copy) = copies.get(&value.unique_id()) {
+        return copy.clone();
+    }
+    // Containers are registered before their elements are copied,
+    // so cycles point back at the copy instead of recursing forever.
+    match value {
+        Blob(fields) => {
+            let copy = Rc::new(RefCell::new(HashMap::new()));
+            copies.insert(value.unique_id(), Blob(Rc::clone(&copy)));
+            for (name, field) in fields.borrow().iter() {
+                let field = deep_copy(field, copies);
+                copy.borrow_mut().insert(name.clone(), field);
+            }
+            Blob(copy)
+        }
+        List(list) => {
+            let copy = Rc::new(RefCell::new(Vec::new()));
+            copies.insert(value.unique_id(), List(Rc::clone(&copy)));
+            for element in list.borrow().iter() {
+                let element = deep_copy(element, copies);
+                copy.borrow_mut().push(element);
+            }
+            List(copy)
+        }
+        Set(set) => {
+            let copy = Rc::new(RefCell::new(IndexSet::new()));
+            copies.insert(value.unique_id(), Set(Rc::clone(&copy)));
+            for element in set.borrow().iter() {
+                let element = deep_copy(element, copies);
+                copy.borrow_mut().insert(element);
+            }
+            Set(copy)
+        }
+        Dict(dict) => {
+            let copy = Rc::new(RefCell::new(IndexMap::new()));
+            copies.insert(value.unique_id(), Dict(Rc::clone(&copy)));
+            for (key, element) in dict.borrow().iter() {
+                let key = deep_copy(key, copies);
+                let element = deep_copy(element, copies);
+                copy.borrow_mut().insert(key, element);
+            }
+            Dict(copy)
+        }
+        // Tuples are immutable, so they can only be part of a cycle through
+        // one of the containers above - which are registered.
+        Tuple(tuple) => {
+            let copy = Tuple(Rc::new(tuple.iter().map(|v| deep_copy(v, copies)).collect()));
+            copies.insert(value.unique_id(), copy.clone());
+            copy
+        }
+        value => value.clone(),
+    }
+}
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    copy,
+    ? "Creates a deep copy of a value, mutating the copy doesn't change the original",
+    -> "fn #X -> #X",
+    [value] => {
+        Ok(deep_copy(value, &mut HashMap::new()))
+    }
+);
+
+/// Merges `over` into `base`. Dicts in both merge recursively, everything
+/// else - lists included - is replaced by the value in `over`. Lists aren't
+/// concatenated since there's no way to tell which elements are overrides.
+fn deep_merge(
+    base: &IndexMap<Value, Value>,
+    over: &IndexMap<Value, Value>,
+    ids: (UniqueId, UniqueId),
+    merged: &mut HashMap<(UniqueId, UniqueId), Value>,
+    copies: &mut HashMap<UniqueId, Value>,
+) -> Value {
+    if let Some(merge) = merged.get(&ids) {
+        return merge.clone();
+    }
+    // Like deep_copy, the merge is registered before recursing so cycles
+    // end up pointing at it.
+    let merge = Rc::new(RefCell::new(IndexMap::new()));
+    merged.insert(ids, Value::Dict(Rc::clone(&merge)));
+    for (key, value) in base.iter() {
+        let value = deep_copy(value, copies);
+        merge.borrow_mut().insert(deep_copy(key, copies), value);
+    }
+    for (key, value) in over.iter() {
+        let value = match (base.get(key), value) {
+            (Some(a @ Value::Dict(inner_base)), b @ Value::Dict(inner_over)) => {
+                let ids = (a.unique_id(), b.unique_id());
+                deep_merge(&inner_base.borrow(), &inner_over.borrow(), ids, merged, copies)
+            }
+            (_, value) => deep_copy(value, copies),
+        };
+        merge.borrow_mut().insert(deep_copy(key, copies), value);
+    }
+    Value::Dict(merge)
+}
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    merge,
+    ? "Merges two dicts into a new dict, nested dicts are merged and otherwise the values in the second dict win",
+    -> "fn {#KEY: #VALUE}, {#KEY: #VALUE} -> {#KEY: #VALUE}",
+    [a @ Dict(base), b @ Dict(over)] => {
+        let ids = (a.unique_id(), b.unique_id());
+        Ok(deep_merge(&base.borrow(), &over.borrow(), ids, &mut HashMap::new(), &mut HashMap::new()))
+    }
+);
+
+fn floats_within(a: f64, b: f64, epsilon: f64) -> bool {
+    // Infinities of the same sign are equal, but their difference is NaN.
+    a == b || (a - b).abs() <= epsilon
+}
+
+/// Compares like `==`, except floats only have to be within `epsilon` of
+/// each other. Lists, tuples and dicts are compared element by element,
+/// everything else has to be exactly equal. Pairs of containers that are
+/// already being compared are assumed to be equal, which stops cycles.
+fn approx_equal_within(a: &Value, b: &Value, epsilon: f64, seen: &mut HashSet<(UniqueId, UniqueId)>) -> bool {
+    use Value::*;
+    match (a, b) {
+        (Float(a), Float(b)) => floats_within(*a, *b, epsilon),
+        (Tuple(x), Tuple(y)) => {
+            x.len() == y.len() && x.iter().zip(y.iter()).all(|(x, y)| approx_equal_within(x, y, epsilon, seen))
+        }
+        (List(x), List(y)) => {
+            if !seen.insert((a.unique_id(), b.unique_id())) {
+                return true;
+            }
+            let (x, y) = (x.borrow(), y.borrow());
+            x.len() == y.len() && x.iter().zip(y.iter()).all(|(x, y)| approx_equal_within(x, y, epsilon, seen))
+        }
+        (Dict(x), Dict(y)) => {
+            if !seen.insert((a.unique_id(), b.unique_id())) {
+                return true;
+            }
+            let (x, y) = (x.borrow(), y.borrow());
+            x.len() == y.len()
+                && x.iter().all(|(key, x)| match y.get(key) {
+                    Some(y) => approx_equal_within(x, y, epsilon, seen),
+                    None => false,
+                })
+        }
+        (a, b) => a == b,
+    }
+}
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    approx_equal,
+    ? "Checks if two floats are at most epsilon apart",
+    -> "fn float, float, float -> bool",
+    [Float(a), Float(b), Float(epsilon)] => {
+        if *epsilon < 0.0 {
+            return Err(RuntimeError::ExternError(
+                "approx_equal".to_string(),
+                format!("The tolerance can't be negative, got {}", epsilon),
+            ));
+        }
+        Ok(Bool(floats_within(*a, *b, *epsilon)))
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    deep_approx_equal,
+    ? "Like `==`, but floats anywhere in the lists, tuples and dicts only have to be at most epsilon apart",
+    -> "fn #X, #X, float -> bool",
+    [a, b, Float(epsilon)] => {
+        if *epsilon < 0.0 {
+            return Err(RuntimeError::ExternError(
+                "deep_approx_equal".to_string(),
+                format!("The tolerance can't be negative, got {}", epsilon),
+            ));
+        }
+        Ok(Bool(approx_equal_within(a, b, *epsilon, &mut HashSet::new())))
+    }
+);
+
 sylt_macro::extern_function!(
     "sylt_std::sylt",
     as_str,
@@ -499,6 +1364,234 @@ sylt_macro::extern_function!(
     [v] => { Ok(Value::String(Rc::new(v.to_string()))) }
 );
 
+/// Reads one value, written the way `as_str` writes values inside
+/// containers, from the start of the tokens.
+fn read_value(tokens: &mut std::iter::Peekable<std::slice::Iter<Token>>) -> Option<Value> {
+    use Value::*;
+    macro_rules! read_until {
+        ($close:pat, $values:ident, $read:expr) => {
+            if !matches!(tokens.peek(), Some($close)) {
+                loop {
+                    $values.push($read);
+                    match tokens.next()? {
+                        Token::Comma => {}
+                        $close => break,
+                        _ => return None,
+                    }
+                }
+            } else {
+                tokens.next();
+            }
+        };
+    }
+
+    Some(match tokens.next()? {
+        Token::Int(i) => Int(*i),
+        Token::Float(f) => Float(*f),
+        Token::Identifier(name) if name == "inf" => Float(f64::INFINITY),
+        Token::Identifier(name) if name == "NaN" => Float(f64::NAN),
+        Token::Minus => match read_value(tokens)? {
+            Int(i) => Int(i.checked_neg()?),
+            Float(f) => Float(-f),
+            _ => return None,
+        },
+        Token::Bool(b) => Bool(*b),
+        Token::String(s) => Value::String(Rc::new(s.clone())),
+        Token::Nil => Nil,
+        Token::LeftBracket => {
+            let mut list = Vec::new();
+            read_until!(Token::RightBracket, list, read_value(tokens)?);
+            List(Rc::new(RefCell::new(list)))
+        }
+        Token::LeftParen => {
+            // One element tuples are written with a trailing comma, `(1,)`.
+            let mut tuple = Vec::new();
+            while !matches!(tokens.peek(), Some(Token::RightParen)) {
+                tuple.push(read_value(tokens)?);
+                match tokens.peek()? {
+                    Token::Comma => { tokens.next(); }
+                    Token::RightParen => {}
+                    _ => return None,
+                }
+            }
+            tokens.next();
+            Tuple(Rc::new(tuple))
+        }
+        Token::LeftBrace => match tokens.peek()? {
+            // An empty dict is written as `{:}`, an empty set as `{}`.
+            Token::Colon => {
+                tokens.next();
+                match tokens.next()? {
+                    Token::RightBrace => Dict(Rc::new(RefCell::new(IndexMap::new()))),
+                    _ => return None,
+                }
+            }
+            Token::RightBrace => {
+                tokens.next();
+                Set(Rc::new(RefCell::new(IndexSet::new())))
+            }
+            _ => {
+                let first = read_value(tokens)?;
+                if matches!(tokens.peek()?, Token::Colon) {
+                    tokens.next();
+                    let mut items = vec![(first, read_value(tokens)?)];
+                    match tokens.next()? {
+                        Token::Comma => read_until!(Token::RightBrace, items, {
+                            let key = read_value(tokens)?;
+                            match tokens.next()? {
+                                Token::Colon => (key, read_value(tokens)?),
+                                _ => return None,
+                            }
+                        }),
+                        Token::RightBrace => {}
+                        _ => return None,
+                    }
+                    if !items.iter().all(|(key, _)| key.is_hashable()) {
+                        return None;
+                    }
+                    Dict(Rc::new(RefCell::new(items.into_iter().collect())))
+                } else {
+                    let mut set = vec![first];
+                    match tokens.next()? {
+                        Token::Comma => read_until!(Token::RightBrace, set, read_value(tokens)?),
+                        Token::RightBrace => {}
+                        _ => return None,
+                    }
+                    if !set.iter().all(Value::is_hashable) {
+                        return None;
+                    }
+                    Set(Rc::new(RefCell::new(set.into_iter().collect())))
+                }
+            }
+        },
+        _ => return None,
+    })
+}
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    parse_value,
+    ? "Reads a value written like `as_str` writes it back, gives nil if it can't. Strings have to be quoted, and functions and blobs can't be read. The result needs a type, like `xs: [int]? = parse_value(s)`, which isn't checked when the program runs",
+    -> "fn str -> #OUT?",
+    [Value::String(text)] => {
+        let tokens: Vec<_> = sylt_tokenizer::string_to_tokens(text)
+            .into_iter()
+            .map(|placed| placed.token)
+            .filter(|token| !matches!(token, Token::Newline))
+            .collect();
+        let mut tokens = tokens.iter().peekable();
+        Ok(match read_value(&mut tokens) {
+            Some(value) if tokens.next().is_none() => value,
+            _ => Nil,
+        })
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    format,
+    ? "Replaces each `{}` in the template with the next value in the list, as `as_str` would write it. `{{` and `}}` are written as `{` and `}`",
+    -> "fn str, [#X] -> str",
+    [Value::String(template), List(values)] => {
+        let values = values.borrow();
+        let given = values.len();
+        let mut values = values.iter();
+        let mut formatted = std::string::String::new();
+        let mut placeholders = 0;
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('{', Some('{')) | ('}', Some('}')) => {
+                    chars.next();
+                    formatted.push(c);
+                }
+                ('{', Some('}')) => {
+                    chars.next();
+                    placeholders += 1;
+                    if let Some(value) = values.next() {
+                        formatted.push_str(&value.to_string());
+                    }
+                }
+                ('{', _) | ('}', _) => {
+                    return Err(RuntimeError::ExternError(
+                        "format".to_string(),
+                        format!("Unmatched '{}' in {:?}, write '{}{}' for the character", c, template, c, c),
+                    ));
+                }
+                _ => formatted.push(c),
+            }
+        }
+        if placeholders != given {
+            return Err(RuntimeError::ExternError(
+                "format".to_string(),
+                format!("The template has {} placeholder(s) but {} value(s) were given", placeholders, given),
+            ));
+        }
+        Ok(Value::String(Rc::new(formatted)))
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    type_of,
+    ? "Gives the type of the value, as it is when the program runs",
+    -> "fn #X -> type",
+    [v] => { Ok(Value::Ty(Type::from(v))) }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    assert_type,
+    ? "Gives back the value if it is of the type, like `is` does, and crashes otherwise",
+    -> "fn #X, type -> #X",
+    [v, Value::Ty(ty)] => {
+        let got = Type::from(v);
+        if ty.fits(&got).is_ok() {
+            Ok(v.clone())
+        } else {
+            Err(RuntimeError::TypeAssertion(ty.clone(), got))
+        }
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    split,
+    ? "Splits a string on every occurrence of the separator. An empty separator splits the string into its characters. An empty string gives a list with one empty string, or an empty list if the separator is empty too",
+    -> "fn str, str -> [str]",
+    [Value::String(s), Value::String(sep)] => {
+        let parts: Vec<Value> = if sep.is_empty() {
+            s.chars().map(|c| Value::String(Rc::new(c.to_string()))).collect()
+        } else {
+            s.split(sep.as_str()).map(Value::from).collect()
+        };
+        Ok(List(Rc::new(RefCell::new(parts))))
+    }
+);
+
+sylt_macro::extern_function!(
+    "sylt_std::sylt",
+    join,
+    ? "Joins a list of strings with the separator in between. An empty list gives an empty string",
+    -> "fn [str], str -> str",
+    [List(ls), Value::String(sep)] => {
+        let ls = ls.borrow();
+        let mut parts = Vec::new();
+        for part in ls.iter() {
+            match part {
+                Value::String(part) => parts.push(part.as_str()),
+                part => {
+                    return Err(RuntimeError::ExternError(
+                        "join".to_string(),
+                        format!("Can only join strings, got {}", part),
+                    ));
+                }
+            }
+        }
+        Ok(Value::String(Rc::new(parts.join(sep.as_str()))))
+    }
+);
+
 sylt_macro::extern_function!(
     "sylt_std::sylt",
     print,
@@ -1,8 +1,11 @@
-use logos::Logos;
+use logos::{Lexer, Logos};
+use serde::Serialize;
 
 #[derive(Logos, Debug, PartialEq, Clone)]
 pub enum Token {
+    /// Backticks make keywords usable as identifiers, e.g. `` `loop` ``.
     #[regex(r"[A-Za-z_][A-Za-z0-9_]*", |lex| lex.slice().to_string())]
+    #[regex(r"`[A-Za-z_][A-Za-z0-9_]*`", |lex| { let s = lex.slice(); s[1..s.len() - 1].to_string() })]
     Identifier(String),
 
     #[regex(r#""[^"]*""#, |lex| { let mut s = lex.slice().to_string(); s.remove(0); s.pop(); s })]
@@ -33,11 +36,17 @@ pub enum Token {
     In,
     #[token("loop")]
     Loop,
+    #[token("type_match")]
+    TypeMatch,
     #[token("blob")]
     Blob,
 
     #[token("ret")]
     Ret,
+    #[token("defer")]
+    Defer,
+    #[token("assert")]
+    Assert,
 
     #[token("+")]
     Plus,
@@ -47,6 +56,8 @@ pub enum Token {
     Star,
     #[token("/")]
     Slash,
+    #[token("%")]
+    Percent,
     #[token("+=")]
     PlusEqual,
     #[token("-=")]
@@ -55,6 +66,8 @@ pub enum Token {
     StarEqual,
     #[token("/=")]
     SlashEqual,
+    #[token("%=")]
+    PercentEqual,
 
     #[token("#")]
     Hash,
@@ -74,6 +87,7 @@ pub enum Token {
     #[token("<=>")]
     AssertEqual,
     #[token("<!>")]
+    #[token("unreachable")]
     Unreachable,
 
     #[token("(")]
@@ -105,6 +119,9 @@ pub enum Token {
     #[token("<=")]
     LessEqual,
 
+    #[token("const")]
+    Const,
+
     #[token("fn")]
     Fn,
 
@@ -122,6 +139,8 @@ pub enum Token {
     Pipe,
     #[token("'")]
     Prime,
+    #[token("@")]
+    At,
 
     #[token(",")]
     Comma,
@@ -131,6 +150,9 @@ pub enum Token {
     Arrow,
     #[token("\n")]
     Newline,
+    /// Separates statements on the same line, like a newline.
+    #[token(";")]
+    Semicolon,
 
     #[token("use")]
     Use,
@@ -146,14 +168,53 @@ pub enum Token {
     #[token(">>>>>>>")]
     GitConflictEnd,
 
-    #[regex(r"//[^\n]*", |lex| lex.slice()[2..].trim().to_string())]
-    Comment(String),
+    #[regex(r"//[^\n]*", |lex| Comment::Line(lex.slice()[2..].trim().to_string()))]
+    #[token("/*", |lex| block_comment(lex).map(Comment::Block))]
+    Comment(Comment),
 
     #[regex(r"[ \t\r]", logos::skip)]
     Whitespace,
 
     EOF,
 
+    /// A `/*` without a matching `*/`.
+    UnterminatedComment,
+
     #[error]
     Error,
 }
+
+/// A comment, and which kind of comment it was written as.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub enum Comment {
+    /// `// ...`, without the leading `//`.
+    Line(String),
+    /// `/* ... */`, kept whole - `/*` and `*/` included.
+    Block(String),
+}
+
+/// Eats the rest of a block comment after the `/*`. Block comments nest, so
+/// every `/*` needs its own `*/`.
+fn block_comment(lex: &mut Lexer<Token>) -> Option<String> {
+    let rest = lex.remainder().as_bytes();
+    let mut depth = 1;
+    let mut i = 0;
+    while i + 1 < rest.len() {
+        match &rest[i..i + 2] {
+            b"/*" => {
+                depth += 1;
+                i += 2;
+            }
+            b"*/" => {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    lex.bump(i);
+                    return Some(lex.slice().to_string());
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    None
+}
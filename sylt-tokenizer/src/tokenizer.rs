@@ -1,9 +1,10 @@
 use logos::Logos;
-pub use token::Token;
+use serde::Serialize;
+pub use token::{Comment, Token};
 
 mod token;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize)]
 /// A location in a file containing source code.
 pub struct Span {
     // TODO(ed): Do this more intelligent, so
@@ -38,6 +39,17 @@ pub struct PlacedToken {
     pub span: Span,
 }
 
+/// The `#!...` line that makes a file executable, if the file starts with one.
+/// It doesn't include the newline.
+pub fn shebang(content: &str) -> Option<&str> {
+    if content.starts_with("#!") {
+        content.lines().next()
+    } else {
+        None
+    }
+}
+
+/// Turns the source into tokens. A shebang on the first line is skipped.
 pub fn string_to_tokens(content: &str) -> Vec<PlacedToken> {
     // A list containing which char index a specific byte index is at.
     //
@@ -63,10 +75,18 @@ pub fn string_to_tokens(content: &str) -> Vec<PlacedToken> {
     let mut line = 1;
     let mut last_newline = 0;
 
-    Token::lexer(&content)
+    let skipped = shebang(content).map_or(0, str::len);
+    Token::lexer(&content[skipped..])
         .spanned()
         // Contains side-effects.
         .map(|(token, byte_range)| {
+            let byte_range = (byte_range.start + skipped)..(byte_range.end + skipped);
+            // The lexer only fails on a `/*` when it can't find the end of the comment.
+            let token = if token == Token::Error && &content[byte_range.clone()] == "/*" {
+                Token::UnterminatedComment
+            } else {
+                token
+            };
             let is_newline = token == Token::Newline;
             let col_start = char_at_byte[byte_range.start].unwrap() - last_newline;
             let col_end = char_at_byte[byte_range.end].unwrap() - last_newline;
@@ -82,14 +102,40 @@ pub fn string_to_tokens(content: &str) -> Vec<PlacedToken> {
                 last_newline = char_at_byte[byte_range.start].unwrap();
                 line += 1;
             }
+            // Block comments can span lines.
+            if let Token::Comment(_) = &placed_token.token {
+                for (pos, _) in content[byte_range.clone()].match_indices('\n') {
+                    last_newline = char_at_byte[byte_range.start + pos].unwrap();
+                    line += 1;
+                }
+            }
             placed_token
         })
         .collect()
 }
 
+/// Every comment in the source with where it is, e.g. for generating docs.
+pub fn string_to_comments(content: &str) -> Vec<(Comment, Span)> {
+    string_to_tokens(content)
+        .into_iter()
+        .filter_map(|placed| match placed.token {
+            Token::Comment(comment) => Some((comment, placed.span)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Names like `loop` are read as keywords, so they need backticks to be
+/// identifiers.
+pub fn is_reserved(name: &str) -> bool {
+    let looks_like_identifier = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    looks_like_identifier && !matches!(Token::lexer(name).next(), Some(Token::Identifier(_)))
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{Token, string_to_tokens};
+    use crate::{Comment, Span, Token, is_reserved, string_to_comments, string_to_tokens};
     use logos::Logos;
 
     fn lex(s: &str) -> Vec<Token> {
@@ -151,6 +197,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn semicolon_stays_on_line() {
+        assert_placed_eq!(
+            string_to_tokens("1; 2"),
+            (Token::Int(1),    1, 1..2),
+            (Token::Semicolon, 1, 2..3),
+            (Token::Int(2),    1, 4..5),
+        );
+    }
+
+    #[test]
+    fn shebang_is_skipped() {
+        assert_placed_eq!(
+            string_to_tokens("#!/usr/bin/env sylt\n1"),
+            (Token::Newline, 1, 20..21),
+            (Token::Int(1),  2, 1..2),
+        );
+        // Only the first line can be a shebang.
+        assert_eq!(string_to_tokens("1\n#!a").len(), 5);
+    }
+
     #[test]
     fn span_with_non_ascii() {
         // The 'ö' is an error but we want to check that its span is a single char.
@@ -197,6 +264,25 @@ mod tests {
         ident_cmp("__");
     }
 
+    #[test]
+    fn escaped_identifiers() {
+        assert_eq!(lex_once("`loop`"), Token::Identifier(String::from("loop")));
+        assert_eq!(lex_once("`fn`"), Token::Identifier(String::from("fn")));
+        assert_eq!(lex_once("`a`"), Token::Identifier(String::from("a")));
+        assert_ne!(lex_once("loop"), Token::Identifier(String::from("loop")));
+    }
+
+    #[test]
+    fn reserved() {
+        assert!(is_reserved("loop"));
+        assert!(is_reserved("true"));
+        assert!(is_reserved("nil"));
+        assert!(!is_reserved("a"));
+        assert!(!is_reserved("looping"));
+        assert!(!is_reserved("a/b"));
+        assert!(!is_reserved("1"));
+    }
+
     #[test]
     fn whitespace() {
         lex_once("1 ");
@@ -215,4 +301,42 @@ mod tests {
         assert_eq!(lex("1// a\n2").len(), 4);
         assert_eq!(lex("1\n// a\n2").len(), 5); // newline is also a token
     }
+
+    #[test]
+    fn block_comment() {
+        assert_eq!(lex_once("/* a */"), Token::Comment(Comment::Block("/* a */".to_string())));
+        assert_eq!(lex_once("/* a /* b */ c */"), Token::Comment(Comment::Block("/* a /* b */ c */".to_string())));
+        assert_eq!(lex("1 /* a */ 2").len(), 3);
+        assert_eq!(lex("/* a // b */ 1").len(), 2);
+        assert_eq!(lex_once("// /* a"), Token::Comment(Comment::Line("/* a".to_string())));
+    }
+
+    #[test]
+    fn unterminated_block_comment() {
+        let tokens = string_to_tokens("1 /* a /* b */");
+        assert_eq!(tokens[1].token, Token::UnterminatedComment);
+        assert_eq!(tokens[1].span, Span { line: 1, col_start: 3, col_end: 5 });
+    }
+
+    #[test]
+    fn block_comment_keeps_lines() {
+        assert_placed_eq!(
+            string_to_tokens("/* a\nb */ 1\n2"),
+            (Token::Comment(Comment::Block("/* a\nb */".to_string())), 1, 1..10),
+            (Token::Int(1), 2, 6..7),
+            (Token::Newline, 2, 7..8),
+            (Token::Int(2), 3, 1..2),
+        );
+    }
+
+    #[test]
+    fn comments_with_spans() {
+        assert_eq!(
+            string_to_comments("// a\n1 // b c\n2"),
+            vec![
+                (Comment::Line(String::from("a")), Span { line: 1, col_start: 1, col_end: 5 }),
+                (Comment::Line(String::from("b c")), Span { line: 2, col_start: 3, col_end: 9 }),
+            ]
+        );
+    }
 }
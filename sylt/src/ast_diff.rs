@@ -0,0 +1,227 @@
+//! Compares the structure of two files. Spans, comments and how the code is
+//! formatted don't matter, so a file and the formatted version of it have no
+//! differences.
+
+use std::fmt::{self, Write};
+use std::path::{Path, PathBuf};
+use sylt_common::Error;
+use sylt_parser::{Expression, ExpressionKind, Statement, StatementKind};
+
+use crate::formatter::format_statement;
+use crate::Args;
+
+/// How a statement differs between the files.
+enum Change<'a> {
+    Removed(&'a Statement),
+    Added(&'a Statement),
+    /// A function with the same name and signature in both files, whose
+    /// bodies differ.
+    Changed(&'a Statement, Vec<Change<'a>>),
+}
+
+/// Parses the two files and writes the statements that differ, as a diff
+/// from the first file to the second. Gives `None` if there are no
+/// differences.
+pub fn ast_diff_with_reader<R>(a: &Path, b: &Path, reader: R) -> Result<Option<String>, Vec<Error>>
+where
+    R: Fn(&Path) -> Result<String, Error>,
+{
+    let a_tree = sylt_parser::tree(a, &reader)?;
+    let b_tree = sylt_parser::tree(b, &reader)?;
+    let changes = diff(&a_tree.modules[0].1.statements, &b_tree.modules[0].1.statements);
+    if changes.is_empty() {
+        return Ok(None);
+    }
+
+    let mut out = String::new();
+    writeln!(out, "--- {}", a.display()).unwrap();
+    writeln!(out, "+++ {}", b.display()).unwrap();
+    write_changes(&mut out, 0, &changes).unwrap();
+    Ok(Some(out))
+}
+
+/// Like [ast_diff_with_reader], reading the two files given on the command line.
+pub fn ast_diff(args: &Args) -> Result<Option<String>, Vec<Error>> {
+    match args.args.as_slice() {
+        [a, b, ..] => ast_diff_with_reader(&PathBuf::from(a), &PathBuf::from(b), crate::read_file),
+        _ => Err(vec![Error::NoFileGiven]),
+    }
+}
+
+/// The body of a function definition, if it is one.
+fn function_body(statement: &Statement) -> Option<(&str, &Expression, &[Statement])> {
+    match &statement.kind {
+        StatementKind::Definition { ident, value, .. } => match &value.kind {
+            ExpressionKind::Function { body, .. } => match &body.kind {
+                StatementKind::Block { statements } => Some((ident.name.as_str(), value, statements)),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The statement as JSON without what is only formatting - spans, comments
+/// and empty lines - so statements can be compared by their structure.
+fn structure(statement: &Statement) -> serde_json::Value {
+    fn strip(value: &mut serde_json::Value) {
+        use serde_json::Value;
+        match value {
+            Value::Array(values) => {
+                values.retain(|v| v.get("kind") != Some(&Value::String("EmptyStatement".to_string())));
+                values.iter_mut().for_each(strip);
+            }
+            Value::Object(fields) => {
+                fields.remove("span");
+                fields.remove("comments");
+                fields.remove("trailing_comments");
+                fields.values_mut().for_each(strip);
+            }
+            _ => {}
+        }
+    }
+
+    let mut value = serde_json::to_value(statement).expect("Failed to serialize the statement");
+    strip(&mut value);
+    value
+}
+
+/// If only the bodies of the two functions differ.
+fn same_signature(a: &Expression, b: &Expression) -> bool {
+    match (&a.kind, &b.kind) {
+        (
+            ExpressionKind::Function { params: a_params, ret: a_ret, is_const: a_const, .. },
+            ExpressionKind::Function { params: b_params, ret: b_ret, is_const: b_const, .. },
+        ) => a_params == b_params && a_ret == b_ret && a_const == b_const,
+        _ => false,
+    }
+}
+
+/// Finds the statements that aren't in the longest common subsequence of the
+/// two lists. A function that is removed and added again with the same
+/// signature is diffed recursively.
+fn diff<'a>(a: &'a [Statement], b: &'a [Statement]) -> Vec<Change<'a>> {
+    let written = |s: &&Statement| !matches!(s.kind, StatementKind::EmptyStatement);
+    let a: Vec<_> = a.iter().filter(written).collect();
+    let b: Vec<_> = b.iter().filter(written).collect();
+    let a_structure: Vec<_> = a.iter().map(|s| structure(s)).collect();
+    let b_structure: Vec<_> = b.iter().map(|s| structure(s)).collect();
+
+    // common[i][j] is the length of the longest common subsequence of a[i..] and b[j..].
+    let mut common = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            common[i][j] = if a_structure[i] == b_structure[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a_structure[i] == b_structure[j] {
+            i += 1;
+            j += 1;
+            continue;
+        }
+        if let (Some((a_name, a_fn, a_body)), Some((b_name, b_fn, b_body))) =
+            (a.get(i).and_then(|s| function_body(s)), b.get(j).and_then(|s| function_body(s)))
+        {
+            if a_name == b_name && same_signature(a_fn, b_fn) {
+                changes.push(Change::Changed(a[i], diff(a_body, b_body)));
+                i += 1;
+                j += 1;
+                continue;
+            }
+        }
+        if j == b.len() || (i < a.len() && common[i + 1][j] >= common[i][j + 1]) {
+            changes.push(Change::Removed(a[i]));
+            i += 1;
+        } else {
+            changes.push(Change::Added(b[j]));
+            j += 1;
+        }
+    }
+    changes
+}
+
+fn write_prefixed(dest: &mut String, prefix: char, indent: u32, statement: &Statement) -> fmt::Result {
+    for line in format_statement(indent, statement.clone()).lines() {
+        writeln!(dest, "{}{}", prefix, line)?;
+    }
+    Ok(())
+}
+
+fn write_changes(dest: &mut String, indent: u32, changes: &[Change]) -> fmt::Result {
+    for change in changes.iter() {
+        match change {
+            Change::Removed(statement) => {
+                writeln!(dest, "@@ -{} @@", statement.span.line)?;
+                write_prefixed(dest, '-', indent, statement)?;
+            }
+            Change::Added(statement) => {
+                writeln!(dest, "@@ +{} @@", statement.span.line)?;
+                write_prefixed(dest, '+', indent, statement)?;
+            }
+            Change::Changed(statement, changes) => {
+                let (name, ..) = function_body(statement).unwrap();
+                writeln!(dest, "@@ in {} @@", name)?;
+                write_changes(dest, indent + 1, changes)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+    use sylt_common::Error;
+
+    const ORIGINAL: &str = "../tests/ast_diff/_original.sy";
+
+    #[test]
+    fn reformatted_has_no_changes() {
+        let mut args = crate::Args::default();
+        args.args = vec![ORIGINAL.to_string()];
+        let formatted = crate::formatter::format(&args).unwrap();
+        let reader = |path: &Path| -> Result<String, Error> {
+            if path == Path::new("formatted.sy") {
+                Ok(formatted.clone())
+            } else {
+                crate::read_file(path)
+            }
+        };
+        let diff = super::ast_diff_with_reader(&PathBuf::from(ORIGINAL), &PathBuf::from("formatted.sy"), reader);
+        assert_eq!(diff.unwrap(), None);
+    }
+
+    #[test]
+    fn modified_has_changes() {
+        let mut args = crate::Args::default();
+        args.args = vec![ORIGINAL.to_string(), "../tests/ast_diff/_modified.sy".to_string()];
+        let diff = super::ast_diff(&args).unwrap().unwrap();
+        let lines: Vec<_> = diff.lines().skip(2).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "@@ in sum_of_squares @@",
+                "@@ -7 @@",
+                "-    total := 0",
+                "@@ +7 @@",
+                "+    total := 1",
+                "@@ in start @@",
+                "@@ -15 @@",
+                "-    sum_of_squares([1, 2, 3]) <=> 14",
+                "@@ +15 @@",
+                "+    sum_of_squares([1, 2, 3]) <=> 15",
+                "@@ +16 @@",
+                "+    square(4) <=> 16",
+            ]
+        );
+    }
+}
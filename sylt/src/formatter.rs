@@ -1,10 +1,12 @@
+use std::collections::HashMap;
 use std::fmt::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use sylt_common::{Error, Type as RuntimeType};
 use sylt_parser::expression::ComparisonKind;
-use sylt_parser::statement::NameIdentifier;
+use sylt_parser::statement::{Annotation, NameIdentifier};
+use sylt_parser::visitor::{walk_statement, Visitor};
 use sylt_parser::{
-    Assignable, AssignableKind, Expression, ExpressionKind, Identifier, Module, Op, Statement,
+    Assignable, AssignableKind, Comment, Expression, ExpressionKind, Identifier, Module, Op, Statement,
     StatementKind, Type, TypeKind, VarKind,
 };
 
@@ -13,14 +15,14 @@ use crate::Args;
 static INDENT: &'static str = "    ";
 
 macro_rules! write_comma_separated {
-    ($dest:expr, $indent:expr, $write:expr, $values:expr) => {
+    ($dest:expr, $indent:expr, $canonical_unions:expr, $write:expr, $values:expr) => {
         let mut first = true;
         for value in $values {
             if !first {
                 write!($dest, ", ")?;
             }
             first = false;
-            $write($dest, $indent, value)?;
+            $write($dest, $indent, $canonical_unions, value)?;
         }
     };
 }
@@ -32,13 +34,36 @@ fn write_indents<W: Write>(dest: &mut W, indent: u32) -> fmt::Result {
     Ok(())
 }
 
+/// Names that are keywords, like `loop`, get their backticks back.
+fn escape_name(name: &str) -> String {
+    if sylt_tokenizer::is_reserved(name) {
+        format!("`{}`", name)
+    } else {
+        name.to_string()
+    }
+}
+
 fn write_identifier<W: Write>(dest: &mut W, identifier: Identifier) -> fmt::Result {
-    write!(dest, "{}", identifier.name)
+    write!(dest, "{}", escape_name(&identifier.name))
+}
+
+fn write_selected_name<W: Write>(
+    dest: &mut W,
+    (name, alias): (Identifier, Identifier),
+) -> fmt::Result {
+    let renamed = name.name != alias.name;
+    write_identifier(dest, name)?;
+    if renamed {
+        write!(dest, " as ")?;
+        write_identifier(dest, alias)?;
+    }
+    Ok(())
 }
 
 fn write_parameters<W: Write>(
     dest: &mut W,
     indent: u32,
+    canonical_unions: bool,
     parameters: Vec<(Identifier, Type)>,
 ) -> fmt::Result {
     let mut first = true;
@@ -49,16 +74,21 @@ fn write_parameters<W: Write>(
         first = false;
         write_identifier(dest, identifier)?;
         write!(dest, ": ")?;
-        write_type(dest, indent, ty)?;
+        write_type(dest, indent, canonical_unions, ty)?;
     }
     Ok(())
 }
 
+/// Writes the fields of a blob or blob instance. If `align` is set, the field
+/// names are padded to the longest one so the colons line up.
 fn write_blob_fields<T, W: Write>(
     dest: &mut W,
     indent: u32,
+    canonical_unions: bool,
     mut fields: Vec<(String, T)>,
-    sub_write: fn(&mut W, u32, T) -> fmt::Result,
+    sub_write: fn(&mut W, u32, bool, T) -> fmt::Result,
+    shorthand: fn(&str, &T) -> bool,
+    align: bool,
 ) -> fmt::Result {
     write!(dest, " {{")?;
     match fields.len() {
@@ -67,16 +97,29 @@ fn write_blob_fields<T, W: Write>(
         }
         1 => {
             let (field, expr) = fields.pop().unwrap();
-            write!(dest, " {}: ", field)?;
-            sub_write(dest, indent, expr)?;
+            if shorthand(&field, &expr) {
+                write!(dest, " {}", escape_name(&field))?;
+            } else {
+                write!(dest, " {}: ", escape_name(&field))?;
+                sub_write(dest, indent, canonical_unions, expr)?;
+            }
             write!(dest, " }}")?;
         }
         _ => {
             write!(dest, "\n")?;
+            let width = if align {
+                fields.iter().map(|(field, _)| escape_name(field).len()).max().unwrap_or(0)
+            } else {
+                0
+            };
             for (field, t) in fields {
                 write_indents(dest, indent)?;
-                write!(dest, "{}: ", field)?;
-                sub_write(dest, indent, t)?;
+                if shorthand(&field, &t) {
+                    write!(dest, "{}", escape_name(&field))?;
+                } else {
+                    write!(dest, "{:width$}: ", escape_name(&field), width = width)?;
+                    sub_write(dest, indent, canonical_unions, t)?;
+                }
                 write!(dest, ",\n")?;
             }
             write_indents(dest, indent - 1)?;
@@ -86,6 +129,14 @@ fn write_blob_fields<T, W: Write>(
     Ok(())
 }
 
+/// `x: x` in a blob instance can be written as just `x`.
+fn is_field_shorthand(field: &str, expr: &Expression) -> bool {
+    matches!(
+        &expr.kind,
+        ExpressionKind::Get(Assignable { kind: AssignableKind::Read(ident), .. }) if ident.name == field
+    )
+}
+
 fn simplify_type(ty: Type) -> Type {
     fn remove_duplicates(ty: Type, mut seen: Vec<Type>) -> Vec<Type> {
         match ty.kind {
@@ -133,30 +184,116 @@ fn simplify_type(ty: Type) -> Type {
     }
 }
 
-fn write_type<W: Write>(dest: &mut W, indent: u32, ty: Type) -> fmt::Result {
+fn is_void(ty: &Type) -> bool {
+    matches!(ty.kind, TypeKind::Resolved(RuntimeType::Void))
+}
+
+fn is_union_or_fn(ty: &Type) -> bool {
+    matches!(ty.kind, TypeKind::Union(..) | TypeKind::Fn(..))
+}
+
+/// Writes a part of a longer union, where `a?` isn't allowed.
+fn write_union_member<W: Write>(
+    dest: &mut W,
+    indent: u32,
+    canonical_unions: bool,
+    ty: Type,
+) -> fmt::Result {
+    match ty.kind {
+        TypeKind::Union(ty, rest) => {
+            write_union_member(dest, indent, canonical_unions, *ty)?;
+            write!(dest, " | ")?;
+            write_union_member(dest, indent, canonical_unions, *rest)
+        }
+        _ => write_type(dest, indent, canonical_unions, ty),
+    }
+}
+
+/// Writes the members of a union sorted, so the same union is always written
+/// the same way. `void` is written last, as `?`.
+fn write_canonical_union<W: Write>(
+    dest: &mut W,
+    indent: u32,
+    canonical_unions: bool,
+    ty: Type,
+) -> fmt::Result {
+    fn flatten(ty: Type, members: &mut Vec<Type>) {
+        match ty.kind {
+            TypeKind::Union(ty, rest) => {
+                flatten(*ty, members);
+                flatten(*rest, members);
+            }
+            _ => members.push(ty),
+        }
+    }
+
+    let mut all = Vec::new();
+    flatten(ty, &mut all);
+    let (voids, rest): (Vec<_>, Vec<_>) = all.into_iter().partition(is_void);
+
+    let mut written = Vec::new();
+    for ty in rest {
+        let needs_parens = matches!(ty.kind, TypeKind::Fn(..)) && !voids.is_empty();
+        let mut member = String::new();
+        write_type(&mut member, indent, canonical_unions, ty)?;
+        if needs_parens {
+            // `fn -> int?` would return the nullable type.
+            member = format!("({})", member);
+        }
+        written.push(member);
+    }
+    written.sort();
+    written.dedup();
+
+    match (written.len(), voids.is_empty()) {
+        (0, _) => write!(dest, "void"),
+        (_, true) => write!(dest, "{}", written.join(" | ")),
+        (1, false) => write!(dest, "{}?", written[0]),
+        (_, false) => write!(dest, "({})?", written.join(" | ")),
+    }
+}
+
+fn write_type<W: Write>(
+    dest: &mut W,
+    indent: u32,
+    canonical_unions: bool,
+    ty: Type,
+) -> fmt::Result {
     let ty = simplify_type(ty);
     match ty.kind {
         TypeKind::Implied => unreachable!(),
         TypeKind::Resolved(ty) => write!(dest, "{}", ty),
-        TypeKind::UserDefined(assignable) => write_assignable(dest, indent, assignable),
+        TypeKind::UserDefined(assignable) => write_assignable(dest, indent, canonical_unions, assignable),
+        TypeKind::Union(..) if canonical_unions => {
+            write_canonical_union(dest, indent, canonical_unions, ty)
+        }
+        // `int?` parses into `int | void`, so keep the sugar. Unions and
+        // functions would need parentheses that weren't there.
+        TypeKind::Union(ty, rest)
+            if is_void(&ty) != is_void(&rest) && !is_union_or_fn(&ty) && !is_union_or_fn(&rest) =>
+        {
+            let ty = if is_void(&ty) { rest } else { ty };
+            write_type(dest, indent, canonical_unions, *ty)?;
+            write!(dest, "?")
+        }
         TypeKind::Union(ty, rest) => {
-            write_type(dest, indent, *ty)?;
+            write_union_member(dest, indent, canonical_unions, *ty)?;
             write!(dest, " | ")?;
-            write_type(dest, indent, *rest)
+            write_union_member(dest, indent, canonical_unions, *rest)
         }
         TypeKind::Fn(params, ret) => {
             write!(dest, "fn")?;
             if !params.is_empty() {
                 write!(dest, " ")?;
-                write_comma_separated!(dest, indent, write_type, params);
+                write_comma_separated!(dest, indent, canonical_unions, write_type, params);
             }
             write!(dest, " -> ")?;
-            write_type(dest, indent, *ret)
+            write_type(dest, indent, canonical_unions, *ret)
         }
         TypeKind::Tuple(types) => {
             write!(dest, "(")?;
             let len = types.len();
-            write_comma_separated!(dest, indent, write_type, types);
+            write_comma_separated!(dest, indent, canonical_unions, write_type, types);
             if len == 1 {
                 write!(dest, ",")?;
             }
@@ -164,133 +301,258 @@ fn write_type<W: Write>(dest: &mut W, indent: u32, ty: Type) -> fmt::Result {
         }
         TypeKind::List(ty) => {
             write!(dest, "[")?;
-            write_type(dest, indent, *ty)?;
+            write_type(dest, indent, canonical_unions, *ty)?;
             write!(dest, "]")
         }
         TypeKind::Set(ty) => {
             write!(dest, "{{")?;
-            write_type(dest, indent, *ty)?;
+            write_type(dest, indent, canonical_unions, *ty)?;
             write!(dest, "}}")
         }
         TypeKind::Dict(key, val) => {
             write!(dest, "{{")?;
-            write_type(dest, indent, *key)?;
+            write_type(dest, indent, canonical_unions, *key)?;
             write!(dest, ": ")?;
-            write_type(dest, indent, *val)?;
+            write_type(dest, indent, canonical_unions, *val)?;
             write!(dest, "}}")
         }
-        TypeKind::Generic(ident) => write_identifier(dest, ident),
+        TypeKind::Generic(ident) => {
+            write!(dest, "#")?;
+            write_identifier(dest, ident)
+        }
         TypeKind::Grouping(ty) => {
             write!(dest, "(")?;
-            write_type(dest, indent, *ty)?;
+            write_type(dest, indent, canonical_unions, *ty)?;
             write!(dest, ")")
         }
     }
 }
 
-fn write_assignable<W: Write>(dest: &mut W, indent: u32, assignable: Assignable) -> fmt::Result {
+/// Chains of calls and accesses longer than this are written with one link
+/// per line.
+const MAX_CHAIN_WIDTH: usize = 80;
+
+/// The number of `->` and `.` in a chain.
+fn chain_links(assignable: &Assignable) -> usize {
+    match &assignable.kind {
+        AssignableKind::Call(callable, _) | AssignableKind::Index(callable, _) => {
+            chain_links(callable)
+        }
+        AssignableKind::ArrowCall(first, _, _) => {
+            1 + match &first.kind {
+                ExpressionKind::Get(first) => chain_links(first),
+                _ => 0,
+            }
+        }
+        AssignableKind::Access(accessable, _) => 1 + chain_links(accessable),
+        AssignableKind::Read(_) | AssignableKind::Expression(_) => 0,
+    }
+}
+
+fn write_assignable<W: Write>(
+    dest: &mut W,
+    indent: u32,
+    canonical_unions: bool,
+    assignable: Assignable,
+) -> fmt::Result {
+    if chain_links(&assignable) < 2 {
+        return write_chain(dest, indent, canonical_unions, assignable, None);
+    }
+    let mut inline = String::new();
+    write_chain(&mut inline, indent, canonical_unions, assignable.clone(), None)?;
+    let width = inline.lines().next().unwrap_or("").len() + indent as usize * INDENT.len();
+    if width > MAX_CHAIN_WIDTH {
+        write_chain(dest, indent, canonical_unions, assignable, Some(indent + 1))
+    } else {
+        write!(dest, "{}", inline)
+    }
+}
+
+/// Writes the links of a chain, each on a new line indented by `broken` if
+/// it's set.
+fn write_chain<W: Write>(
+    dest: &mut W,
+    indent: u32,
+    canonical_unions: bool,
+    assignable: Assignable,
+    broken: Option<u32>,
+) -> fmt::Result {
+    let link = |dest: &mut W, separator: &str| match broken {
+        Some(broken) => {
+            write!(dest, "\n")?;
+            write_indents(dest, broken)?;
+            write!(dest, "{}", separator.trim_start())
+        }
+        None => write!(dest, "{}", separator),
+    };
+    let inner = broken.unwrap_or(indent);
     match assignable.kind {
         AssignableKind::Read(identifier) => write_identifier(dest, identifier),
         AssignableKind::Call(callable, args) => {
-            write_assignable(dest, indent, *callable)?;
+            write_chain(dest, indent, canonical_unions, *callable, broken)?;
             write!(dest, "(")?;
-            write_comma_separated!(dest, indent, write_expression, args);
+            write_comma_separated!(dest, inner, canonical_unions, write_expression, args);
             write!(dest, ")")
         }
         AssignableKind::ArrowCall(first, callable, rest) => {
-            write_expression(dest, indent, *first)?;
-            write!(dest, " -> ")?;
-            write_assignable(dest, indent, *callable)?;
+            match (first.kind, broken) {
+                (ExpressionKind::Get(first), Some(_)) => write_chain(dest, indent, canonical_unions, first, broken)?,
+                (kind, _) => write_expression(dest, indent, canonical_unions, Expression { kind, ..*first })?,
+            }
+            link(dest, " -> ")?;
+            write_assignable(dest, inner, canonical_unions, *callable)?;
             write!(dest, "(")?;
-            write_comma_separated!(dest, indent, write_expression, rest);
+            write_comma_separated!(dest, inner, canonical_unions, write_expression, rest);
             write!(dest, ")")?;
             Ok(())
         }
         AssignableKind::Access(accessable, ident) => {
-            write_assignable(dest, indent, *accessable)?;
-            write!(dest, ".")?;
+            write_chain(dest, indent, canonical_unions, *accessable, broken)?;
+            link(dest, ".")?;
             write_identifier(dest, ident)
         }
         AssignableKind::Index(indexable, index) => {
-            write_assignable(dest, indent, *indexable)?;
+            write_chain(dest, indent, canonical_unions, *indexable, broken)?;
             write!(dest, "[")?;
-            write_expression(dest, indent, *index)?;
+            write_expression(dest, inner, canonical_unions, *index)?;
             write!(dest, "]")
         }
-        AssignableKind::Expression(expr) => write_expression(dest, indent, *expr),
+        AssignableKind::Expression(expr) => write_expression(dest, indent, canonical_unions, *expr),
+    }
+}
+
+/// Writes `and`s and `or`s, with a line break before every operator if the
+/// expression is wider than [MAX_CHAIN_WIDTH].
+fn write_condition<W: Write>(
+    dest: &mut W,
+    indent: u32,
+    canonical_unions: bool,
+    expression: Expression,
+) -> fmt::Result {
+    let mut inline = String::new();
+    write_condition_links(&mut inline, indent, canonical_unions, expression.clone(), None)?;
+    let width = inline.lines().next().unwrap_or("").len() + indent as usize * INDENT.len();
+    if width > MAX_CHAIN_WIDTH {
+        write_condition_links(dest, indent, canonical_unions, expression, Some(indent + 1))
+    } else {
+        write!(dest, "{}", inline)
+    }
+}
+
+/// Writes the operands of a condition, each operator on a new line indented
+/// by `broken` if it's set.
+fn write_condition_links<W: Write>(
+    dest: &mut W,
+    indent: u32,
+    canonical_unions: bool,
+    expression: Expression,
+    broken: Option<u32>,
+) -> fmt::Result {
+    let (lhs, op, rhs) = match expression.kind {
+        ExpressionKind::And(lhs, rhs) => (lhs, "and", rhs),
+        ExpressionKind::Or(lhs, rhs) => (lhs, "or", rhs),
+        kind => return write_expression(dest, indent, canonical_unions, Expression { kind, ..expression }),
+    };
+    write_condition_links(dest, indent, canonical_unions, *lhs, broken)?;
+    match broken {
+        Some(broken) => {
+            writeln!(dest)?;
+            write_indents(dest, broken)?;
+            write!(dest, "{} ", op)?;
+        }
+        None => write!(dest, " {} ", op)?,
     }
+    write_expression(dest, broken.unwrap_or(indent), canonical_unions, *rhs)
 }
 
 macro_rules! expr_binary_op {
-    ($dest:expr, $indent:expr, $lhs:expr, $op:literal, $rhs:expr) => {
-        write_expression($dest, $indent, $lhs)?;
+    ($dest:expr, $indent:expr, $canonical_unions:expr, $lhs:expr, $op:literal, $rhs:expr) => {
+        write_expression($dest, $indent, $canonical_unions, $lhs)?;
         write!($dest, $op)?;
-        write_expression($dest, $indent, $rhs)?;
+        write_expression($dest, $indent, $canonical_unions, $rhs)?;
     };
 }
 
-fn write_expression<W: Write>(dest: &mut W, indent: u32, expression: Expression) -> fmt::Result {
+fn write_expression<W: Write>(
+    dest: &mut W,
+    indent: u32,
+    canonical_unions: bool,
+    expression: Expression,
+) -> fmt::Result {
     match expression.kind {
-        ExpressionKind::Get(assignable) => write_assignable(dest, indent, assignable)?,
+        ExpressionKind::Get(assignable) => write_assignable(dest, indent, canonical_unions, assignable)?,
         ExpressionKind::TypeConstant(ty) => {
             write!(dest, ":")?;
-            write_type(dest, indent, ty)?;
+            write_type(dest, indent, canonical_unions, ty)?;
         }
         ExpressionKind::Add(lhs, rhs) => {
-            expr_binary_op!(dest, indent, *lhs, " + ", *rhs);
+            expr_binary_op!(dest, indent, canonical_unions, *lhs, " + ", *rhs);
         }
         ExpressionKind::Sub(lhs, rhs) => {
-            expr_binary_op!(dest, indent, *lhs, " - ", *rhs);
+            expr_binary_op!(dest, indent, canonical_unions, *lhs, " - ", *rhs);
         }
         ExpressionKind::Mul(lhs, rhs) => {
-            expr_binary_op!(dest, indent, *lhs, " * ", *rhs);
+            expr_binary_op!(dest, indent, canonical_unions, *lhs, " * ", *rhs);
         }
         ExpressionKind::Div(lhs, rhs) => {
-            expr_binary_op!(dest, indent, *lhs, " / ", *rhs);
+            expr_binary_op!(dest, indent, canonical_unions, *lhs, " / ", *rhs);
+        }
+        ExpressionKind::Mod(lhs, rhs) => {
+            expr_binary_op!(dest, indent, canonical_unions, *lhs, " % ", *rhs);
         }
         ExpressionKind::Neg(expr) => {
             write!(dest, "-")?;
-            write_expression(dest, indent, *expr)?;
+            write_expression(dest, indent, canonical_unions, *expr)?;
         }
         ExpressionKind::Comparison(lhs, cmp, rhs) => match cmp {
             ComparisonKind::Equals => {
-                expr_binary_op!(dest, indent, *lhs, " == ", *rhs);
+                expr_binary_op!(dest, indent, canonical_unions, *lhs, " == ", *rhs);
             }
             ComparisonKind::NotEquals => {
-                expr_binary_op!(dest, indent, *lhs, " != ", *rhs);
+                expr_binary_op!(dest, indent, canonical_unions, *lhs, " != ", *rhs);
             }
             ComparisonKind::Greater => {
-                expr_binary_op!(dest, indent, *lhs, " > ", *rhs);
+                expr_binary_op!(dest, indent, canonical_unions, *lhs, " > ", *rhs);
             }
             ComparisonKind::GreaterEqual => {
-                expr_binary_op!(dest, indent, *lhs, " >= ", *rhs);
+                expr_binary_op!(dest, indent, canonical_unions, *lhs, " >= ", *rhs);
             }
             ComparisonKind::Less => {
-                expr_binary_op!(dest, indent, *lhs, " < ", *rhs);
+                expr_binary_op!(dest, indent, canonical_unions, *lhs, " < ", *rhs);
             }
             ComparisonKind::LessEqual => {
-                expr_binary_op!(dest, indent, *lhs, " <= ", *rhs);
+                expr_binary_op!(dest, indent, canonical_unions, *lhs, " <= ", *rhs);
             }
             ComparisonKind::In => {
-                expr_binary_op!(dest, indent, *lhs, " in ", *rhs);
+                expr_binary_op!(dest, indent, canonical_unions, *lhs, " in ", *rhs);
             }
         },
+        ExpressionKind::Is(lhs, ty) => {
+            write_expression(dest, indent, canonical_unions, *lhs)?;
+            write!(dest, " is ")?;
+            write_type(dest, indent, canonical_unions, ty)?;
+        }
         ExpressionKind::AssertEq(lhs, rhs) => {
-            expr_binary_op!(dest, indent, *lhs, " <=> ", *rhs);
+            expr_binary_op!(dest, indent, canonical_unions, *lhs, " <=> ", *rhs);
         }
-        ExpressionKind::And(lhs, rhs) => {
-            expr_binary_op!(dest, indent, *lhs, " and ", *rhs);
+        ExpressionKind::Assert { condition, message } => {
+            write!(dest, "assert(")?;
+            write_expression(dest, indent, canonical_unions, *condition)?;
+            if let Some(message) = message {
+                write!(dest, ", \"{}\"", message)?;
+            }
+            write!(dest, ")")?;
         }
-        ExpressionKind::Or(lhs, rhs) => {
-            expr_binary_op!(dest, indent, *lhs, " or ", *rhs);
+        kind @ (ExpressionKind::And(..) | ExpressionKind::Or(..)) => {
+            write_condition(dest, indent, canonical_unions, Expression { kind, ..expression })?;
         }
         ExpressionKind::Not(expr) => {
             write!(dest, "not ")?;
-            write_expression(dest, indent, *expr)?;
+            write_expression(dest, indent, canonical_unions, *expr)?;
         }
         ExpressionKind::Parenthesis(expr) => {
             write!(dest, "(")?;
-            write_expression(dest, indent, *expr)?;
+            write_expression(dest, indent, canonical_unions, *expr)?;
             write!(dest, ")")?;
         }
         ExpressionKind::IfExpression {
@@ -298,54 +560,84 @@ fn write_expression<W: Write>(dest: &mut W, indent: u32, expression: Expression)
             pass,
             fail,
         } => {
-            write_expression(dest, indent, *pass)?;
+            write_expression(dest, indent, canonical_unions, *pass)?;
             write!(dest, " if ")?;
-            write_expression(dest, indent, *condition)?;
+            write_expression(dest, indent, canonical_unions, *condition)?;
             write!(dest, " else ")?;
-            write_expression(dest, indent, *fail)?;
+            write_expression(dest, indent, canonical_unions, *fail)?;
         }
         ExpressionKind::Function {
             name: _,
             params,
             ret,
+            is_const,
             body,
         } => {
+            if is_const {
+                write!(dest, "const ")?;
+            }
             write!(dest, "fn")?;
             if !params.is_empty() {
                 write!(dest, " ")?;
             }
-            write_parameters(dest, indent, params)?;
+            write_parameters(dest, indent, canonical_unions, params)?;
             if matches!(ret.kind, TypeKind::Resolved(RuntimeType::Void)) {
                 write!(dest, " ")?;
             } else {
                 write!(dest, " -> ")?;
-                write_type(dest, indent, ret)?;
+                write_type(dest, indent, canonical_unions, ret)?;
                 write!(dest, " ")?;
             }
 
             match body.kind {
                 StatementKind::Block { statements } => {
                     write!(dest, "do\n")?;
-                    for s in merge_empty_statements(statements) {
-                        write_statement(dest, indent + 1, s)?;
-                    }
+                    write_block_statements(dest, indent + 1, canonical_unions, statements)?;
                     write_indents(dest, indent)?;
                     write!(dest, "end")?;
                     // NOTE(ed): No newline here!
                 }
                 _ => {
-                    write_statement(dest, indent, *body)?;
+                    write_statement(dest, indent, canonical_unions, *body)?;
+                }
+            }
+        }
+        ExpressionKind::Loop(body) => {
+            if let StatementKind::Loop { condition, body, .. } = body.kind {
+                write!(dest, "loop ")?;
+                if !matches!(condition.kind, ExpressionKind::Bool(true)) {
+                    write_expression(dest, indent, canonical_unions, condition)?;
+                    write!(dest, " ")?;
+                }
+                match body.kind {
+                    StatementKind::Block { statements } => {
+                        write!(dest, "do\n")?;
+                        write_block_statements(dest, indent + 1, canonical_unions, statements)?;
+                        write_indents(dest, indent)?;
+                        write!(dest, "end")?;
+                    }
+                    _ => {
+                        write_statement(dest, indent, canonical_unions, *body)?;
+                    }
                 }
             }
         }
         ExpressionKind::Blob { blob, fields } => {
-            write_assignable(dest, indent, blob)?;
-            write_blob_fields(dest, indent + 1, fields, write_expression)?;
+            write_assignable(dest, indent, canonical_unions, blob)?;
+            write_blob_fields(
+                dest,
+                indent + 1,
+                canonical_unions,
+                fields,
+                write_expression,
+                is_field_shorthand,
+                false,
+            )?;
         }
         ExpressionKind::Tuple(exprs) => {
             let num_exprs = exprs.len();
             write!(dest, "(")?;
-            write_comma_separated!(dest, indent, write_expression, exprs);
+            write_comma_separated!(dest, indent, canonical_unions, write_expression, exprs);
             if num_exprs == 1 {
                 write!(dest, ",")?;
             }
@@ -353,12 +645,12 @@ fn write_expression<W: Write>(dest: &mut W, indent: u32, expression: Expression)
         }
         ExpressionKind::List(exprs) => {
             write!(dest, "[")?;
-            write_comma_separated!(dest, indent, write_expression, exprs);
+            write_comma_separated!(dest, indent, canonical_unions, write_expression, exprs);
             write!(dest, "]")?;
         }
         ExpressionKind::Set(exprs) => {
             write!(dest, "{{")?;
-            write_comma_separated!(dest, indent, write_expression, exprs);
+            write_comma_separated!(dest, indent, canonical_unions, write_expression, exprs);
             write!(dest, "}}")?;
         }
         ExpressionKind::Dict(exprs) => {
@@ -373,9 +665,9 @@ fn write_expression<W: Write>(dest: &mut W, indent: u32, expression: Expression)
                         write!(dest, ", ")?;
                     }
                     first = false;
-                    write_expression(dest, indent, expr)?;
+                    write_expression(dest, indent, canonical_unions, expr)?;
                     write!(dest, ": ")?;
-                    write_expression(dest, indent, exprs.next().unwrap())?;
+                    write_expression(dest, indent, canonical_unions, exprs.next().unwrap())?;
                 }
             }
             write!(dest, "}}")?;
@@ -390,20 +682,104 @@ fn write_expression<W: Write>(dest: &mut W, indent: u32, expression: Expression)
     Ok(())
 }
 
-fn write_statement<W: Write>(dest: &mut W, indent: u32, statement: Statement) -> fmt::Result {
-    for comment in &statement.comments {
+fn write_comments<W: Write>(dest: &mut W, indent: u32, comments: &[Comment]) -> fmt::Result {
+    for comment in comments {
+        write_indents(dest, indent)?;
+        write_comment(dest, comment)?;
+        write!(dest, "\n")?;
+    }
+    Ok(())
+}
+
+fn write_comment<W: Write>(dest: &mut W, comment: &Comment) -> fmt::Result {
+    match comment {
+        Comment::Line(text) => write!(dest, "// {}", text),
+        Comment::Block(text) => write!(dest, "{}", text),
+    }
+}
+
+/// Writes the statements inside a block. Comments after the last statement
+/// are kept right above the end of the block.
+fn write_block_statements<W: Write>(
+    dest: &mut W,
+    indent: u32,
+    canonical_unions: bool,
+    statements: Vec<Statement>,
+) -> fmt::Result {
+    let mut statements = merge_empty_statements(statements);
+    let closing = match statements.last() {
+        Some(Statement { kind: StatementKind::EmptyStatement, .. }) => statements.pop(),
+        _ => None,
+    };
+    for s in statements {
+        write_statement(dest, indent, canonical_unions, s)?;
+    }
+    if let Some(closing) = closing {
+        write_comments(dest, indent, &closing.comments)?;
+    }
+    Ok(())
+}
+
+fn write_annotations<W: Write>(
+    dest: &mut W,
+    indent: u32,
+    canonical_unions: bool,
+    annotations: Vec<Annotation>,
+) -> fmt::Result {
+    for annotation in annotations {
         write_indents(dest, indent)?;
-        write!(dest, "// {}\n", comment)?;
+        write!(dest, "@")?;
+        write_identifier(dest, annotation.name)?;
+        if !annotation.args.is_empty() {
+            write!(dest, "(")?;
+            write_comma_separated!(dest, indent, canonical_unions, write_expression, annotation.args);
+            write!(dest, ")")?;
+        }
+        writeln!(dest)?;
     }
+    Ok(())
+}
+
+fn write_blob<W: Write>(
+    dest: &mut W,
+    indent: u32,
+    canonical_unions: bool,
+    name: String,
+    fields: HashMap<String, Type>,
+    align: bool,
+) -> fmt::Result {
+    write_indents(dest, indent)?;
+    write!(dest, "{} :: blob", escape_name(&name))?;
+    let fields_as_tuples = fields.into_iter().collect();
+    write_blob_fields(dest, indent + 1, canonical_unions, fields_as_tuples, write_type, |_, _| false, align)
+}
+
+fn write_statement<W: Write>(
+    dest: &mut W,
+    indent: u32,
+    canonical_unions: bool,
+    statement: Statement,
+) -> fmt::Result {
+    write_comments(dest, indent, &statement.comments)?;
+    let trailing_comments = statement.trailing_comments;
+    write_annotations(dest, indent, canonical_unions, statement.annotations)?;
 
     match statement.kind {
+        StatementKind::Assert { expr, message } => {
+            write_indents(dest, indent)?;
+            write!(dest, "assert ")?;
+            write_expression(dest, indent, canonical_unions, expr)?;
+            if let Some(message) = message {
+                write!(dest, ", \"{}\"", message)?;
+            }
+        }
         StatementKind::Assignment {
             kind,
             target,
             value,
         } => {
             write_indents(dest, indent)?;
-            write_assignable(dest, indent, target)?;
+            write_assignable(dest, indent, canonical_unions, target)?;
             write!(
                 dest,
                 " {}= ",
@@ -413,47 +789,64 @@ fn write_statement<W: Write>(dest: &mut W, indent: u32, statement: Statement) ->
                     Op::Sub => "-",
                     Op::Mul => "*",
                     Op::Div => "/",
+                    Op::Mod => "%",
                 }
             )?;
-            write_expression(dest, indent, value)?;
+            write_expression(dest, indent, canonical_unions, value)?;
         }
         StatementKind::Blob { name, fields } => {
-            write_indents(dest, indent)?;
-            write!(dest, "{} :: blob", name)?;
-            let fields_as_tuples = fields.into_iter().collect();
-            write_blob_fields(dest, indent + 1, fields_as_tuples, write_type)?;
+            write_blob(dest, indent, canonical_unions, name, fields, false)?;
         }
         StatementKind::Block { statements } => {
             write_indents(dest, indent)?;
             write!(dest, "do\n")?;
 
-            for s in merge_empty_statements(statements) {
-                write_statement(dest, indent + 1, s)?;
-            }
+            write_block_statements(dest, indent + 1, canonical_unions, statements)?;
 
             write_indents(dest, indent)?;
             write!(dest, "end")?
         }
-        StatementKind::Break => {
+        StatementKind::Break { label, value } => {
             write_indents(dest, indent)?;
-            write!(dest, "break")?
+            write!(dest, "break")?;
+            let labeled = label.is_some();
+            if let Some(label) = label {
+                write!(dest, " ")?;
+                write_identifier(dest, label)?;
+            }
+            if let Some(value) = value {
+                write!(dest, " ")?;
+                // A lone name would be read as a label.
+                let is_name = matches!(&value.kind, ExpressionKind::Get(Assignable { kind: AssignableKind::Read(_), .. }));
+                if is_name && !labeled {
+                    write!(dest, "(")?;
+                    write_expression(dest, indent, canonical_unions, value)?;
+                    write!(dest, ")")?;
+                } else {
+                    write_expression(dest, indent, canonical_unions, value)?;
+                }
+            }
         }
-        StatementKind::Continue => {
+        StatementKind::Continue { label } => {
             write_indents(dest, indent)?;
-            write!(dest, "continue")?
+            write!(dest, "continue")?;
+            if let Some(label) = label {
+                write!(dest, " ")?;
+                write_identifier(dest, label)?;
+            }
         }
         StatementKind::ExternalDefinition {
             ident,
             kind,
             ty,
         } => {
-            assert!(!matches!(ty.kind, TypeKind::Implied), "Should not parse");
-            assert!(!kind.force(), "Should not parse");
+            assert!(!matches!(ty.kind, TypeKind::Implied), "checked by unwritable_definitions");
+            assert!(!kind.force(), "checked by unwritable_definitions");
 
             write_indents(dest, indent)?;
             write_identifier(dest, ident)?;
             write!(dest, ": ")?;
-            write_type(dest, indent, ty)?;
+            write_type(dest, indent, canonical_unions, ty)?;
             if kind.immutable() {
                 write!(dest, " : ")?;
             } else {
@@ -476,8 +869,9 @@ fn write_statement<W: Write>(dest: &mut W, indent: u32, statement: Statement) ->
                     match kind {
                         VarKind::Const => " :: ",
                         VarKind::Mutable => " := ",
-                        VarKind::ForceConst => unreachable!("can't force an implied type"),
-                        VarKind::ForceMutable => unreachable!("can't force an implied type"),
+                        VarKind::ForceConst | VarKind::ForceMutable => {
+                            unreachable!("checked by unwritable_definitions")
+                        }
                     }
                 )?;
             } else {
@@ -485,14 +879,38 @@ fn write_statement<W: Write>(dest: &mut W, indent: u32, statement: Statement) ->
                 if kind.force() {
                     write!(dest, "!")?;
                 }
-                write_type(dest, indent, ty)?;
+                write_type(dest, indent, canonical_unions, ty)?;
                 if kind.immutable() {
                     write!(dest, " : ")?;
                 } else {
                     write!(dest, " = ")?;
                 }
             }
-            write_expression(dest, indent, value)?;
+            write_expression(dest, indent, canonical_unions, value)?;
+        }
+        StatementKind::DestructuringDefinition { idents, kind, value } => {
+            write_indents(dest, indent)?;
+            write!(dest, "(")?;
+            for (i, ident) in idents.into_iter().enumerate() {
+                if i != 0 {
+                    write!(dest, ", ")?;
+                }
+                write_identifier(dest, ident)?;
+            }
+            write!(dest, "){}", if kind.immutable() { " :: " } else { " := " })?;
+            write_expression(dest, indent, canonical_unions, value)?;
+        }
+        StatementKind::DestructuringAssignment { targets, value } => {
+            write_indents(dest, indent)?;
+            write!(dest, "(")?;
+            for (i, target) in targets.into_iter().enumerate() {
+                if i != 0 {
+                    write!(dest, ", ")?;
+                }
+                write_assignable(dest, indent, canonical_unions, target)?;
+            }
+            write!(dest, ") = ")?;
+            write_expression(dest, indent, canonical_unions, value)?;
         }
         StatementKind::EmptyStatement => (),
         StatementKind::If {
@@ -501,44 +919,76 @@ fn write_statement<W: Write>(dest: &mut W, indent: u32, statement: Statement) ->
             fail,
         } => {
             if matches!(fail.kind, StatementKind::EmptyStatement) {
-                for comment in &fail.comments {
-                    write_indents(dest, indent)?;
-                    write!(dest, "// {}\n", comment)?;
-                }
+                write_comments(dest, indent, &fail.comments)?;
             }
 
             write_indents(dest, indent)?;
             write!(dest, "if ")?;
-            write_expression(dest, indent, condition)?;
+            write_expression(dest, indent, canonical_unions, condition)?;
             write!(dest, " ")?;
-            write_statement(dest, indent, *pass)?;
+            write_statement(dest, indent, canonical_unions, *pass)?;
             if !matches!(fail.kind, StatementKind::EmptyStatement) {
                 write!(dest, " else ")?;
-                write_statement(dest, indent, *fail)?;
+                write_statement(dest, indent, canonical_unions, *fail)?;
             }
         }
+        StatementKind::TypeMatch { value, arms, fallback, .. } => {
+            write_indents(dest, indent)?;
+            write!(dest, "type_match ")?;
+            write_expression(dest, indent, canonical_unions, value)?;
+            write!(dest, " do\n")?;
+            let arms = arms.into_iter().map(|(ty, body)| (Some(ty), body));
+            for (ty, mut body) in arms.chain(fallback.map(|fallback| (None, *fallback))) {
+                write_comments(dest, indent + 1, &body.comments)?;
+                body.comments.clear();
+                write_indents(dest, indent + 1)?;
+                match ty {
+                    Some(ty) => write_type(dest, indent + 1, canonical_unions, ty)?,
+                    None => write!(dest, "_")?,
+                }
+                write!(dest, " -> ")?;
+                // The statement continues the line of the arm.
+                let mut arm = String::new();
+                write_statement(&mut arm, indent + 1, canonical_unions, body)?;
+                write!(dest, "{}\n", arm.trim())?;
+            }
+            write_indents(dest, indent)?;
+            write!(dest, "end")?;
+        }
+        StatementKind::Defer { body } => {
+            write_indents(dest, indent)?;
+            write!(dest, "defer ")?;
+            // The deferred statement continues this line.
+            let mut deferred = String::new();
+            write_statement(&mut deferred, indent, canonical_unions, *body)?;
+            write!(dest, "{}", deferred.trim())?;
+        }
         StatementKind::IsCheck { lhs, rhs } => {
             write_indents(dest, indent)?;
             write!(dest, ":")?;
-            write_type(dest, indent, lhs)?;
+            write_type(dest, indent, canonical_unions, lhs)?;
             write!(dest, " is :")?;
-            write_type(dest, indent, rhs)?;
+            write_type(dest, indent, canonical_unions, rhs)?;
         }
-        StatementKind::Loop { condition, body } => {
+        StatementKind::Loop { label, condition, body } => {
             write_indents(dest, indent)?;
+            if let Some(label) = label {
+                write_identifier(dest, label)?;
+                write!(dest, ": ")?;
+            }
             write!(dest, "loop ")?;
-            write_expression(dest, indent, condition)?;
+            write_expression(dest, indent, canonical_unions, condition)?;
             write!(dest, " ")?;
-            write_statement(dest, indent, *body)?;
+            write_statement(dest, indent, canonical_unions, *body)?;
         }
         StatementKind::Ret { value } => {
             write_indents(dest, indent)?;
             write!(dest, "ret ")?;
-            write_expression(dest, indent, value)?;
+            write_expression(dest, indent, canonical_unions, value)?;
         }
         StatementKind::StatementExpression { value } => {
             write_indents(dest, indent)?;
-            write_expression(dest, indent, value)?;
+            write_expression(dest, indent, canonical_unions, value)?;
         }
         StatementKind::Unreachable => {
             write_indents(dest, indent)?;
@@ -552,12 +1002,36 @@ fn write_statement<W: Write>(dest: &mut W, indent: u32, statement: Statement) ->
             write_indents(dest, indent)?;
             write!(dest, "use ")?;
             write_identifier(dest, path)?;
-            if let NameIdentifier::Alias(alias) = name {
-                write!(dest, " as ")?;
-                write_identifier(dest, alias)?;
+            match name {
+                NameIdentifier::Implicit(_) => {}
+                NameIdentifier::All => {
+                    write!(dest, ".*")?;
+                }
+                NameIdentifier::Alias(alias) => {
+                    write!(dest, " as ")?;
+                    write_identifier(dest, alias)?;
+                }
+                NameIdentifier::Select(mut selected) if selected.len() == 1 => {
+                    write!(dest, ".")?;
+                    write_selected_name(dest, selected.remove(0))?;
+                }
+                NameIdentifier::Select(selected) => {
+                    write!(dest, " {{ ")?;
+                    for (i, selected) in selected.into_iter().enumerate() {
+                        if i != 0 {
+                            write!(dest, ", ")?;
+                        }
+                        write_selected_name(dest, selected)?;
+                    }
+                    write!(dest, " }}")?;
+                }
             }
         }
     }
+    for comment in &trailing_comments {
+        write!(dest, " ")?;
+        write_comment(dest, comment)?;
+    }
     write!(dest, "\n")?;
 
     Ok(())
@@ -573,10 +1047,12 @@ fn merge_empty_statements(mut statements: Vec<Statement>) -> Vec<Statement> {
     let mut ret = Vec::new();
     while let Some(mut statement) = statements.pop() {
         // Begin eating empty statements
-        while matches!(
-            statements.last().map(|s| &s.kind),
-            Some(StatementKind::EmptyStatement)
-        ) {
+        while matches!(statement.kind, StatementKind::EmptyStatement)
+            && matches!(
+                statements.last().map(|s| &s.kind),
+                Some(StatementKind::EmptyStatement)
+            )
+        {
             statement
                 .comments
                 .append(&mut statements.pop().unwrap().comments);
@@ -586,30 +1062,137 @@ fn merge_empty_statements(mut statements: Vec<Statement>) -> Vec<Statement> {
     ret
 }
 
-fn format_module(module: Module) -> Result<String, fmt::Error> {
+/// Finds the definitions there is no syntax for, like a forced kind without a
+/// type - which the parser never produces but a rewritten tree might contain.
+fn unwritable_definitions(file: &Path, module: &Module) -> Vec<Error> {
+    struct Unwritable<'a> {
+        file: &'a Path,
+        errors: Vec<Error>,
+    }
+
+    impl Visitor for Unwritable<'_> {
+        fn visit_statement(&mut self, statement: &Statement) {
+            let message = match &statement.kind {
+                StatementKind::Definition { kind, ty, .. }
+                    if kind.force() && matches!(ty.kind, TypeKind::Implied) =>
+                {
+                    Some("A forced definition needs a type, e.g. `a: !int = 1`")
+                }
+                StatementKind::ExternalDefinition { kind, ty, .. }
+                    if kind.force() || matches!(ty.kind, TypeKind::Implied) =>
+                {
+                    Some("An external definition needs a type that isn't forced")
+                }
+                _ => None,
+            };
+            if let Some(message) = message {
+                self.errors.push(Error::SyntaxError {
+                    file: self.file.to_path_buf(),
+                    span: statement.span,
+                    message: message.to_string(),
+                });
+            }
+            walk_statement(self, statement);
+        }
+    }
+
+    let mut unwritable = Unwritable { file, errors: Vec::new() };
+    unwritable.visit_module(module);
+    unwritable.errors
+}
+
+fn format_module(module: Module, args: &Args) -> Result<String, fmt::Error> {
     let mut formatted = String::new();
+    if let Some(shebang) = &module.shebang {
+        writeln!(formatted, "{}", shebang)?;
+    }
     merge_empty_statements(module.statements)
         .into_iter()
         // Side effects incoming!
         .map(|s| {
-            write_statement(&mut formatted, 0, s)?;
+            match s.kind {
+                // Blobs are only allowed in the outer scope.
+                StatementKind::Blob { name, fields } if args.align_blob_fields => {
+                    write_comments(&mut formatted, 0, &s.comments)?;
+                    write_blob(&mut formatted, 0, args.canonical_unions, name, fields, true)?;
+                    write!(formatted, "\n")?;
+                }
+                _ => write_statement(&mut formatted, 0, args.canonical_unions, s)?,
+            }
             write!(formatted, "\n")
         })
         .collect::<Result<Vec<_>, _>>()?;
     Ok(formatted)
 }
 
+/// Writes the statement like it's written in a formatted file, indented
+/// `indent` levels.
+pub(crate) fn format_statement(indent: u32, statement: Statement) -> String {
+    let mut formatted = String::new();
+    write_statement(&mut formatted, indent, false, statement).unwrap();
+    formatted
+}
+
 pub fn format(args: &Args) -> Result<String, Vec<Error>> {
     let mut tree = sylt_parser::tree(
         &PathBuf::from(args.args.first().expect("No file to run")),
         crate::read_file,
     )?;
-    Ok(format_module(tree.modules.remove(0).1).unwrap())
+    let (file, module) = tree.modules.remove(0);
+    let errors = unwritable_definitions(&file, &module);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(format_module(module, args).unwrap())
+}
+
+/// Files ending with this next to a test file pin the exact formatted output
+/// of the test. Set the environment variable below to overwrite them with the
+/// current output; an empty snapshot file can be added to start pinning a test.
+#[cfg(test)]
+const SNAPSHOT_EXTENSION: &str = "fmt";
+#[cfg(test)]
+const UPDATE_SNAPSHOTS_VAR: &str = "SYLT_UPDATE_SNAPSHOTS";
+
+/// Compares the formatted output with the snapshot next to the file, if there
+/// is one.
+#[cfg(test)]
+fn check_snapshot(path: &str, formatted: &str) {
+    let snapshot = std::path::PathBuf::from(format!("{}.{}", path, SNAPSHOT_EXTENSION));
+    if !snapshot.exists() {
+        return;
+    }
+
+    if std::env::var_os(UPDATE_SNAPSHOTS_VAR).is_some() {
+        std::fs::write(&snapshot, formatted).unwrap();
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&snapshot).unwrap();
+    if expected == formatted {
+        return;
+    }
+
+    let expected_lines: Vec<_> = expected.lines().collect();
+    let formatted_lines: Vec<_> = formatted.lines().collect();
+    for i in 0..expected_lines.len().max(formatted_lines.len()) {
+        let expected = expected_lines.get(i);
+        let got = formatted_lines.get(i);
+        if expected != got {
+            eprintln!("{:>4} - {}", i + 1, expected.unwrap_or(&""));
+            eprintln!("{:>4} + {}", i + 1, got.unwrap_or(&""));
+        }
+    }
+    panic!(
+        "the formatted output differs from {}, set {} to update it",
+        snapshot.display(),
+        UPDATE_SNAPSHOTS_VAR,
+    );
 }
 
 #[cfg(test)]
 macro_rules! test_formatter_on_file {
-    ($fn:ident, $path:literal, $print:expr, $errs:pat, $_:expr) => {
+    ($fn:ident, $path:literal, $print:expr, $errs:pat, $_:expr, $_lua:expr) => {
         #[test]
         fn $fn() {
             use std::path::{Path, PathBuf};
@@ -638,6 +1221,8 @@ macro_rules! test_formatter_on_file {
             // Format the file.
             match $crate::formatter::format(&args) {
                 Ok(formatted) => {
+                    $crate::formatter::check_snapshot(&path, &formatted);
+
                     let formatted_path = PathBuf::from(&path).canonicalize().unwrap();
                     let read_formatted_or_file = |path: &Path| {
                         if path.canonicalize().unwrap() == formatted_path {
@@ -669,3 +1254,169 @@ macro_rules! test_formatter_on_file {
 
 #[cfg(test)]
 sylt_macro::find_tests!(test_formatter_on_file);
+
+#[cfg(test)]
+mod test {
+    use crate::Args;
+    use std::path::PathBuf;
+
+    fn format_file(path: &str, align_blob_fields: bool) -> String {
+        let mut args = Args::default();
+        args.args = vec![path.to_string()];
+        args.align_blob_fields = align_blob_fields;
+        super::format(&args).unwrap()
+    }
+
+    #[test]
+    fn blob_fields_unaligned() {
+        let formatted = format_file("../tests/blob/field_alignment.sy", false);
+        assert!(formatted.contains("\n    x: float,\n"));
+        assert!(formatted.contains("\n    a_longer_name: int,\n"));
+    }
+
+    #[test]
+    fn blob_fields_aligned() {
+        let formatted = format_file("../tests/blob/field_alignment.sy", true);
+        assert!(formatted.starts_with("// A blob with fields of varying name lengths.\n"));
+        assert!(formatted.contains("\n    x            : float,\n"));
+        assert!(formatted.contains("\n    y            : float,\n"));
+        assert!(formatted.contains("\n    name         : str,\n"));
+        assert!(formatted.contains("\n    a_longer_name: int,\n"));
+        // Blob instances aren't aligned.
+        assert!(formatted.contains("\n        x: 1.0,\n"));
+    }
+
+    #[test]
+    fn blob_field_shorthand() {
+        let formatted = format_file("../tests/blob/field_shorthand_explicit.sy", false);
+        assert!(formatted.contains("\n        x,\n        y,\n"));
+        let formatted = format_file("../tests/blob/field_shorthand_mixed.sy", false);
+        assert!(formatted.contains("\n        x,\n        y: x + 1,\n"));
+    }
+
+    #[test]
+    fn short_chain() {
+        let formatted = format_file("../tests/expression/arrow_chain_short.sy", false);
+        assert!(formatted.contains("\n    sum := 1 -> add(2) -> add(3)\n"));
+    }
+
+    #[test]
+    fn long_chain() {
+        let formatted = format_file("../tests/expression/arrow_chain_long.sy", false);
+        assert!(formatted.contains("\n    sum := 1\n        -> add_numbers(2)\n        -> add_numbers(3)\n"));
+        assert!(formatted.contains("\n    sum = 1 -> add_numbers(10) -> add_numbers(100)\n"));
+        assert!(formatted.contains("_behind_the_house\n        .branch\n        .leaf\n        .value <=> 1\n"));
+        assert!(formatted.contains("\n    tree.branch.leaf.value <=> 1\n"));
+    }
+
+    #[test]
+    fn keyword_names() {
+        let formatted = format_file("../tests/blob/keyword_field.sy", false);
+        assert!(formatted.contains("\n    `loop`: bool,\n"));
+        assert!(formatted.contains("\n    `end` := 1\n"));
+        assert!(formatted.contains("\n    step.`fn`(1) <=> 2\n"));
+        let formatted = format_file("../tests/blob/keyword_field_shorthand.sy", false);
+        assert!(formatted.contains("step := Step { `loop` }"));
+    }
+
+    #[test]
+    fn type_match() {
+        let formatted = format_file("../tests/type_match/dispatch.sy", false);
+        assert!(formatted.contains("\n    type_match value do\n        int -> ret \"int \" + as_str(value + 1)\n"));
+        assert!(formatted.contains("\n        _ -> ret \"something else\"\n    end\n"));
+        let formatted = format_file("../tests/type_match/first_arm.sy", false);
+        assert!(formatted.contains("\n        int | str -> do\n"));
+        assert!(formatted.contains("\n            hits += 1\n        end\n        int -> do\n"));
+    }
+
+    #[test]
+    fn tuple_types() {
+        let formatted = format_file("../tests/typing/tuple_types.sy", false);
+        assert!(formatted.contains("fn a: (), b: (int,), c: (int, float) -> (int, (float,)) do"));
+        assert!(formatted.contains("a: () = ()"));
+        assert!(formatted.contains("b: (int,) = (1,)"));
+        assert!(formatted.contains("c: (int, float) = (1, 2.0)"));
+    }
+
+    #[test]
+    fn defer() {
+        let formatted = format_file("../tests/defer/order.sy", false);
+        assert!(formatted.contains("\n        defer l -> push(1)\n        defer l -> push(2)\n"));
+        assert!(formatted.contains("\n        defer do\n"));
+    }
+
+    #[test]
+    fn destructuring() {
+        let formatted = format_file("../tests/destructuring/definition.sy", false);
+        assert!(formatted.contains("\n    (a, b) := pair()\n"));
+        assert!(formatted.contains("\n    (x, y, z) :: (1.5, true, [1])\n"));
+        let formatted = format_file("../tests/destructuring/assignment.sy", false);
+        assert!(formatted.contains("\n    (a, b) = (b, a)\n"));
+        assert!(formatted.contains("\n    (p.x, l[1]) = (3, 4)\n"));
+    }
+
+    #[test]
+    fn nullable_sugar() {
+        let path = "../tests/typing/nullable_sugar.sy";
+        let formatted = format_file(path, false);
+        assert!(formatted.contains("a: int? = nil"));
+        assert!(formatted.contains("b: int? = 1"));
+        assert!(formatted.contains("c: (int | str)? = \"c\""));
+        assert!(formatted.contains("d: [int?] = [1, nil]"));
+        assert!(formatted.contains("e: int | str | void = nil"));
+        assert!(formatted.contains("f: fn int? -> str? = fn x: int? -> str? do"));
+        assert!(formatted.contains("g: (fn -> int)? = nil"));
+
+        // The sugar parses back into the same types.
+        let mut tree = sylt_parser::tree(&PathBuf::from(path), |_| Ok(formatted.clone())).unwrap();
+        let reformatted = super::format_module(tree.modules.remove(0).1, &Args::default()).unwrap();
+        let definitions = |source: &str| {
+            source.lines().filter(|line| line.contains(": ")).map(String::from).collect::<Vec<_>>()
+        };
+        assert_eq!(definitions(&formatted), definitions(&reformatted));
+    }
+
+    #[test]
+    fn forced_definitions() {
+        let formatted = format_file("../tests/typing/forced_definitions.sy", false);
+        assert!(formatted.contains("\n    x: !int = a[0 + 0]\n"));
+        assert!(formatted.contains("\n    y: !int : a[0 + 0]\n"));
+        let formatted = format_file("../tests/typing/forced_definition_excessive.sy", false);
+        assert!(formatted.contains("\n    x: !int = 0\n"));
+    }
+
+    #[test]
+    fn forced_implied_definition() {
+        use sylt_common::Error;
+        use sylt_parser::{StatementKind, TypeKind};
+
+        let path = PathBuf::from("forced_implied.sy");
+        let source = "x: !int = 0\nstart :: fn do end\n";
+        let mut tree = sylt_parser::tree(&path, |_| Ok(source.to_string())).unwrap();
+        let (file, mut module) = tree.modules.remove(0);
+        assert!(super::unwritable_definitions(&file, &module).is_empty());
+
+        // What the tree could look like after the type is inferred away.
+        match &mut module.statements[0].kind {
+            StatementKind::Definition { ty, .. } => ty.kind = TypeKind::Implied,
+            _ => unreachable!(),
+        }
+        let errors = super::unwritable_definitions(&file, &module);
+        assert!(matches!(
+            errors.as_slice(),
+            [Error::SyntaxError { span, .. }] if span.line == 1
+        ));
+    }
+
+    #[test]
+    fn canonical_unions() {
+        let mut args = Args::default();
+        args.args = vec!["../tests/typing/canonical_unions.sy".to_string()];
+        args.canonical_unions = true;
+        let formatted = super::format(&args).unwrap();
+        assert!(formatted.contains("a: int? = 1"));
+        assert!(formatted.contains("b: (int | str)? = \"b\""));
+        assert!(formatted.contains("c: (int | str)? = 1"));
+        assert!(formatted.contains("d: int | str = \"d\""));
+    }
+}
@@ -1,13 +1,16 @@
 /// Re-export of derived functions for [Args].
 pub use gumdrop::Options;
 
+use std::collections::BTreeSet;
 use std::fmt::Debug;
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use sylt_common::error::Error;
+use sylt_common::error::{Error, Severity};
 use sylt_common::prog::{BytecodeProg, Prog};
 use sylt_common::RustFunction;
+use sylt_parser::{Expression, ExpressionKind, Span, StatementKind, AST};
 
+pub mod ast_diff;
 pub mod formatter;
 
 type ExternFunctionList = Vec<(String, RustFunction, String)>;
@@ -31,21 +34,101 @@ pub fn read_file(path: &Path) -> Result<String, Error> {
     std::fs::read_to_string(path).map_err(|_| Error::FileNotFound(path.to_path_buf()))
 }
 
+/// Compiles the file, and returns the program together with the warnings for
+/// the caller to show. With `--deny-warnings` the warnings are errors instead.
 pub fn compile_with_reader_to_writer<R>(
     args: &Args,
     functions: ExternFunctionList,
     reader: R,
     write_file: Option<Box<dyn Write>>,
-) -> Result<Prog, Vec<Error>>
+) -> Result<(Prog, Vec<Error>), Vec<Error>>
 where
     R: Fn(&Path) -> Result<String, Error>,
 {
     let file = PathBuf::from(args.args.first().expect("No file to run"));
-    let tree = sylt_parser::tree(&file, reader)?;
+    // The lua-compiler has no runtime checks of forced types.
+    if args.debug_checks && write_file.is_some() {
+        return Err(vec![Error::CompileError {
+            file,
+            span: Span::zero(),
+            message: Some("--debug-checks only works when running with the VM, not with lua".to_string()),
+        }]);
+    }
+    let mut tree = sylt_parser::tree(&file, reader)?;
+    let warnings = sylt_parser::warnings(&tree);
+    if args.deny_warnings && warnings.iter().any(|w| w.severity() == Severity::Warning) {
+        return Err(warnings);
+    }
     if args.dump_tree {
         println!("{}", tree);
     }
-    sylt_compiler::compile(!args.skip_typecheck, write_file, tree, &functions)
+    let entry = match &args.run_function {
+        Some(name) if !exported(&tree, &file).contains(name) => {
+            return Err(vec![Error::CompileError {
+                file,
+                span: Span::zero(),
+                message: Some(format!("'{}' isn't an exported function, add @export above it to run it", name)),
+            }]);
+        }
+        Some(name) => name.as_str(),
+        None => "start",
+    };
+    if args.optimize || args.optimize_report {
+        let propagations = sylt_compiler::optimize(&mut tree)?;
+        if args.optimize_report {
+            for propagation in propagations {
+                eprintln!("{}", propagation);
+            }
+        }
+    }
+    let prog = sylt_compiler::compile(!args.skip_typecheck, args.debug_checks, entry, write_file, tree, &functions)?;
+    Ok((prog, warnings))
+}
+
+/// Parses the file and lists the functions in it that are marked with
+/// `@export`, in the order they're defined. These are the entry points
+/// `--run-function` accepts.
+pub fn exported_functions(args: &Args) -> Result<Vec<String>, Vec<Error>> {
+    let file = PathBuf::from(args.args.first().expect("No file to run"));
+    let tree = sylt_parser::tree(&file, read_file)?;
+    Ok(exported(&tree, &file))
+}
+
+fn exported(tree: &AST, file: &Path) -> Vec<String> {
+    tree.modules
+        .iter()
+        .filter(|(path, _)| path == file)
+        .flat_map(|(_, module)| module.statements.iter())
+        .filter(|statement| statement.annotations.iter().any(|a| a.name.name == "export"))
+        .filter_map(|statement| match &statement.kind {
+            StatementKind::Definition { ident, value: Expression { kind: ExpressionKind::Function { .. }, .. }, .. } => {
+                Some(ident.name.clone())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Parses the file and serializes the tree - spans included - as JSON.
+pub fn emit_ast(args: &Args) -> Result<String, Vec<Error>> {
+    let file = PathBuf::from(args.args.first().expect("No file to run"));
+    let tree = sylt_parser::tree(&file, read_file)?;
+    Ok(serde_json::to_string_pretty(&tree).expect("Failed to serialize the tree"))
+}
+
+/// Parses the file and every file it uses, and lists the files it depends
+/// on - directly or through other files - sorted, one per line.
+pub fn dump_deps(args: &Args) -> Result<String, Vec<Error>> {
+    let file = PathBuf::from(args.args.first().expect("No file to run"));
+    let tree = sylt_parser::tree(&file, read_file)?;
+    let deps: BTreeSet<_> = tree
+        .modules
+        .iter()
+        .map(|(path, _)| path)
+        .filter(|path| **path != file)
+        .map(|path| path.display().to_string())
+        .collect();
+    Ok(deps.into_iter().collect::<Vec<_>>().join("\n"))
 }
 
 // TODO(ed): This name isn't true anymore - since it can compile
@@ -53,11 +136,11 @@ pub fn run_file_with_reader<R>(
     args: &Args,
     functions: ExternFunctionList,
     reader: R,
-) -> Result<(), Vec<Error>>
+) -> Result<Vec<Error>, Vec<Error>>
 where
     R: Fn(&Path) -> Result<String, Error>,
 {
-    match (&args.lua_run, &args.lua_compile) {
+    let warnings = match (&args.lua_run, &args.lua_compile) {
         (true, _) => {
             use std::process::{Command, Stdio};
             let mut child = Command::new("lua")
@@ -66,7 +149,8 @@ where
                 .spawn()
                 .expect("Failed to start lua - make sure it's installed correctly");
             let stdin = child.stdin.take().unwrap();
-            match compile_with_reader_to_writer(args, functions, reader, Some(Box::new(stdin)))? {
+            let (prog, warnings) = compile_with_reader_to_writer(args, functions, reader, Some(Box::new(stdin)))?;
+            match prog {
                 Prog::Lua => {
                     let output = child.wait_with_output().unwrap();
                     // NOTE(ed): Status is always 0 when piping to STDIN, atleast on my version of lua,
@@ -77,12 +161,14 @@ where
                 }
                 Prog::Bytecode(_) => unreachable!(),
             };
+            warnings
         }
 
         (false, Some(s)) if s == "%" => {
             use std::io;
             // NOTE(ed): Lack of running
-            compile_with_reader_to_writer(args, functions, reader, Some(Box::new(io::stdout())))?;
+            let (_, warnings) = compile_with_reader_to_writer(args, functions, reader, Some(Box::new(io::stdout())))?;
+            warnings
         }
 
         (false, Some(s)) => {
@@ -90,22 +176,25 @@ where
             let file = File::create(PathBuf::from(s)).expect(&format!("Failed to create file: {}", s));
             let writer: Option<Box<dyn Write>> = Some(Box::new(file));
             // NOTE(ed): Lack of running
-            compile_with_reader_to_writer(args, functions, reader, writer)?;
+            let (_, warnings) = compile_with_reader_to_writer(args, functions, reader, writer)?;
+            warnings
         }
 
         (_, _) => {
-            match compile_with_reader_to_writer(args, functions, reader, None)? {
+            let (prog, warnings) = compile_with_reader_to_writer(args, functions, reader, None)?;
+            match prog {
                 Prog::Bytecode(prog) => run(&prog, &args)?,
                 Prog::Lua => unreachable!(),
             };
+            warnings
         }
     };
-    Ok(())
+    Ok(warnings)
 }
 
 /// Compiles, links and runs the given file. The supplied functions are callable
-/// external functions.
-pub fn run_file(args: &Args, functions: ExternFunctionList) -> Result<(), Vec<Error>> {
+/// external functions. Returns the warnings if it all went well.
+pub fn run_file(args: &Args, functions: ExternFunctionList) -> Result<Vec<Error>, Vec<Error>> {
     run_file_with_reader(args, functions, read_file)
 }
 
@@ -113,6 +202,12 @@ pub fn run(prog: &BytecodeProg, args: &Args) -> Result<(), Vec<Error>> {
     let mut vm = sylt_machine::VM::new();
     vm.print_bytecode = args.verbosity >= 1;
     vm.print_exec = args.verbosity >= 2;
+    if let Some(depth) = args.max_call_depth {
+        vm.max_call_depth = depth;
+    }
+    vm.max_loop_iterations = args.max_loop_iterations;
+    // A seed from an earlier run on the same thread mustn't carry over.
+    sylt_std::sylt::seed_random(args.seed);
     vm.init(&prog, &args.args);
     if let Err(e) = vm.run() {
         Err(vec![e])
@@ -152,6 +247,111 @@ pub struct Args {
     )]
     pub format: bool,
 
+    #[options(
+        long = "align-blob-fields",
+        no_short,
+        help = "Pad the field names of blobs so the colons line up when formatting."
+    )]
+    pub align_blob_fields: bool,
+
+    #[options(
+        long = "canonical-unions",
+        no_short,
+        help = "Sort the members of union types, with void last as `?`, when formatting."
+    )]
+    pub canonical_unions: bool,
+
+    #[options(
+        long = "emit-ast",
+        no_short,
+        help = "Print the parsed tree, with the span of every node, as JSON to stdout."
+    )]
+    pub emit_ast: bool,
+
+    #[options(
+        long = "ast-diff",
+        no_short,
+        help = "Compare the structure of two files and print what differs, ignoring formatting and comments."
+    )]
+    pub ast_diff: bool,
+
+    #[options(
+        long = "dump-deps",
+        no_short,
+        help = "Print every file the supplied file uses, directly or through other files."
+    )]
+    pub dump_deps: bool,
+
+    #[options(
+        long = "max-call-depth",
+        no_short,
+        help = "The maximum number of nested function calls before a stack overflow error, defaults to 10000."
+    )]
+    pub max_call_depth: Option<usize>,
+
+    #[options(
+        long = "max-loop-iterations",
+        no_short,
+        help = "Stop the program if its loops start over more than this many times in total."
+    )]
+    pub max_loop_iterations: Option<usize>,
+
+    #[options(
+        long = "seed",
+        no_short,
+        help = "Seeds the random numbers so the run can be reproduced. Sets and dicts always iterate in insertion order."
+    )]
+    pub seed: Option<u64>,
+
+    #[options(
+        long = "deny-warnings",
+        no_short,
+        help = "Fail if there are any warnings"
+    )]
+    pub deny_warnings: bool,
+
+    #[options(
+        long = "debug-checks",
+        no_short,
+        help = "Check forced types when the program runs, only without lua"
+    )]
+    pub debug_checks: bool,
+
+    #[options(
+        long = "optimize",
+        no_short,
+        help = "Replace reads of constants bound to literals with the literals"
+    )]
+    pub optimize: bool,
+
+    #[options(
+        long = "optimize-report",
+        no_short,
+        help = "Optimize, and print every read of a constant that was replaced"
+    )]
+    pub optimize_report: bool,
+
+    #[options(
+        long = "functions",
+        no_short,
+        help = "List the functions marked with @export, which --run-function can run"
+    )]
+    pub list_functions: bool,
+
+    #[options(
+        long = "run-function",
+        no_short,
+        help = "Start the program in this function marked with @export instead of start"
+    )]
+    pub run_function: Option<String>,
+
+    #[options(
+        long = "explain",
+        no_short,
+        help = "Explain an error code, e.g. E0103"
+    )]
+    pub explain: Option<String>,
+
     #[options(help = "Print this help")]
     pub help: bool,
 
@@ -176,29 +376,315 @@ pub fn path_to_module(current_file: &Path, module: &str) -> PathBuf {
 #[macro_export]
 macro_rules! assert_errs {
     ($result:expr, $expect:pat) => {
+        $crate::assert_errs!($result, $expect if true);
+    };
+    ($result:expr, $expect:pat if $guard:expr) => {
         let errs = $result.err().unwrap_or(Vec::new());
 
         #[allow(unused_imports)]
         use sylt_common::error::Error;
         #[allow(unused_imports)]
         use sylt_tokenizer::Span;
-        if !matches!(errs.as_slice(), $expect) {
+        if !matches!(errs.as_slice(), $expect if $guard) {
             eprintln!("===== Got =====");
             for err in errs {
                 eprint!("{}", err);
             }
             eprintln!("===== Expect =====");
-            eprint!("{}\n\n", stringify!($expect));
+            eprint!("{} if {}\n\n", stringify!($expect), stringify!($guard));
             assert!(false);
         }
     };
 }
 
+#[cfg(test)]
+mod emit_ast {
+    #[test]
+    fn golden() {
+        let mut args = crate::Args::default();
+        args.args = vec!["../tests/emit_ast/small.sy".to_string()];
+        let emitted = crate::emit_ast(&args).unwrap();
+        let golden = std::fs::read_to_string("../tests/emit_ast/small.json").unwrap();
+        assert_eq!(emitted, golden.trim_end());
+    }
+}
+
+#[cfg(test)]
+mod dump_deps {
+    #[test]
+    fn chain() {
+        let mut args = crate::Args::default();
+        args.args = vec!["../tests/dump_deps/chain.sy".to_string()];
+        let deps = crate::dump_deps(&args).unwrap();
+        assert_eq!(
+            deps.lines().collect::<Vec<_>>(),
+            vec!["../tests/dump_deps/_leaf.sy", "../tests/dump_deps/_middle.sy"]
+        );
+    }
+
+    #[test]
+    fn no_uses() {
+        let mut args = crate::Args::default();
+        args.args = vec!["../tests/dump_deps/_leaf.sy".to_string()];
+        assert_eq!(crate::dump_deps(&args).unwrap(), "");
+    }
+}
+
+/// Runs a file from the tests directory with the standard library, after
+/// `set` has changed the arguments.
+#[cfg(test)]
+fn run_test_file(file: &str, set: impl FnOnce(&mut Args)) -> Result<Vec<Error>, Vec<Error>> {
+    let mut args = Args::default();
+    args.args = vec![format!("../tests/{}", file)];
+    set(&mut args);
+    run_file(&args, lib_bindings())
+}
+
+#[cfg(test)]
+mod stack_trace {
+    use sylt_common::error::Error;
+
+    #[test]
+    fn lists_every_call() {
+        let errs = crate::run_test_file("core/stack_trace.sy", |_| {}).unwrap_err();
+        let trace = match errs.as_slice() {
+            [Error::RuntimeError { trace, .. }] => trace,
+            errs => panic!("expected one runtime error, got {:?}", errs),
+        };
+        let calls: Vec<_> = trace
+            .iter()
+            .map(|frame| (frame.function.split(' ').nth(1).unwrap(), frame.line))
+            .collect();
+        assert_eq!(calls, vec![("inner", 2), ("middle", 6), ("outer", 10), ("start", 14)]);
+        assert!(format!("{}", errs[0]).contains("in fn middle"));
+    }
+}
+
+#[cfg(test)]
+mod debug_checks {
+    use sylt_common::error::RuntimeError;
+
+    #[test]
+    fn forced_type_is_checked() {
+        assert!(crate::run_test_file("core/_debug_checks_forced.sy", |_| {}).is_ok());
+        assert_errs!(
+            crate::run_test_file("core/_debug_checks_forced.sy", |args| args.debug_checks = true),
+            [Error::RuntimeError { kind: RuntimeError::TypeAssertion(..), .. }]
+        );
+    }
+
+    #[test]
+    fn forced_nil_is_checked() {
+        assert!(crate::run_test_file("core/_debug_checks_nil.sy", |_| {}).is_ok());
+        assert_errs!(
+            crate::run_test_file("core/_debug_checks_nil.sy", |args| args.debug_checks = true),
+            [Error::RuntimeError { kind: RuntimeError::TypeAssertion(..), .. }]
+        );
+    }
+
+    #[test]
+    fn out_of_bounds_is_an_error() {
+        assert_errs!(
+            crate::run_test_file("core/_debug_checks_bounds.sy", |args| args.debug_checks = true),
+            [Error::RuntimeError { kind: RuntimeError::IndexOutOfBounds(..), .. }]
+        );
+    }
+
+    #[test]
+    fn lua_is_rejected() {
+        let mut args = crate::Args::default();
+        args.args = vec!["../tests/core/_debug_checks_forced.sy".to_string()];
+        args.debug_checks = true;
+        let writer: Option<Box<dyn std::io::Write>> = Some(Box::new(Vec::new()));
+        assert_errs!(
+            crate::compile_with_reader_to_writer(&args, crate::lib_bindings(), crate::read_file, writer),
+            [Error::CompileError { message: Some(message), .. }] if message.contains("--debug-checks")
+        );
+    }
+}
+
+#[cfg(test)]
+mod equality {
+    use sylt_common::error::RuntimeError;
+    use sylt_common::Op;
+
+    #[test]
+    fn different_types_error_without_typechecking() {
+        assert_errs!(
+            crate::run_test_file("core/_equality_unchecked.sy", |args| args.skip_typecheck = true),
+            [Error::RuntimeError { kind: RuntimeError::TypeError(Op::Equal, _), .. }]
+        );
+    }
+}
+
+#[cfg(test)]
+mod max_loop_iterations {
+    use sylt_common::error::RuntimeError;
+
+    #[test]
+    fn infinite_loop_stops() {
+        assert_errs!(
+            crate::run_test_file("looping/_infinite_loop.sy", |args| args.max_loop_iterations = Some(100)),
+            [Error::RuntimeError { kind: RuntimeError::ExecutionLimitExceeded(100), .. }]
+        );
+    }
+
+    #[test]
+    fn finite_loop_runs() {
+        // The loop in the file starts over twice.
+        assert!(crate::run_test_file("looping/simple.sy", |args| args.max_loop_iterations = Some(2)).is_ok());
+        assert!(crate::run_test_file("looping/simple.sy", |args| args.max_loop_iterations = Some(1)).is_err());
+    }
+}
+
+#[cfg(test)]
+mod deny_warnings {
+    use sylt_common::error::Error;
+
+    #[test]
+    fn warnings_are_returned() {
+        let warnings = crate::run_test_file("dead_code/after_ret.sy", |_| {}).unwrap();
+        assert!(matches!(warnings.as_slice(), [Error::Warning { .. }]));
+    }
+
+    #[test]
+    fn warnings_fail_the_run() {
+        assert!(crate::run_test_file("dead_code/after_ret.sy", |_| {}).is_ok());
+        assert_errs!(
+            crate::run_test_file("dead_code/after_ret.sy", |args| args.deny_warnings = true),
+            [Error::Warning { span: Span { line: 3, .. }, .. }]
+        );
+    }
+}
+
+#[cfg(test)]
+mod optimize {
+    use std::path::PathBuf;
+
+    fn propagated(file: &str) -> Vec<(String, usize)> {
+        let path = PathBuf::from(format!("../tests/optimize/{}", file));
+        let mut tree = sylt_parser::tree(&path, crate::read_file).unwrap();
+        let propagations = sylt_compiler::optimize(&mut tree).unwrap();
+        assert!(crate::run_test_file(&format!("optimize/{}", file), |args| args.optimize = true).is_ok());
+
+        propagations.into_iter().map(|p| (p.name, p.span.line)).collect()
+    }
+
+    #[test]
+    fn constants_are_propagated() {
+        let expected = [("SIZE", 2), ("SIZE", 2), ("AREA", 10), ("GREETING", 11), ("SIZE", 13), ("local", 14)];
+        let expected: Vec<_> = expected.iter().map(|(name, line)| (name.to_string(), *line)).collect();
+        assert_eq!(propagated("propagated_constant.sy"), expected);
+    }
+
+    #[test]
+    fn mutable_variables_are_left_alone() {
+        assert!(propagated("mutable_left_alone.sy").is_empty());
+    }
+}
+
+#[cfg(test)]
+mod seed {
+    use std::cell::RefCell;
+    use sylt_common::error::RuntimeError;
+    use sylt_common::{RuntimeContext, Value};
+
+    thread_local! {
+        static RECORDED: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn record(ctx: RuntimeContext) -> Result<Value, RuntimeError> {
+        let values = ctx.machine.stack_from_base(ctx.stack_base);
+        let line = values.iter().map(|v| format!("{}", v)).collect::<Vec<_>>().join(" ");
+        // Blobs print their address, which differs between runs.
+        let line = line.split(" (0x").next().unwrap().to_string();
+        RECORDED.with(|recorded| recorded.borrow_mut().push(line));
+        Ok(Value::Nil)
+    }
+
+    fn run(seed: Option<u64>) -> Vec<String> {
+        let mut args = crate::Args::default();
+        args.args = vec!["../tests/core/_seeded.sy".to_string()];
+        args.seed = seed;
+        let mut functions = crate::lib_bindings();
+        functions.push(("record".to_string(), record, "fn #X -> void".to_string()));
+        crate::run_file(&args, functions).unwrap();
+        RECORDED.with(|recorded| recorded.borrow_mut().drain(..).collect())
+    }
+
+    #[test]
+    fn same_seed_same_output() {
+        let first = run(Some(1234));
+        assert_eq!(first.len(), 4);
+        assert_eq!(first, run(Some(1234)));
+        assert_ne!(first, run(Some(4321)));
+    }
+
+    #[test]
+    fn seed_is_forgotten_by_the_next_run() {
+        run(Some(1234));
+        assert_ne!(run(None), run(None));
+    }
+}
+
+#[cfg(test)]
+mod extern_functions {
+    use std::path::Path;
+    use sylt_common::prog::Prog;
+    use sylt_common::Value;
+
+    #[test]
+    fn find_by_name() {
+        let mut args = crate::Args::default();
+        args.args = vec!["extern_functions.sy".to_string()];
+        let functions = sylt_std::sylt::_sylt_link();
+        let len = functions.iter().position(|(name, _, _)| name == "len").unwrap();
+        let reader = |_: &Path| Ok("start :: fn do end\n".to_string());
+        let prog = match crate::compile_with_reader_to_writer(&args, functions, reader, None) {
+            Ok((Prog::Bytecode(prog), _)) => prog,
+            _ => unreachable!(),
+        };
+        assert_eq!(prog.extern_function("len"), Some(len));
+        assert_eq!(prog.extern_function("not_a_function"), None);
+
+        let mut vm = sylt_machine::VM::new();
+        vm.init(&prog, &[]);
+        assert!(matches!(vm.extern_function("len"), Some(Value::ExternFunction(slot)) if slot == len));
+    }
+}
+
+#[cfg(test)]
+mod export {
+    use sylt_common::error::Error;
+
+    const FILE: &str = "export/entry_points.sy";
+
+    #[test]
+    fn only_exported_are_listed() {
+        let mut args = crate::Args::default();
+        args.args = vec![format!("../tests/{}", FILE)];
+        assert_eq!(crate::exported_functions(&args).unwrap(), vec!["greet"]);
+    }
+
+    #[test]
+    fn run_exported() {
+        assert!(crate::run_test_file(FILE, |args| args.run_function = Some("greet".to_string())).is_ok());
+    }
+
+    #[test]
+    fn run_internal_is_an_error() {
+        match crate::run_test_file(FILE, |args| args.run_function = Some("helper".to_string())).unwrap_err().as_slice() {
+            [Error::CompileError { message: Some(message), .. }] => assert!(message.contains("helper")),
+            errs => panic!("expected a compile error, got {:?}", errs),
+        }
+    }
+}
+
 #[cfg(test)]
 mod bytecode {
     #[macro_export]
     macro_rules! test_file_run {
-        ($fn:ident, $path:literal, $print:expr, $errs:pat, $_:expr) => {
+        ($fn:ident, $path:literal, $print:expr, $errs:pat, $_:expr, $_lua:expr) => {
             #[test]
             fn $fn() {
                 #[allow(unused_imports)]
@@ -224,7 +710,9 @@ mod bytecode {
 mod lua {
     #[macro_export]
     macro_rules! test_file_lua {
-        ($fn:ident, $path:literal, $print:expr, $errs:pat, $any_runtime_errors:expr) => {
+        // Tests flagged with `no_lua` use something the lua-compiler doesn't support.
+        ($fn:ident, $path:literal, $print:expr, $errs:pat, $any_runtime_errors:expr, false) => {};
+        ($fn:ident, $path:literal, $print:expr, $errs:pat, $any_runtime_errors:expr, true) => {
             #[test]
             fn $fn() {
                 use std::io::Write;
@@ -7,6 +7,16 @@ fn main() -> Result<(), String> {
         return Ok(());
     }
 
+    if let Some(code) = &args.explain {
+        return match sylt_common::error::explain(code) {
+            Some(explanation) => {
+                print!("{}", explanation);
+                Ok(())
+            }
+            None => Err(format!("'{}' is not an error code", code)),
+        };
+    }
+
     let errs = if args.format {
         match sylt::formatter::format(&args) {
             Ok(formatted) => {
@@ -15,8 +25,56 @@ fn main() -> Result<(), String> {
             }
             Err(errs) => errs,
         }
+    } else if args.dump_deps {
+        match sylt::dump_deps(&args) {
+            Ok(deps) => {
+                if !deps.is_empty() {
+                    println!("{}", deps);
+                }
+                Vec::new()
+            }
+            Err(errs) => errs,
+        }
+    } else if args.ast_diff {
+        match sylt::ast_diff::ast_diff(&args) {
+            Ok(Some(diff)) => {
+                print!("{}", diff);
+                Vec::new()
+            }
+            Ok(None) => {
+                println!("No changes");
+                Vec::new()
+            }
+            Err(errs) => errs,
+        }
+    } else if args.list_functions {
+        match sylt::exported_functions(&args) {
+            Ok(functions) => {
+                for function in functions {
+                    println!("{}", function);
+                }
+                Vec::new()
+            }
+            Err(errs) => errs,
+        }
+    } else if args.emit_ast {
+        match sylt::emit_ast(&args) {
+            Ok(json) => {
+                println!("{}", json);
+                Vec::new()
+            }
+            Err(errs) => errs,
+        }
     } else {
-        sylt::run_file(&args, lib_bindings()).err().unwrap_or_else(Vec::new)
+        match sylt::run_file(&args, lib_bindings()) {
+            Ok(warnings) => {
+                for warning in warnings {
+                    eprint!("{}", warning);
+                }
+                Vec::new()
+            }
+            Err(errs) => errs,
+        }
     };
 
     if errs.is_empty() {